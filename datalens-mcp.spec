@@ -12,7 +12,7 @@ BuildRequires:  rust
 
 %description
 datalens-mcp is a Model Context Protocol (MCP) server for Yandex DataLens.
-It exposes DataLens RPC methods as MCP tools over stdio transport.
+It exposes DataLens RPC methods as MCP tools over stdio or streamable HTTP transport.
 
 %prep
 %setup -q
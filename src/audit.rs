@@ -0,0 +1,473 @@
+use std::{
+    ffi::OsString,
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, Write as _},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use ring::digest;
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use tracing::warn;
+
+use crate::{env_flag, env_non_empty, parse_non_negative, policy::glob_match};
+
+const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+const DEFAULT_MAX_FILES: u64 = 5;
+/// Entries returned by a query when no `limit` is given, and the most it may ask for.
+pub(crate) const DEFAULT_QUERY_LIMIT: usize = 50;
+pub(crate) const MAX_QUERY_LIMIT: usize = 500;
+
+/// Payload keys whose values are never written to the log.
+const SECRET_KEY_PARTS: [&str; 5] = ["token", "password", "secret", "privatekey", "apikey"];
+
+tokio::task_local! {
+    static CALL_ORIGIN: CallOrigin;
+}
+
+/// Where `DATALENS_AUDIT_LOG` entries go and how large the file may grow.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct AuditConfig {
+    pub(crate) path: PathBuf,
+    pub(crate) include_payload: bool,
+    /// Size at which the file is rotated; `0` never rotates.
+    pub(crate) max_bytes: u64,
+    /// Rotated files kept next to the live one (`audit.jsonl.1` is the newest).
+    pub(crate) max_files: u32,
+}
+
+impl AuditConfig {
+    pub(crate) fn from_env() -> Option<Self> {
+        Some(Self {
+            path: PathBuf::from(env_non_empty("DATALENS_AUDIT_LOG")?),
+            include_payload: env_flag("DATALENS_AUDIT_LOG_PAYLOADS"),
+            max_bytes: parse_non_negative("DATALENS_AUDIT_LOG_MAX_BYTES", DEFAULT_MAX_BYTES),
+            max_files: parse_non_negative("DATALENS_AUDIT_LOG_MAX_FILES", DEFAULT_MAX_FILES)
+                .min(u64::from(u32::MAX)) as u32,
+        })
+    }
+
+    /// `audit.jsonl.<index>`; `1` is the newest rotated file.
+    fn rotated(&self, index: u32) -> PathBuf {
+        let mut path = OsString::from(self.path.as_os_str());
+        path.push(format!(".{index}"));
+        PathBuf::from(path)
+    }
+}
+
+/// The MCP client and tool on whose behalf `call_rpc` runs.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CallOrigin {
+    pub(crate) client: Option<String>,
+    pub(crate) tool: Option<String>,
+}
+
+pub(crate) async fn with_origin<F: Future>(origin: CallOrigin, future: F) -> F::Output {
+    CALL_ORIGIN.scope(origin, future).await
+}
+
+fn current_origin() -> CallOrigin {
+    CALL_ORIGIN.try_with(Clone::clone).unwrap_or_default()
+}
+
+/// How a call ended, as recorded in the log.
+pub(crate) enum Outcome {
+    Ok {
+        http_status: u64,
+    },
+    DryRun,
+    Error {
+        message: String,
+        http_status: Option<u64>,
+    },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry<'a> {
+    ts: String,
+    profile: &'a str,
+    method: &'a str,
+    category: &'a str,
+    payload_sha256: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload: Option<Value>,
+    outcome: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    http_status: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    latency_ms: u64,
+    client: Option<String>,
+    tool: Option<String>,
+}
+
+/// Append-only JSON lines log of RPC calls, rotated by size. A dedicated thread writes the
+/// entries, so recording a call never waits on the disk.
+pub(crate) struct AuditLog {
+    cfg: AuditConfig,
+    writer: mpsc::Sender<Command>,
+}
+
+enum Command {
+    Append(String),
+    /// Answered once every entry sent before it is written.
+    Flush(mpsc::Sender<()>),
+}
+
+/// The writer thread's side of the log; it owns every write, rename and removal.
+struct LogFile {
+    cfg: AuditConfig,
+}
+
+/// One `call_rpc` call, as seen by the audit log.
+pub(crate) struct Record<'a> {
+    pub(crate) profile: &'a str,
+    pub(crate) method: &'a str,
+    pub(crate) category: &'a str,
+    pub(crate) payload: &'a Value,
+    pub(crate) outcome: Outcome,
+    pub(crate) latency: Duration,
+}
+
+/// Filters of `datalens_audit_log_query`; unset fields match every entry.
+#[derive(Debug, Default)]
+pub(crate) struct Query {
+    /// Method name or glob.
+    pub(crate) method: Option<String>,
+    pub(crate) profile: Option<String>,
+    pub(crate) tool: Option<String>,
+    pub(crate) outcome: Option<String>,
+    pub(crate) since: Option<DateTime<Utc>>,
+    pub(crate) limit: usize,
+}
+
+impl AuditLog {
+    pub(crate) fn new(cfg: AuditConfig) -> Self {
+        let (writer, commands) = mpsc::channel();
+        let file = LogFile { cfg: cfg.clone() };
+        thread::spawn(move || file.run(commands));
+        Self { cfg, writer }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.cfg.path
+    }
+
+    /// Queues one entry for the writer thread. Write failures are logged and never fail the
+    /// call itself.
+    pub(crate) fn record(&self, record: Record<'_>) {
+        let origin = current_origin();
+        let (outcome, http_status, error) = match record.outcome {
+            Outcome::Ok { http_status } => ("ok", Some(http_status), None),
+            Outcome::DryRun => ("dry_run", None, None),
+            Outcome::Error {
+                message,
+                http_status,
+            } => ("error", http_status, Some(message)),
+        };
+        let payload = redact_secrets(record.payload);
+        let entry = Entry {
+            ts: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            profile: record.profile,
+            method: record.method,
+            category: record.category,
+            payload_sha256: sha256_hex(&payload),
+            payload: self.cfg.include_payload.then_some(payload),
+            outcome,
+            http_status,
+            error,
+            latency_ms: record.latency.as_millis().min(u128::from(u64::MAX)) as u64,
+            client: origin.client,
+            tool: origin.tool,
+        };
+        let line = match serde_json::to_string(&entry) {
+            Ok(line) => line,
+            Err(error) => {
+                warn!(%error, "failed to serialize audit log entry");
+                return;
+            }
+        };
+        if self.writer.send(Command::Append(line)).is_err() {
+            warn!(path = %self.cfg.path.display(), "audit log writer has stopped; entry dropped");
+        }
+    }
+
+    /// Waits until every entry recorded so far is on disk. Blocks the calling thread.
+    fn flush(&self) {
+        let (done, wait) = mpsc::channel();
+        if self.writer.send(Command::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+    }
+
+    /// Matching entries, newest first, from the live file and then the rotated ones.
+    /// Waits for pending writes first, so it blocks; call it off the runtime.
+    pub(crate) fn query(&self, query: &Query) -> io::Result<Vec<Value>> {
+        self.flush();
+        let mut found = Vec::new();
+        let files = std::iter::once(self.cfg.path.clone())
+            .chain((1..=self.cfg.max_files).map(|index| self.cfg.rotated(index)));
+        for path in files {
+            let file = match fs::File::open(&path) {
+                Ok(file) => file,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(error),
+            };
+            let mut entries = Vec::new();
+            let mut reached_since = false;
+            for line in BufReader::new(file).lines() {
+                let Ok(Value::Object(entry)) = serde_json::from_str::<Value>(&line?) else {
+                    continue;
+                };
+                if query
+                    .since
+                    .is_some_and(|since| entry_time(&entry).is_some_and(|ts| ts < since))
+                {
+                    reached_since = true;
+                    continue;
+                }
+                if query.matches(&entry) {
+                    entries.push(Value::Object(entry));
+                }
+            }
+            found.extend(entries.into_iter().rev());
+            // Older files only hold older entries.
+            if found.len() >= query.limit || reached_since {
+                break;
+            }
+        }
+        found.truncate(query.limit);
+        Ok(found)
+    }
+}
+
+impl LogFile {
+    /// Writes entries until every `AuditLog` handle is dropped.
+    fn run(self, commands: mpsc::Receiver<Command>) {
+        for command in commands {
+            match command {
+                Command::Append(line) => {
+                    if let Err(error) = self.append(&line) {
+                        warn!(path = %self.cfg.path.display(), %error, "failed to write audit log entry");
+                    }
+                }
+                Command::Flush(done) => {
+                    let _ = done.send(());
+                }
+            }
+        }
+    }
+
+    fn append(&self, line: &str) -> io::Result<()> {
+        let size = fs::metadata(&self.cfg.path).map_or(0, |meta| meta.len());
+        if self.cfg.max_bytes > 0 && size > 0 && size + line.len() as u64 + 1 > self.cfg.max_bytes {
+            self.rotate()?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.cfg.path)?;
+        writeln!(file, "{line}")
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        if self.cfg.max_files == 0 {
+            return fs::remove_file(&self.cfg.path);
+        }
+        for index in (1..self.cfg.max_files).rev() {
+            match fs::rename(self.cfg.rotated(index), self.cfg.rotated(index + 1)) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                _ => {}
+            }
+        }
+        fs::rename(&self.cfg.path, self.cfg.rotated(1))
+    }
+}
+
+impl Query {
+    fn matches(&self, entry: &Map<String, Value>) -> bool {
+        let field = |name: &str| entry.get(name).and_then(Value::as_str).unwrap_or("");
+        self.method
+            .as_deref()
+            .is_none_or(|pattern| glob_match(pattern, field("method")))
+            && self
+                .profile
+                .as_deref()
+                .is_none_or(|profile| profile == field("profile"))
+            && self
+                .tool
+                .as_deref()
+                .is_none_or(|tool| tool == field("tool"))
+            && self
+                .outcome
+                .as_deref()
+                .is_none_or(|outcome| outcome == field("outcome"))
+    }
+}
+
+fn entry_time(entry: &Map<String, Value>) -> Option<DateTime<Utc>> {
+    let ts = entry.get("ts")?.as_str()?;
+    DateTime::parse_from_rfc3339(ts)
+        .ok()
+        .map(|ts| ts.with_timezone(&Utc))
+}
+
+fn sha256_hex(payload: &Value) -> String {
+    let digest = digest::digest(&digest::SHA256, payload.to_string().as_bytes());
+    digest
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Replaces values of credential-like keys (`token`, `password`, `secret`, ...) at any depth.
+fn redact_secrets(value: &Value) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(key, value)| {
+                    let normalized = key.to_ascii_lowercase().replace(['_', '-'], "");
+                    let value = if SECRET_KEY_PARTS
+                        .iter()
+                        .any(|part| normalized.contains(part))
+                    {
+                        json!("[redacted]")
+                    } else {
+                        redact_secrets(value)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact_secrets).collect()),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_in(name: &str, max_bytes: u64, include_payload: bool) -> AuditLog {
+        let dir =
+            std::env::temp_dir().join(format!("datalens-mcp-audit-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        AuditLog::new(AuditConfig {
+            path: dir.join("audit.jsonl"),
+            include_payload,
+            max_bytes,
+            max_files: 2,
+        })
+    }
+
+    fn record<'a>(method: &'a str, payload: &'a Value) -> Record<'a> {
+        Record {
+            profile: "default",
+            method,
+            category: "write",
+            payload,
+            outcome: Outcome::Ok { http_status: 200 },
+            latency: Duration::from_millis(12),
+        }
+    }
+
+    #[tokio::test]
+    async fn record_writes_redacted_entry_with_origin() {
+        let log = log_in("record", 0, true);
+        let payload =
+            json!({"type": "clickhouse", "password": "hunter2", "auth": {"oauth_token": "y0_x"}});
+        let origin = CallOrigin {
+            client: Some("codex 1.2".to_owned()),
+            tool: Some("datalens_create_connection".to_owned()),
+        };
+        with_origin(origin, async {
+            log.record(record("createConnection", &payload))
+        })
+        .await;
+
+        log.flush();
+        let raw = fs::read_to_string(log.path()).unwrap();
+        assert!(!raw.contains("hunter2") && !raw.contains("y0_x"));
+        let entry: Value = serde_json::from_str(raw.trim()).unwrap();
+        assert_eq!(entry["method"], "createConnection");
+        assert_eq!(entry["outcome"], "ok");
+        assert_eq!(entry["httpStatus"], 200);
+        assert_eq!(entry["latencyMs"], 12);
+        assert_eq!(entry["client"], "codex 1.2");
+        assert_eq!(entry["tool"], "datalens_create_connection");
+        assert_eq!(entry["payload"]["password"], "[redacted]");
+        assert_eq!(entry["payload"]["auth"]["oauth_token"], "[redacted]");
+        assert_eq!(entry["payloadSha256"].as_str().unwrap().len(), 64);
+        let _ = fs::remove_dir_all(log.path().parent().unwrap());
+    }
+
+    #[test]
+    fn record_omits_payload_unless_opted_in() {
+        let log = log_in("hash-only", 0, false);
+        let payload = json!({"datasetId": "ds-1"});
+        log.record(record("deleteDataset", &payload));
+        log.record(record("deleteDataset", &payload));
+
+        let entries = log
+            .query(&Query {
+                limit: 10,
+                ..Query::default()
+            })
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].get("payload").is_none());
+        assert_eq!(entries[0]["payloadSha256"], entries[1]["payloadSha256"]);
+        let _ = fs::remove_dir_all(log.path().parent().unwrap());
+    }
+
+    #[test]
+    fn rotation_keeps_bounded_files_and_query_reads_newest_first() {
+        let log = log_in("rotate", 400, false);
+        let payload = json!({});
+        for index in 0..12 {
+            log.record(record(&format!("method{index:02}"), &payload));
+        }
+
+        log.flush();
+        assert!(fs::metadata(log.path()).unwrap().len() <= 400);
+        assert!(log.cfg.rotated(1).exists() && log.cfg.rotated(2).exists());
+        assert!(!log.cfg.rotated(3).exists());
+
+        let entries = log
+            .query(&Query {
+                limit: 3,
+                ..Query::default()
+            })
+            .unwrap();
+        let methods: Vec<_> = entries
+            .iter()
+            .map(|entry| entry["method"].clone())
+            .collect();
+        assert_eq!(
+            methods,
+            [json!("method11"), json!("method10"), json!("method09")]
+        );
+
+        let entries = log
+            .query(&Query {
+                method: Some("method0?".to_owned()),
+                limit: MAX_QUERY_LIMIT,
+                ..Query::default()
+            })
+            .unwrap();
+        assert!(
+            entries
+                .iter()
+                .all(|entry| entry["method"].as_str().unwrap() < "method10")
+        );
+        let _ = fs::remove_dir_all(log.path().parent().unwrap());
+    }
+}
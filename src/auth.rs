@@ -0,0 +1,752 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::Client;
+use rmcp::ErrorData as McpError;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::{process::Command, sync::Mutex};
+use tracing::{debug, info};
+
+use crate::{parse_response_data, truncate_utf8};
+
+pub(crate) const DEFAULT_IAM_ENDPOINT: &str = "https://iam.api.cloud.yandex.net/iam/v1/tokens";
+
+/// Lifetime of the JWT signed with the service-account key. IAM accepts at most one hour.
+const SERVICE_ACCOUNT_JWT_LIFETIME: Duration = Duration::from_secs(3600);
+/// How long before `expiresAt` a cached IAM token is exchanged again.
+const IAM_TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(300);
+/// Upper bound for a single run of the configured token command.
+const TOKEN_COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Where the subject token for DataLens calls comes from, as read from configuration.
+#[derive(Clone, Debug)]
+pub(crate) enum TokenSource {
+    /// Token value from the environment. A value with the legacy `OAuth ` prefix is passed
+    /// through to DataLens as an OAuth token, anything else is treated as an IAM token.
+    Static(String),
+    ServiceAccountKey {
+        key_file: PathBuf,
+        iam_endpoint: String,
+    },
+    OAuthExchange {
+        oauth_token: String,
+        iam_endpoint: String,
+    },
+    Command {
+        command: String,
+        ttl: Duration,
+    },
+}
+
+impl TokenSource {
+    pub(crate) fn describe(&self) -> &'static str {
+        match self {
+            Self::Static(token) => match SubjectToken::from_static(token) {
+                SubjectToken::Iam(_) => "static IAM token",
+                SubjectToken::OAuth(_) => "static OAuth token (passed through)",
+            },
+            Self::ServiceAccountKey { .. } => "service account authorized key",
+            Self::OAuthExchange { .. } => "OAuth token exchanged for IAM tokens",
+            Self::Command { .. } => "external token command",
+        }
+    }
+}
+
+/// Credential attached to a DataLens request. The variant decides which header carries it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum SubjectToken {
+    /// IAM token, sent as `x-yacloud-subjecttoken: <token>`.
+    Iam(String),
+    /// Yandex OAuth token, sent as `x-dl-auth-token: OAuth <token>`.
+    OAuth(String),
+}
+
+impl SubjectToken {
+    fn from_static(value: &str) -> Self {
+        match value.strip_prefix("OAuth ") {
+            Some(oauth_token) => Self::OAuth(oauth_token.trim().to_owned()),
+            None => Self::Iam(value.to_owned()),
+        }
+    }
+
+    /// Header name and value used to authenticate a DataLens request.
+    pub(crate) fn header(&self) -> (&'static str, String) {
+        match self {
+            Self::Iam(token) => ("x-yacloud-subjecttoken", token.clone()),
+            Self::OAuth(token) => ("x-dl-auth-token", format!("OAuth {token}")),
+        }
+    }
+}
+
+/// Runtime token provider used by `call_rpc`. Caches obtained tokens and refreshes them
+/// before they expire or after DataLens rejects them.
+pub(crate) enum TokenProvider {
+    Static(SubjectToken),
+    ServiceAccount(ServiceAccountTokens),
+    OAuthExchange(OAuthExchangeTokens),
+    Command(CommandTokens),
+}
+
+impl TokenProvider {
+    pub(crate) fn from_source(source: &TokenSource) -> Result<Self> {
+        match source {
+            TokenSource::Static(token) => Ok(Self::Static(SubjectToken::from_static(token))),
+            TokenSource::ServiceAccountKey {
+                key_file,
+                iam_endpoint,
+            } => {
+                let key = ServiceAccountKey::from_file(key_file)?;
+                info!(
+                    key_id = %key.key_id,
+                    service_account_id = %key.service_account_id,
+                    iam_endpoint = %iam_endpoint,
+                    "using service account authorized key for IAM tokens"
+                );
+                Ok(Self::ServiceAccount(ServiceAccountTokens {
+                    key,
+                    iam_endpoint: iam_endpoint.clone(),
+                    cached: Mutex::new(None),
+                }))
+            }
+            TokenSource::OAuthExchange {
+                oauth_token,
+                iam_endpoint,
+            } => {
+                info!(
+                    iam_endpoint = %iam_endpoint,
+                    "using OAuth token exchange for IAM tokens"
+                );
+                Ok(Self::OAuthExchange(OAuthExchangeTokens {
+                    oauth_token: oauth_token
+                        .strip_prefix("OAuth ")
+                        .unwrap_or(oauth_token)
+                        .trim()
+                        .to_owned(),
+                    iam_endpoint: iam_endpoint.clone(),
+                    cached: Mutex::new(None),
+                }))
+            }
+            TokenSource::Command { command, ttl } => {
+                info!(
+                    ttl_seconds = ttl.as_secs(),
+                    "using external command for subject tokens"
+                );
+                Ok(Self::Command(CommandTokens {
+                    command: command.clone(),
+                    ttl: *ttl,
+                    cached: Mutex::new(None),
+                }))
+            }
+        }
+    }
+
+    pub(crate) async fn token(&self, http: &Client) -> Result<SubjectToken, McpError> {
+        match self {
+            Self::Static(token) => Ok(token.clone()),
+            Self::ServiceAccount(tokens) => tokens.token(http).await.map(SubjectToken::Iam),
+            Self::OAuthExchange(tokens) => tokens.token(http).await.map(SubjectToken::Iam),
+            Self::Command(tokens) => tokens.token().await.map(SubjectToken::Iam),
+        }
+    }
+
+    /// Drops the cached token so the next `token` call obtains a new one.
+    /// Returns `false` when the provider cannot produce a different token.
+    pub(crate) async fn invalidate(&self) -> bool {
+        match self {
+            Self::Static(_) => false,
+            Self::ServiceAccount(tokens) => {
+                *tokens.cached.lock().await = None;
+                true
+            }
+            Self::OAuthExchange(tokens) => {
+                *tokens.cached.lock().await = None;
+                true
+            }
+            Self::Command(tokens) => {
+                *tokens.cached.lock().await = None;
+                true
+            }
+        }
+    }
+}
+
+pub(crate) struct ServiceAccountTokens {
+    key: ServiceAccountKey,
+    iam_endpoint: String,
+    cached: Mutex<Option<CachedToken>>,
+}
+
+impl ServiceAccountTokens {
+    async fn token(&self, http: &Client) -> Result<String, McpError> {
+        let mut cached = self.cached.lock().await;
+        if let Some(token) = cached.as_ref().filter(|token| token.is_fresh()) {
+            return Ok(token.value.clone());
+        }
+
+        let jwt = self.key.sign_jwt(&self.iam_endpoint)?;
+        let token = exchange_iam_token(http, &self.iam_endpoint, json!({ "jwt": jwt })).await?;
+        let value = token.value.clone();
+        *cached = Some(token);
+
+        Ok(value)
+    }
+}
+
+pub(crate) struct OAuthExchangeTokens {
+    oauth_token: String,
+    iam_endpoint: String,
+    cached: Mutex<Option<CachedToken>>,
+}
+
+impl OAuthExchangeTokens {
+    async fn token(&self, http: &Client) -> Result<String, McpError> {
+        let mut cached = self.cached.lock().await;
+        if let Some(token) = cached.as_ref().filter(|token| token.is_fresh()) {
+            return Ok(token.value.clone());
+        }
+
+        let token = exchange_iam_token(
+            http,
+            &self.iam_endpoint,
+            json!({ "yandexPassportOauthToken": self.oauth_token }),
+        )
+        .await?;
+        let value = token.value.clone();
+        *cached = Some(token);
+
+        Ok(value)
+    }
+}
+
+pub(crate) struct CommandTokens {
+    command: String,
+    ttl: Duration,
+    cached: Mutex<Option<CachedToken>>,
+}
+
+impl CommandTokens {
+    async fn token(&self) -> Result<String, McpError> {
+        let mut cached = self.cached.lock().await;
+        if let Some(token) = cached.as_ref().filter(|token| token.is_fresh()) {
+            return Ok(token.value.clone());
+        }
+
+        let value = run_token_command(&self.command).await?;
+        *cached = Some(CachedToken {
+            value: value.clone(),
+            refresh_at: Instant::now() + self.ttl,
+        });
+
+        Ok(value)
+    }
+}
+
+struct CachedToken {
+    value: String,
+    refresh_at: Instant,
+}
+
+impl CachedToken {
+    fn is_fresh(&self) -> bool {
+        Instant::now() < self.refresh_at
+    }
+}
+
+/// Authorized key file as downloaded from Yandex Cloud (`yc iam key create --output key.json`).
+#[derive(Deserialize)]
+struct AuthorizedKeyFile {
+    id: String,
+    service_account_id: String,
+    private_key: String,
+}
+
+struct ServiceAccountKey {
+    key_id: String,
+    service_account_id: String,
+    encoding_key: EncodingKey,
+}
+
+impl ServiceAccountKey {
+    fn from_file(path: &Path) -> Result<Self> {
+        let raw = fs::read_to_string(path).with_context(|| {
+            format!("failed to read service account key file {}", path.display())
+        })?;
+        let file: AuthorizedKeyFile = serde_json::from_str(&raw).with_context(|| {
+            format!(
+                "service account key file {} is not a valid authorized key JSON",
+                path.display()
+            )
+        })?;
+
+        // Keys issued by Yandex Cloud prefix the PEM with a "PLEASE DO NOT REMOVE THIS LINE!" marker.
+        let pem_start = file.private_key.find("-----BEGIN").with_context(|| {
+            format!(
+                "private_key in {} does not contain a PEM block",
+                path.display()
+            )
+        })?;
+        let encoding_key = EncodingKey::from_rsa_pem(&file.private_key.as_bytes()[pem_start..])
+            .with_context(|| format!("private_key in {} is not an RSA key", path.display()))?;
+
+        Ok(Self {
+            key_id: file.id,
+            service_account_id: file.service_account_id,
+            encoding_key,
+        })
+    }
+
+    fn sign_jwt(&self, audience: &str) -> Result<String, McpError> {
+        let issued_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let claims = ServiceAccountClaims {
+            aud: audience,
+            iss: &self.service_account_id,
+            iat: issued_at,
+            exp: issued_at + SERVICE_ACCOUNT_JWT_LIFETIME.as_secs(),
+        };
+
+        let mut header = Header::new(Algorithm::PS256);
+        header.kid = Some(self.key_id.clone());
+
+        jsonwebtoken::encode(&header, &claims, &self.encoding_key).map_err(|error| {
+            McpError::internal_error(format!("failed to sign service account JWT: {error}"), None)
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct ServiceAccountClaims<'a> {
+    aud: &'a str,
+    iss: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IamTokenResponse {
+    iam_token: String,
+    expires_at: String,
+}
+
+async fn exchange_iam_token(
+    http: &Client,
+    iam_endpoint: &str,
+    request: Value,
+) -> Result<CachedToken, McpError> {
+    debug!(iam_endpoint = %iam_endpoint, "requesting IAM token");
+
+    let response = http
+        .post(iam_endpoint)
+        .json(&request)
+        .send()
+        .await
+        .map_err(|error| {
+            McpError::internal_error(format!("failed to reach IAM endpoint: {error}"), None)
+        })?;
+
+    let status = response.status();
+    let body = response.text().await.map_err(|error| {
+        McpError::internal_error(format!("failed to read IAM response: {error}"), None)
+    })?;
+
+    if !status.is_success() {
+        return Err(McpError::internal_error(
+            format!("IAM endpoint returned {status} while exchanging credentials"),
+            Some(json!({
+                "status": status.as_u16(),
+                "response": parse_response_data(&body),
+            })),
+        ));
+    }
+
+    let parsed = serde_json::from_str::<IamTokenResponse>(&body).map_err(|error| {
+        McpError::internal_error(format!("IAM endpoint returned invalid JSON: {error}"), None)
+    })?;
+    let expires_at = DateTime::parse_from_rfc3339(&parsed.expires_at).map_err(|error| {
+        McpError::internal_error(
+            format!(
+                "IAM endpoint returned invalid expiresAt `{}`: {error}",
+                parsed.expires_at
+            ),
+            None,
+        )
+    })?;
+
+    let lifetime = (expires_at.with_timezone(&Utc) - Utc::now())
+        .to_std()
+        .unwrap_or_default();
+    debug!(
+        expires_in_seconds = lifetime.as_secs(),
+        "received IAM token"
+    );
+
+    Ok(CachedToken {
+        value: parsed.iam_token,
+        refresh_at: Instant::now() + lifetime.saturating_sub(IAM_TOKEN_REFRESH_MARGIN),
+    })
+}
+
+fn shell_command(command: &str) -> Command {
+    #[cfg(windows)]
+    {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(command);
+        shell
+    }
+    #[cfg(not(windows))]
+    {
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(command);
+        shell
+    }
+}
+
+async fn run_token_command(command: &str) -> Result<String, McpError> {
+    debug!("running token command");
+
+    let mut shell = shell_command(command);
+    shell
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let output = tokio::time::timeout(TOKEN_COMMAND_TIMEOUT, shell.output())
+        .await
+        .map_err(|_| {
+            McpError::internal_error(
+                format!(
+                    "token command did not finish within {} seconds",
+                    TOKEN_COMMAND_TIMEOUT.as_secs()
+                ),
+                None,
+            )
+        })?
+        .map_err(|error| {
+            McpError::internal_error(format!("failed to run token command: {error}"), None)
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(McpError::internal_error(
+            format!("token command exited with {}", output.status),
+            Some(json!({ "stderr": truncate_utf8(stderr.trim(), 2000) })),
+        ));
+    }
+
+    let token = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    if token.is_empty() {
+        return Err(McpError::internal_error(
+            "token command printed an empty token",
+            None,
+        ));
+    }
+
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{DecodingKey, Validation};
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_json, method, path},
+    };
+
+    const TEST_KEY_FILE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/service-account-key.json"
+    );
+
+    #[derive(Deserialize)]
+    struct TestKeyFile {
+        public_key: String,
+    }
+
+    fn service_account_source(iam_endpoint: String) -> TokenSource {
+        TokenSource::ServiceAccountKey {
+            key_file: PathBuf::from(TEST_KEY_FILE),
+            iam_endpoint,
+        }
+    }
+
+    fn iam_response(token: &str, expires_in: chrono::Duration) -> Value {
+        json!({
+            "iamToken": token,
+            "expiresAt": (Utc::now() + expires_in).to_rfc3339(),
+        })
+    }
+
+    #[test]
+    fn service_account_jwt_is_ps256_signed_with_key_id() {
+        let key = ServiceAccountKey::from_file(Path::new(TEST_KEY_FILE))
+            .expect("fixture key must load despite the Yandex Cloud marker line");
+        let jwt = key
+            .sign_jwt(DEFAULT_IAM_ENDPOINT)
+            .expect("JWT must be signed");
+
+        let fixture: TestKeyFile =
+            serde_json::from_str(&fs::read_to_string(TEST_KEY_FILE).unwrap()).unwrap();
+        let decoding_key = DecodingKey::from_rsa_pem(fixture.public_key.as_bytes()).unwrap();
+        let mut validation = Validation::new(Algorithm::PS256);
+        validation.set_audience(&[DEFAULT_IAM_ENDPOINT]);
+        validation.set_issuer(&["ajetestserviceacct00"]);
+
+        let decoded = jsonwebtoken::decode::<Value>(&jwt, &decoding_key, &validation)
+            .expect("JWT must verify against the public key");
+        assert_eq!(decoded.header.kid.as_deref(), Some("ajetestkeyid0000000"));
+        assert_eq!(
+            decoded.claims["exp"].as_u64().unwrap() - decoded.claims["iat"].as_u64().unwrap(),
+            SERVICE_ACCOUNT_JWT_LIFETIME.as_secs()
+        );
+    }
+
+    #[test]
+    fn missing_key_file_fails_provider_construction() {
+        let source = TokenSource::ServiceAccountKey {
+            key_file: PathBuf::from("/nonexistent/key.json"),
+            iam_endpoint: DEFAULT_IAM_ENDPOINT.to_owned(),
+        };
+
+        assert!(TokenProvider::from_source(&source).is_err());
+    }
+
+    #[tokio::test]
+    async fn service_account_token_is_cached_until_refresh_margin() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/iam/v1/tokens"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(iam_response("iam-1", chrono::Duration::hours(12))),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = TokenProvider::from_source(&service_account_source(format!(
+            "{}/iam/v1/tokens",
+            mock_server.uri()
+        )))
+        .expect("provider must initialize");
+        let http = Client::new();
+
+        assert_eq!(
+            provider.token(&http).await.unwrap(),
+            SubjectToken::Iam("iam-1".to_owned())
+        );
+        assert_eq!(
+            provider.token(&http).await.unwrap(),
+            SubjectToken::Iam("iam-1".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn service_account_token_is_refreshed_before_expiry() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/iam/v1/tokens"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(iam_response("iam-short", chrono::Duration::minutes(1))),
+            )
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let provider = TokenProvider::from_source(&service_account_source(format!(
+            "{}/iam/v1/tokens",
+            mock_server.uri()
+        )))
+        .expect("provider must initialize");
+        let http = Client::new();
+
+        provider.token(&http).await.unwrap();
+        provider.token(&http).await.unwrap();
+    }
+
+    #[cfg(unix)]
+    fn counting_command(dir: &Path) -> String {
+        let counter = dir.join("runs");
+        format!(
+            "echo run >> '{0}' && echo \"cmd-token-$(wc -l < '{0}' | tr -d ' ')\"",
+            counter.display()
+        )
+    }
+
+    #[cfg(unix)]
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("datalens-mcp-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("scratch dir must be created");
+        dir
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn command_token_is_cached_for_ttl_and_rerun_after_invalidate() {
+        let dir = scratch_dir("command-ttl");
+        let provider = TokenProvider::from_source(&TokenSource::Command {
+            command: counting_command(&dir),
+            ttl: Duration::from_secs(3600),
+        })
+        .expect("provider must initialize");
+        let http = Client::new();
+
+        assert_eq!(
+            provider.token(&http).await.unwrap(),
+            SubjectToken::Iam("cmd-token-1".to_owned())
+        );
+        assert_eq!(
+            provider.token(&http).await.unwrap(),
+            SubjectToken::Iam("cmd-token-1".to_owned())
+        );
+        assert!(provider.invalidate().await);
+        assert_eq!(
+            provider.token(&http).await.unwrap(),
+            SubjectToken::Iam("cmd-token-2".to_owned())
+        );
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn command_token_is_rerun_once_ttl_elapses() {
+        let dir = scratch_dir("command-expired");
+        let provider = TokenProvider::from_source(&TokenSource::Command {
+            command: counting_command(&dir),
+            ttl: Duration::ZERO,
+        })
+        .expect("provider must initialize");
+        let http = Client::new();
+
+        assert_eq!(
+            provider.token(&http).await.unwrap(),
+            SubjectToken::Iam("cmd-token-1".to_owned())
+        );
+        assert_eq!(
+            provider.token(&http).await.unwrap(),
+            SubjectToken::Iam("cmd-token-2".to_owned())
+        );
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn failing_token_command_reports_stderr() {
+        let provider = TokenProvider::from_source(&TokenSource::Command {
+            command: "echo 'not logged in' >&2; exit 3".to_owned(),
+            ttl: Duration::from_secs(60),
+        })
+        .expect("provider must initialize");
+
+        let err = provider
+            .token(&Client::new())
+            .await
+            .expect_err("non-zero exit must fail");
+        assert_eq!(err.data.as_ref().unwrap()["stderr"], json!("not logged in"));
+    }
+
+    #[tokio::test]
+    async fn static_token_cannot_be_invalidated() {
+        let provider = TokenProvider::from_source(&TokenSource::Static("token".to_owned()))
+            .expect("provider must initialize");
+        assert!(!provider.invalidate().await);
+    }
+
+    #[test]
+    fn static_token_headers_depend_on_token_kind() {
+        assert_eq!(
+            SubjectToken::from_static("t1.iam").header(),
+            ("x-yacloud-subjecttoken", "t1.iam".to_owned())
+        );
+        assert_eq!(
+            SubjectToken::from_static("OAuth y0_oauth").header(),
+            ("x-dl-auth-token", "OAuth y0_oauth".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn oauth_token_is_exchanged_for_cached_iam_token() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/iam/v1/tokens"))
+            .and(body_json(json!({ "yandexPassportOauthToken": "y0_oauth" })))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(iam_response("iam-from-oauth", chrono::Duration::hours(12))),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = TokenProvider::from_source(&TokenSource::OAuthExchange {
+            oauth_token: "OAuth y0_oauth".to_owned(),
+            iam_endpoint: format!("{}/iam/v1/tokens", mock_server.uri()),
+        })
+        .expect("provider must initialize");
+        let http = Client::new();
+
+        for _ in 0..2 {
+            assert_eq!(
+                provider.token(&http).await.unwrap(),
+                SubjectToken::Iam("iam-from-oauth".to_owned())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn oauth_exchange_is_repeated_before_expiry() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(iam_response("iam-short", chrono::Duration::minutes(2))),
+            )
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let provider = TokenProvider::from_source(&TokenSource::OAuthExchange {
+            oauth_token: "y0_oauth".to_owned(),
+            iam_endpoint: mock_server.uri(),
+        })
+        .expect("provider must initialize");
+        let http = Client::new();
+
+        provider.token(&http).await.unwrap();
+        provider.token(&http).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn iam_exchange_error_is_reported_with_status() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(json!({"message": "bad jwt"})))
+            .mount(&mock_server)
+            .await;
+
+        let provider = TokenProvider::from_source(&service_account_source(mock_server.uri()))
+            .expect("provider must initialize");
+        let err = provider
+            .token(&Client::new())
+            .await
+            .expect_err("IAM failure must surface");
+
+        assert_eq!(err.data.as_ref().unwrap()["status"], json!(401));
+    }
+}
@@ -0,0 +1,448 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, Read, Write},
+    net::SocketAddr,
+    path::PathBuf,
+};
+
+use anyhow::{Context, Result, anyhow};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rmcp::ErrorData as McpError;
+use serde_json::Value;
+
+use crate::{
+    DEFAULT_HTTP_LISTEN, DataLensServer,
+    doctor::{self, CheckStatus},
+    method_catalog, method_schema,
+    registry::{self, RegistryChanges},
+    registry_diff::{self, RegistryDiff},
+};
+
+const DEFAULT_REGISTRY_PATH: &str = "openapi/datalens-rpc-methods.json";
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "datalens-mcp",
+    version,
+    about = "MCP server for Yandex DataLens API",
+    args_conflicts_with_subcommands = true
+)]
+pub(crate) struct Cli {
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+    #[command(flatten)]
+    pub(crate) serve: ServeArgs,
+    /// Profiles file (default: `$XDG_CONFIG_HOME/datalens-mcp/config.toml`).
+    #[arg(long, value_name = "PATH", global = true)]
+    pub(crate) config: Option<PathBuf>,
+    /// Profile used when a call does not name one.
+    #[arg(long, value_name = "NAME", global = true)]
+    pub(crate) profile: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum Command {
+    /// Serve MCP (default when no subcommand is given).
+    Serve(ServeArgs),
+    /// Call a DataLens RPC method and print the JSON response.
+    Call {
+        /// RPC method name, e.g. `listDirectory`.
+        method: String,
+        /// JSON object payload, `@file` to read it from a file, or `-` for stdin.
+        #[arg(long, default_value = "{}")]
+        payload: String,
+    },
+    /// List RPC methods from the method registry.
+    Methods {
+        /// Print the full catalog as JSON instead of a table.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the request schema and invocation hints for an RPC method.
+    Schema {
+        /// RPC method name, e.g. `createDataset`.
+        method: String,
+    },
+    /// Check credentials and connectivity for the selected profile.
+    Doctor {
+        /// Print the report as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Generate the method registry from a DataLens OpenAPI document and summarize changes.
+    Registry(RegistryArgs),
+    /// Compare two method registries and report API drift.
+    RegistryDiff(RegistryDiffArgs),
+}
+
+#[derive(Clone, Debug, Args)]
+pub(crate) struct RegistryDiffArgs {
+    /// `embedded`, `active` (honors `DATALENS_OPENAPI_PATH`), or a registry/OpenAPI JSON file.
+    pub(crate) base: String,
+    /// Registry to compare against `base`.
+    #[arg(default_value = "active")]
+    pub(crate) target: String,
+    /// Print the diff as JSON.
+    #[arg(long)]
+    pub(crate) json: bool,
+    /// Exit non-zero when the diff contains breaking changes.
+    #[arg(long)]
+    pub(crate) fail_on_breaking: bool,
+}
+
+#[derive(Clone, Debug, Args)]
+pub(crate) struct RegistryArgs {
+    /// DataLens OpenAPI 3.1 document (JSON).
+    pub(crate) openapi: PathBuf,
+    /// Registry file to compare against and overwrite.
+    #[arg(long, default_value = DEFAULT_REGISTRY_PATH)]
+    pub(crate) output: PathBuf,
+    /// `sourceUrl` to record (default: the one in the current registry).
+    #[arg(long, value_name = "URL")]
+    pub(crate) source_url: Option<String>,
+    /// `snapshotDate` to record, `YYYY-MM-DD` (default: today).
+    #[arg(long, value_name = "DATE")]
+    pub(crate) snapshot_date: Option<String>,
+}
+
+#[derive(Clone, Debug, Args)]
+pub(crate) struct ServeArgs {
+    /// MCP transport to serve.
+    #[arg(long, value_enum, default_value_t = Transport::Stdio)]
+    pub(crate) transport: Transport,
+    /// Address to listen on with `--transport http`.
+    #[arg(long, default_value = DEFAULT_HTTP_LISTEN)]
+    pub(crate) listen: SocketAddr,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Transport {
+    /// MCP over standard input/output, launched by an MCP host.
+    Stdio,
+    /// MCP streamable HTTP (with SSE) on `--listen`, shared by several clients.
+    Http,
+}
+
+/// Runs one RPC through the same `call_rpc` path MCP tools use, with the selected profile.
+pub(crate) async fn run_call(server: &DataLensServer, method: &str, payload: &str) -> Result<()> {
+    let payload = read_payload(payload)?;
+    let response = server
+        .call_rpc(None, method, payload)
+        .await
+        .map_err(mcp_error)?;
+    print_json(&Value::Object(response.0))
+}
+
+/// Prints the doctor report and fails when any check failed, so CI jobs can gate on it.
+pub(crate) async fn run_doctor(server: &DataLensServer, json: bool) -> Result<()> {
+    let report = doctor::diagnose(server, None).await.map_err(mcp_error)?;
+    if json {
+        print_json(&Value::Object(report.to_json()))?;
+    } else {
+        write_stdout(&report.render())?;
+    }
+
+    if report.status == CheckStatus::Fail {
+        return Err(anyhow!("doctor found failing checks"));
+    }
+    Ok(())
+}
+
+/// Converts an OpenAPI document into the registry at `--output`, keeping the typed tool
+/// mappings and categories of the registry it replaces, and prints what changed.
+pub(crate) fn run_registry(args: &RegistryArgs) -> Result<()> {
+    let raw = fs::read_to_string(&args.openapi)
+        .with_context(|| format!("failed to read {}", args.openapi.display()))?;
+    let document: Value = serde_json::from_str(&raw)
+        .with_context(|| format!("{} is not valid JSON", args.openapi.display()))?;
+
+    let current = if args.output.exists() {
+        Some(registry::load(&args.output)?)
+    } else {
+        None
+    };
+    let baseline = current
+        .as_ref()
+        .unwrap_or_else(|| registry::embedded_registry());
+    let generated = registry::from_openapi(
+        &document,
+        args.source_url.as_deref().unwrap_or(&baseline.source_url),
+        args.snapshot_date
+            .clone()
+            .unwrap_or_else(|| chrono::Utc::now().format("%Y-%m-%d").to_string()),
+        baseline,
+    )?;
+    registry::write(&generated, &args.output)?;
+
+    let changes = RegistryChanges::between(baseline, &generated);
+    let mut summary = format!(
+        "Wrote {}: {} methods ({} added, {} removed, {} changed)\n",
+        args.output.display(),
+        generated.methods.len(),
+        changes.added.len(),
+        changes.removed.len(),
+        changes.changed.len(),
+    );
+    if changes.is_empty() {
+        summary.push_str("No method changes.\n");
+    }
+    summary.push_str(&changes.render());
+    write_stdout(&summary)
+}
+
+/// Prints the drift between two registries; `--fail-on-breaking` lets CI gate on it.
+pub(crate) fn run_registry_diff(args: &RegistryDiffArgs) -> Result<()> {
+    let base = registry_diff::open(&args.base)?;
+    let target = registry_diff::open(&args.target)?;
+    let diff = RegistryDiff::between(&args.base, &base, &args.target, &target);
+    if args.json {
+        print_json(&Value::Object(diff.to_json()))?;
+    } else {
+        write_stdout(&diff.render())?;
+    }
+
+    if args.fail_on_breaking && diff.breaking_changes > 0 {
+        return Err(anyhow!(
+            "{} breaking registry change(s)",
+            diff.breaking_changes
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn print_methods(json: bool) -> Result<()> {
+    let catalog = method_catalog().map_err(mcp_error)?;
+    if json {
+        return print_json(&Value::Object(catalog));
+    }
+
+    let methods = catalog
+        .get("methods")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let mut table = String::new();
+    let width = methods
+        .iter()
+        .filter_map(|item| item.get("method").and_then(Value::as_str))
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    for item in methods {
+        let field = |name: &str| item.get(name).and_then(Value::as_str).unwrap_or("");
+        let experimental = if item.get("experimental") == Some(&Value::Bool(true)) {
+            " (experimental)"
+        } else {
+            ""
+        };
+        writeln!(
+            table,
+            "{:<width$}  {:<6}  {}{experimental}",
+            field("method"),
+            field("category"),
+            field("invokeWith"),
+        )?;
+    }
+    write_stdout(&table)
+}
+
+pub(crate) fn print_schema(method: &str) -> Result<()> {
+    let schema = method_schema(method).map_err(mcp_error)?;
+    print_json(&Value::Object(schema))
+}
+
+fn read_payload(raw: &str) -> Result<Value> {
+    let text = if raw == "-" {
+        let mut text = String::new();
+        io::stdin()
+            .read_to_string(&mut text)
+            .context("failed to read payload from stdin")?;
+        text
+    } else if let Some(path) = raw.strip_prefix('@') {
+        fs::read_to_string(path).with_context(|| format!("failed to read payload file {path}"))?
+    } else {
+        raw.to_owned()
+    };
+
+    let payload: Value = serde_json::from_str(&text).context("payload must be valid JSON")?;
+    if !payload.is_object() {
+        return Err(anyhow!("payload must be a JSON object"));
+    }
+    Ok(payload)
+}
+
+fn print_json(value: &Value) -> Result<()> {
+    write_stdout(&format!("{}\n", serde_json::to_string_pretty(value)?))
+}
+
+/// Writes command output, treating a closed pipe (e.g. `| head`) as success.
+fn write_stdout(text: &str) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    match stdout
+        .write_all(text.as_bytes())
+        .and_then(|()| stdout.flush())
+    {
+        Err(error) if error.kind() != io::ErrorKind::BrokenPipe => {
+            Err(error).context("failed to write to stdout")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Turns a tool error into a CLI error that keeps the structured details.
+fn mcp_error(error: McpError) -> anyhow::Error {
+    match error.data {
+        Some(data) => anyhow!(
+            "{}\n{}",
+            error.message,
+            serde_json::to_string_pretty(&data).unwrap_or_default()
+        ),
+        None => anyhow!("{}", error.message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_defaults_to_serving_stdio() {
+        let cli = Cli::try_parse_from(["datalens-mcp"]).expect("empty args must parse");
+        assert!(cli.command.is_none());
+        assert_eq!(cli.serve.transport, Transport::Stdio);
+        assert_eq!(
+            cli.serve.listen,
+            DEFAULT_HTTP_LISTEN.parse::<SocketAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn cli_accepts_http_transport_with_listen_address() {
+        let cli = Cli::try_parse_from([
+            "datalens-mcp",
+            "--transport",
+            "http",
+            "--listen",
+            "0.0.0.0:9000",
+        ])
+        .expect("http transport args must parse");
+        assert_eq!(cli.serve.transport, Transport::Http);
+        assert_eq!(cli.serve.listen.port(), 9000);
+
+        let cli = Cli::try_parse_from(["datalens-mcp", "serve", "--transport", "http"])
+            .expect("serve subcommand must parse");
+        assert!(matches!(
+            cli.command,
+            Some(Command::Serve(ServeArgs {
+                transport: Transport::Http,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn cli_accepts_config_file_and_profile() {
+        let cli = Cli::try_parse_from([
+            "datalens-mcp",
+            "--config",
+            "/etc/datalens-mcp/config.toml",
+            "--profile",
+            "sandbox",
+        ])
+        .expect("config args must parse");
+        assert_eq!(
+            cli.config.as_deref(),
+            Some(std::path::Path::new("/etc/datalens-mcp/config.toml"))
+        );
+        assert_eq!(cli.profile.as_deref(), Some("sandbox"));
+    }
+
+    #[test]
+    fn cli_parses_call_with_global_profile_after_subcommand() {
+        let cli = Cli::try_parse_from([
+            "datalens-mcp",
+            "call",
+            "listDirectory",
+            "--payload",
+            r#"{"path":"/"}"#,
+            "--profile",
+            "sandbox",
+        ])
+        .expect("call args must parse");
+        assert_eq!(cli.profile.as_deref(), Some("sandbox"));
+        match cli.command {
+            Some(Command::Call { method, payload }) => {
+                assert_eq!(method, "listDirectory");
+                assert_eq!(payload, r#"{"path":"/"}"#);
+            }
+            other => panic!("expected call subcommand, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn cli_rejects_transport_flags_before_other_subcommands() {
+        assert!(Cli::try_parse_from(["datalens-mcp", "--transport", "http", "methods"]).is_err());
+    }
+
+    #[test]
+    fn run_registry_writes_registry_that_loads_back() {
+        let dir =
+            std::env::temp_dir().join(format!("datalens-mcp-cli-registry-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let openapi = dir.join("openapi.json");
+        fs::write(
+            &openapi,
+            serde_json::json!({
+                "openapi": "3.1.0",
+                "info": { "title": "DataLens API", "version": "1" },
+                "paths": {
+                    "/rpc/getDataset": { "post": { "requestBody": { "content": { "application/json": {
+                        "schema": { "type": "object", "properties": { "datasetId": { "type": "string" } }, "required": ["datasetId"] }
+                    } } } } }
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let output = dir.join("registry.json");
+
+        run_registry(&RegistryArgs {
+            openapi,
+            output: output.clone(),
+            source_url: None,
+            snapshot_date: Some("2026-10-01".to_owned()),
+        })
+        .expect("registry must be generated");
+
+        let written = registry::load(&output).expect("generated registry must load");
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(written.snapshot_date, "2026-10-01");
+        assert_eq!(written.source_url, registry::embedded_registry().source_url);
+        let item = written.find("getDataset").unwrap();
+        assert_eq!(item.typed_tool.as_deref(), Some("datalens_get_dataset"));
+        assert_eq!(
+            item.request_example,
+            Some(serde_json::json!({ "datasetId": "string" }))
+        );
+    }
+
+    #[test]
+    fn read_payload_accepts_inline_and_file_json() {
+        assert_eq!(
+            read_payload(r#"{"path":"/"}"#).unwrap(),
+            serde_json::json!({"path": "/"})
+        );
+
+        let file = std::env::temp_dir().join(format!(
+            "datalens-mcp-cli-payload-{}.json",
+            std::process::id()
+        ));
+        fs::write(&file, r#"{"datasetId":"ds-1"}"#).unwrap();
+        let payload = read_payload(&format!("@{}", file.display())).unwrap();
+        let _ = fs::remove_file(&file);
+        assert_eq!(payload, serde_json::json!({"datasetId": "ds-1"}));
+
+        assert!(read_payload("[1, 2]").is_err());
+    }
+}
@@ -0,0 +1,548 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use rmcp::ErrorData as McpError;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    AppConfig, DEFAULT_TOKEN_COMMAND_TTL_SECONDS,
+    audit::AuditConfig,
+    auth::{DEFAULT_IAM_ENDPOINT, TokenProvider, TokenSource},
+    env_non_empty,
+    policy::Policy,
+    retry::RetryPolicy,
+    schema::ValidationMode,
+    subscriptions,
+    throttle::{Throttle, ThrottleConfig, default_burst},
+};
+
+/// Profile name used when the server is configured from environment variables only.
+pub(crate) const DEFAULT_PROFILE: &str = "default";
+
+const CONFIG_DIR_NAME: &str = "datalens-mcp";
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Named DataLens configurations the server can talk to.
+#[derive(Clone, Debug)]
+pub(crate) struct ServerConfig {
+    pub(crate) default_profile: String,
+    pub(crate) profiles: BTreeMap<String, AppConfig>,
+    /// Server-wide audit log; never set per profile.
+    pub(crate) audit: Option<AuditConfig>,
+    /// Poll interval of resource subscriptions; `None` turns them off.
+    pub(crate) subscription_poll: Option<Duration>,
+    /// `DATALENS_REGISTRY_DIR`: where `datalens_registry_diff` may read registry files.
+    pub(crate) registry_dir: Option<PathBuf>,
+}
+
+impl ServerConfig {
+    pub(crate) fn single(cfg: AppConfig) -> Self {
+        Self {
+            default_profile: DEFAULT_PROFILE.to_owned(),
+            profiles: BTreeMap::from([(DEFAULT_PROFILE.to_owned(), cfg)]),
+            audit: None,
+            subscription_poll: None,
+            registry_dir: None,
+        }
+    }
+
+    /// Loads profiles from `path` (or the default config file when it exists) and falls back to
+    /// a single profile built from environment variables.
+    pub(crate) fn load(path: Option<&Path>, selected: Option<&str>) -> Result<Self> {
+        let env_cfg = AppConfig::from_env();
+        let path = match path {
+            Some(path) => Some(path.to_path_buf()),
+            None => default_config_path().filter(|path| path.is_file()),
+        };
+        let Some(path) = path else {
+            if let Some(selected) = selected.filter(|name| *name != DEFAULT_PROFILE) {
+                bail!("profile `{selected}` requested but no config file was found");
+            }
+            return Ok(Self {
+                audit: AuditConfig::from_env(),
+                subscription_poll: subscriptions::poll_interval_from_env(),
+                registry_dir: env_non_empty("DATALENS_REGISTRY_DIR").map(PathBuf::from),
+                ..Self::single(env_cfg)
+            });
+        };
+
+        let raw = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        let config = Self::parse(&raw, selected, &env_cfg)
+            .with_context(|| format!("invalid config file {}", path.display()))?;
+        Ok(Self {
+            audit: AuditConfig::from_env(),
+            subscription_poll: subscriptions::poll_interval_from_env(),
+            registry_dir: env_non_empty("DATALENS_REGISTRY_DIR").map(PathBuf::from),
+            ..config
+        })
+    }
+
+    /// Builds profiles from config file contents. Connection settings a profile leaves out are
+    /// taken from `defaults`; credentials never are, so one org's token cannot leak into another.
+    pub(crate) fn parse(raw: &str, selected: Option<&str>, defaults: &AppConfig) -> Result<Self> {
+        let file: ConfigFile = toml::from_str(raw)?;
+        if file.profiles.is_empty() {
+            bail!("config file must define at least one [profiles.<name>] table");
+        }
+
+        let default_profile = match selected.or(file.default_profile.as_deref()) {
+            Some(name) => name.to_owned(),
+            None if file.profiles.len() == 1 => file.profiles.keys().next().cloned().unwrap(),
+            None if file.profiles.contains_key(DEFAULT_PROFILE) => DEFAULT_PROFILE.to_owned(),
+            None => bail!(
+                "set `default_profile` to pick one of: {}",
+                profile_names(&file.profiles)
+            ),
+        };
+        if !file.profiles.contains_key(&default_profile) {
+            bail!(
+                "profile `{default_profile}` is not defined; available profiles: {}",
+                profile_names(&file.profiles)
+            );
+        }
+
+        let profiles = file
+            .profiles
+            .into_iter()
+            .map(|(name, profile)| {
+                let cfg = profile.into_app_config(defaults);
+                (name, cfg)
+            })
+            .collect();
+
+        Ok(Self {
+            default_profile,
+            profiles,
+            audit: None,
+            subscription_poll: None,
+            registry_dir: None,
+        })
+    }
+}
+
+/// A configured profile with its live token provider.
+pub(crate) struct Profile {
+    pub(crate) cfg: AppConfig,
+    pub(crate) tokens: Option<Arc<TokenProvider>>,
+    pub(crate) throttle: Throttle,
+    /// Loaded `AppConfig::policies`, with their paths for error messages.
+    pub(crate) policies: Vec<(PathBuf, Policy)>,
+}
+
+pub(crate) struct Profiles {
+    default: String,
+    entries: BTreeMap<String, Profile>,
+}
+
+impl Profiles {
+    pub(crate) fn from_config(config: ServerConfig) -> Result<Self> {
+        let entries = config
+            .profiles
+            .into_iter()
+            .map(|(name, cfg)| {
+                let tokens = cfg
+                    .token_source
+                    .as_ref()
+                    .map(TokenProvider::from_source)
+                    .transpose()
+                    .with_context(|| {
+                        format!("failed to initialize token provider for profile `{name}`")
+                    })?
+                    .map(Arc::new);
+                let throttle = Throttle::new(&cfg.throttle);
+                let policies = cfg
+                    .policies
+                    .iter()
+                    .map(|path| Ok((path.clone(), Policy::load(path)?)))
+                    .collect::<Result<_>>()
+                    .with_context(|| format!("failed to load policy for profile `{name}`"))?;
+                Ok((
+                    name,
+                    Profile {
+                        cfg,
+                        tokens,
+                        throttle,
+                        policies,
+                    },
+                ))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            default: config.default_profile,
+            entries,
+        })
+    }
+
+    pub(crate) fn default_name(&self) -> &str {
+        &self.default
+    }
+
+    pub(crate) fn default_profile(&self) -> &Profile {
+        &self.entries[&self.default]
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, &Profile)> {
+        self.entries
+            .iter()
+            .map(|(name, profile)| (name.as_str(), profile))
+    }
+
+    /// Looks up a profile by name, or the default profile when no name is given.
+    pub(crate) fn resolve(&self, name: Option<&str>) -> Result<(&str, &Profile), McpError> {
+        let name = name.unwrap_or(&self.default);
+        self.entries
+            .get_key_value(name)
+            .map(|(name, profile)| (name.as_str(), profile))
+            .ok_or_else(|| {
+                McpError::invalid_params(
+                    format!("Unknown DataLens profile: {name}"),
+                    Some(json!({
+                        "availableProfiles": self.entries.keys().collect::<Vec<_>>(),
+                        "hint": "Call datalens_list_profiles to see configured profiles."
+                    })),
+                )
+            })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    default_profile: Option<String>,
+    #[serde(default)]
+    profiles: BTreeMap<String, ProfileFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileFile {
+    org_id: Option<String>,
+    base_url: Option<String>,
+    api_version: Option<String>,
+    timeout_seconds: Option<u64>,
+    max_retries: Option<u32>,
+    retry_writes: Option<bool>,
+    rate_limit_rps: Option<f64>,
+    rate_limit_burst: Option<u32>,
+    max_in_flight: Option<usize>,
+    validation: Option<ValidationMode>,
+    read_only: Option<bool>,
+    policy: Option<PathBuf>,
+    dry_run: Option<bool>,
+    confirm_ttl_seconds: Option<u64>,
+    token: Option<TokenFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+enum TokenFile {
+    /// IAM token (or `OAuth <token>`) written in the file.
+    Static { token: String },
+    /// Static token read from another environment variable at startup.
+    Env { var: String },
+    ServiceAccountKey {
+        key_file: PathBuf,
+        iam_endpoint: Option<String>,
+    },
+    Oauth {
+        oauth_token: String,
+        iam_endpoint: Option<String>,
+    },
+    Command {
+        command: String,
+        ttl_seconds: Option<u64>,
+    },
+}
+
+impl ProfileFile {
+    fn into_app_config(self, defaults: &AppConfig) -> AppConfig {
+        let rate_limit_rps = self
+            .rate_limit_rps
+            .filter(|rps| rps.is_finite() && *rps >= 0.0)
+            .unwrap_or(defaults.throttle.requests_per_second);
+
+        AppConfig {
+            base_url: self.base_url.unwrap_or_else(|| defaults.base_url.clone()),
+            api_version: self
+                .api_version
+                .unwrap_or_else(|| defaults.api_version.clone()),
+            org_id: self.org_id.filter(|org_id| !org_id.trim().is_empty()),
+            token_source: self.token.and_then(TokenFile::into_source),
+            timeout: self
+                .timeout_seconds
+                .filter(|seconds| *seconds > 0)
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.timeout),
+            retry: RetryPolicy {
+                max_retries: self.max_retries.unwrap_or(defaults.retry.max_retries),
+                retry_writes: self.retry_writes.unwrap_or(defaults.retry.retry_writes),
+                ..defaults.retry.clone()
+            },
+            throttle: ThrottleConfig {
+                requests_per_second: rate_limit_rps,
+                burst: self.rate_limit_burst.map_or_else(
+                    || default_burst(rate_limit_rps) as u32,
+                    |burst| burst.max(1),
+                ),
+                max_in_flight: self
+                    .max_in_flight
+                    .unwrap_or(defaults.throttle.max_in_flight),
+            },
+            validation: self.validation.unwrap_or(defaults.validation),
+            // `DATALENS_READ_ONLY` locks every profile; a profile can only opt in.
+            read_only: defaults.read_only || self.read_only.unwrap_or(false),
+            // Like read-only mode, `DATALENS_POLICY` binds every profile; a profile's own
+            // policy is checked after it and can only deny more.
+            policies: defaults
+                .policies
+                .iter()
+                .cloned()
+                .chain(self.policy.map(expand_home))
+                .collect(),
+            // `DATALENS_DRY_RUN` binds every profile too; a profile can only opt in.
+            dry_run: defaults.dry_run || self.dry_run.unwrap_or(false),
+            confirm_ttl: self
+                .confirm_ttl_seconds
+                .map_or(defaults.confirm_ttl, Duration::from_secs),
+        }
+    }
+}
+
+impl TokenFile {
+    fn into_source(self) -> Option<TokenSource> {
+        let iam_endpoint =
+            |endpoint: Option<String>| endpoint.unwrap_or_else(|| DEFAULT_IAM_ENDPOINT.to_owned());
+
+        match self {
+            Self::Static { token } => Some(TokenSource::Static(token)),
+            Self::Env { var } => env_non_empty(&var).map(TokenSource::Static),
+            Self::ServiceAccountKey {
+                key_file,
+                iam_endpoint: endpoint,
+            } => Some(TokenSource::ServiceAccountKey {
+                key_file: expand_home(key_file),
+                iam_endpoint: iam_endpoint(endpoint),
+            }),
+            Self::Oauth {
+                oauth_token,
+                iam_endpoint: endpoint,
+            } => Some(TokenSource::OAuthExchange {
+                oauth_token,
+                iam_endpoint: iam_endpoint(endpoint),
+            }),
+            Self::Command {
+                command,
+                ttl_seconds,
+            } => Some(TokenSource::Command {
+                command,
+                ttl: Duration::from_secs(
+                    ttl_seconds
+                        .filter(|seconds| *seconds > 0)
+                        .unwrap_or(DEFAULT_TOKEN_COMMAND_TTL_SECONDS),
+                ),
+            }),
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/datalens-mcp/config.toml`, falling back to `~/.config` (or `%APPDATA%`).
+pub(crate) fn default_config_path() -> Option<PathBuf> {
+    env_non_empty("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env_non_empty("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| env_non_empty("APPDATA").map(PathBuf::from))
+        .map(|dir| dir.join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME))
+}
+
+fn expand_home(path: PathBuf) -> PathBuf {
+    match (path.strip_prefix("~"), env_non_empty("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path,
+    }
+}
+
+fn profile_names(profiles: &BTreeMap<String, ProfileFile>) -> String {
+    profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> AppConfig {
+        AppConfig {
+            base_url: "https://api.datalens.tech".to_owned(),
+            api_version: "0".to_owned(),
+            org_id: Some("env-org".to_owned()),
+            token_source: Some(TokenSource::Static("env-token".to_owned())),
+            timeout: Duration::from_secs(30),
+            retry: RetryPolicy::default(),
+            throttle: ThrottleConfig::default(),
+            validation: ValidationMode::default(),
+            read_only: false,
+            policies: Vec::new(),
+            dry_run: false,
+            confirm_ttl: Duration::from_secs(120),
+        }
+    }
+
+    const TWO_PROFILES: &str = r#"
+        default_profile = "prod"
+
+        [profiles.prod]
+        org_id = "org-prod"
+        timeout_seconds = 60
+        max_retries = 5
+        rate_limit_rps = 4
+        max_in_flight = 2
+        validation = "warn"
+
+        [profiles.prod.token]
+        source = "service_account_key"
+        key_file = "/etc/datalens/prod-key.json"
+
+        [profiles.sandbox]
+        org_id = "org-sandbox"
+        base_url = "https://datalens.example.test"
+        api_version = "1"
+
+        [profiles.sandbox.token]
+        source = "command"
+        command = "yc iam create-token --profile sandbox"
+        ttl_seconds = 600
+    "#;
+
+    #[test]
+    fn parse_builds_profiles_with_defaults_for_missing_settings() {
+        let config = ServerConfig::parse(TWO_PROFILES, None, &defaults()).expect("config parses");
+
+        assert_eq!(config.default_profile, "prod");
+        let prod = &config.profiles["prod"];
+        assert_eq!(prod.org_id.as_deref(), Some("org-prod"));
+        assert_eq!(prod.base_url, "https://api.datalens.tech");
+        assert_eq!(prod.timeout, Duration::from_secs(60));
+        assert_eq!(prod.retry.max_retries, 5);
+        assert_eq!(prod.throttle.requests_per_second, 4.0);
+        assert_eq!(prod.throttle.burst, 4);
+        assert_eq!(prod.throttle.max_in_flight, 2);
+        assert_eq!(prod.validation, ValidationMode::Warn);
+        assert!(matches!(
+            &prod.token_source,
+            Some(TokenSource::ServiceAccountKey { key_file, iam_endpoint })
+                if key_file == Path::new("/etc/datalens/prod-key.json")
+                    && iam_endpoint == DEFAULT_IAM_ENDPOINT
+        ));
+
+        let sandbox = &config.profiles["sandbox"];
+        assert_eq!(sandbox.base_url, "https://datalens.example.test");
+        assert_eq!(sandbox.api_version, "1");
+        assert_eq!(sandbox.timeout, Duration::from_secs(30));
+        assert!(matches!(
+            &sandbox.token_source,
+            Some(TokenSource::Command { ttl, .. }) if *ttl == Duration::from_secs(600)
+        ));
+    }
+
+    #[test]
+    fn parse_does_not_inherit_credentials_from_environment() {
+        let config =
+            ServerConfig::parse("[profiles.bare]\n", None, &defaults()).expect("config parses");
+
+        assert_eq!(config.default_profile, "bare");
+        assert_eq!(config.profiles["bare"].org_id, None);
+        assert!(config.profiles["bare"].token_source.is_none());
+    }
+
+    #[test]
+    fn parse_prefers_selected_profile_over_file_default() {
+        let config =
+            ServerConfig::parse(TWO_PROFILES, Some("sandbox"), &defaults()).expect("config parses");
+        assert_eq!(config.default_profile, "sandbox");
+
+        let err = ServerConfig::parse(TWO_PROFILES, Some("staging"), &defaults())
+            .expect_err("unknown profile must be rejected");
+        assert!(err.to_string().contains("prod, sandbox"));
+    }
+
+    #[test]
+    fn parse_requires_default_profile_when_ambiguous() {
+        let raw = "[profiles.a]\norg_id = \"a\"\n[profiles.b]\norg_id = \"b\"\n";
+        let err = ServerConfig::parse(raw, None, &defaults())
+            .expect_err("ambiguous default must be rejected");
+        assert!(err.to_string().contains("default_profile"));
+    }
+
+    #[test]
+    fn parse_read_only_cannot_be_disabled_by_profile() {
+        let raw = "[profiles.a]\nread_only = true\n[profiles.b]\nread_only = false\n[profiles.c]\n";
+        let config = ServerConfig::parse(raw, Some("a"), &defaults()).expect("config parses");
+        assert!(config.profiles["a"].read_only);
+        assert!(!config.profiles["b"].read_only);
+        assert!(!config.profiles["c"].read_only);
+
+        let locked = AppConfig {
+            read_only: true,
+            ..defaults()
+        };
+        let config = ServerConfig::parse(raw, Some("a"), &locked).expect("config parses");
+        assert!(config.profiles.values().all(|cfg| cfg.read_only));
+    }
+
+    #[test]
+    fn parse_dry_run_cannot_be_disabled_by_profile() {
+        let raw = "[profiles.a]\ndry_run = true\n[profiles.b]\ndry_run = false\n[profiles.c]\n";
+        let config = ServerConfig::parse(raw, Some("a"), &defaults()).expect("config parses");
+        assert!(config.profiles["a"].dry_run);
+        assert!(!config.profiles["b"].dry_run);
+        assert!(!config.profiles["c"].dry_run);
+
+        let locked = AppConfig {
+            dry_run: true,
+            ..defaults()
+        };
+        let config = ServerConfig::parse(raw, Some("a"), &locked).expect("config parses");
+        assert!(config.profiles.values().all(|cfg| cfg.dry_run));
+    }
+
+    #[test]
+    fn parse_rejects_unknown_keys() {
+        let raw = "[profiles.a]\norg = \"typo\"\n";
+        assert!(ServerConfig::parse(raw, None, &defaults()).is_err());
+    }
+
+    #[test]
+    fn profiles_resolve_unknown_name_lists_available_profiles() {
+        let raw = r#"
+            default_profile = "prod"
+            [profiles.prod]
+            org_id = "org-prod"
+            token = { source = "static", token = "prod-token" }
+            [profiles.sandbox]
+            org_id = "org-sandbox"
+        "#;
+        let config = ServerConfig::parse(raw, None, &defaults()).expect("config parses");
+        let profiles = Profiles::from_config(config).expect("profiles initialize");
+
+        let (name, profile) = profiles.resolve(None).expect("default resolves");
+        assert_eq!(name, "prod");
+        assert_eq!(profile.cfg.org_id.as_deref(), Some("org-prod"));
+
+        let err = match profiles.resolve(Some("staging")) {
+            Ok(_) => panic!("unknown profile must fail"),
+            Err(err) => err,
+        };
+        assert_eq!(
+            err.data.unwrap()["availableProfiles"],
+            json!(["prod", "sandbox"])
+        );
+    }
+}
@@ -0,0 +1,393 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use ring::rand::{SecureRandom, SystemRandom};
+use serde_json::{Map, Value, json};
+
+use crate::http_server::ClientCredentials;
+
+/// Default lifetime of a confirmation token.
+pub(crate) const DEFAULT_CONFIRM_TTL_SECONDS: u64 = 120;
+
+/// Entries listed per kind in a confirmation summary.
+const MAX_SUMMARY_ITEMS: usize = 20;
+/// IDs of a bulk delete looked up for the summary.
+const MAX_SUMMARY_TARGETS: usize = 10;
+
+/// Methods that must be confirmed with a token before `datalens_rpc` runs them.
+pub(crate) fn is_destructive(method: &str) -> bool {
+    method.starts_with("delete")
+}
+
+/// Confirmation tokens issued by `datalens_rpc`, each bound to one caller, profile, method
+/// and payload.
+#[derive(Default)]
+pub(crate) struct Confirmations {
+    pending: Mutex<HashMap<String, Pending>>,
+}
+
+struct Pending {
+    /// DataLens credentials the HTTP caller sent; `None` over stdio.
+    caller: Option<ClientCredentials>,
+    profile: String,
+    method: String,
+    payload: Value,
+    expires_at: Instant,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum RedeemError {
+    /// Never issued, already used, expired, or issued to another caller.
+    Unknown,
+    /// Issued for another profile, method or payload; the token is spent.
+    Mismatch,
+}
+
+impl Confirmations {
+    /// A new token for this call; fails only if the system random generator does.
+    pub(crate) fn issue(
+        &self,
+        caller: Option<&ClientCredentials>,
+        profile: &str,
+        method: &str,
+        payload: &Value,
+        ttl: Duration,
+    ) -> Result<String, ring::error::Unspecified> {
+        let now = Instant::now();
+        let token = Self::new_token()?;
+        let mut pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+        pending.retain(|_, entry| entry.expires_at > now);
+        pending.insert(
+            token.clone(),
+            Pending {
+                caller: caller.cloned(),
+                profile: profile.to_owned(),
+                method: method.to_owned(),
+                payload: payload.clone(),
+                expires_at: now + ttl,
+            },
+        );
+        Ok(token)
+    }
+
+    /// Consumes `token` if it was issued for exactly this call and has not expired. Another
+    /// caller's token is reported as unknown and left for its owner.
+    pub(crate) fn redeem(
+        &self,
+        token: &str,
+        caller: Option<&ClientCredentials>,
+        profile: &str,
+        method: &str,
+        payload: &Value,
+    ) -> Result<(), RedeemError> {
+        let mut pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+        if pending
+            .get(token)
+            .is_none_or(|entry| entry.caller.as_ref() != caller)
+        {
+            return Err(RedeemError::Unknown);
+        }
+        let entry = pending
+            .remove(token)
+            .filter(|entry| entry.expires_at > Instant::now())
+            .ok_or(RedeemError::Unknown)?;
+        if entry.profile != profile || entry.method != method || entry.payload != *payload {
+            return Err(RedeemError::Mismatch);
+        }
+        Ok(())
+    }
+
+    fn new_token() -> Result<String, ring::error::Unspecified> {
+        let mut bytes = [0u8; 16];
+        SystemRandom::new().fill(&mut bytes)?;
+        let hex: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+        Ok(format!("confirm-{hex}"))
+    }
+}
+
+/// Read calls whose results describe what `method` would delete, labelled by summary section.
+pub(crate) fn summary_requests(
+    method: &str,
+    payload: &Value,
+) -> Vec<(&'static str, &'static str, Value)> {
+    let ids = |single: &str, many: &str| -> Vec<String> {
+        let mut ids: Vec<String> = payload
+            .get(many)
+            .and_then(Value::as_array)
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default();
+        ids.extend(
+            payload
+                .get(single)
+                .and_then(Value::as_str)
+                .map(str::to_owned),
+        );
+        ids.truncate(MAX_SUMMARY_TARGETS);
+        ids
+    };
+
+    let entry_id = ["datasetId", "connectionId", "dashboardId", "chartId"]
+        .iter()
+        .find_map(|key| payload.get(*key).and_then(Value::as_str));
+    match method {
+        "deleteWorkbook" | "deleteWorkbooks" => ids("workbookId", "workbookIds")
+            .into_iter()
+            .map(|id| ("entries", "getWorkbookEntries", json!({ "workbookId": id })))
+            .collect(),
+        "deleteCollection" | "deleteCollections" => ids("collectionId", "collectionIds")
+            .into_iter()
+            .map(|id| {
+                (
+                    "contents",
+                    "getCollectionContent",
+                    json!({ "collectionId": id }),
+                )
+            })
+            .collect(),
+        _ => match entry_id {
+            Some(id) => vec![
+                ("entries", "getEntries", json!({ "ids": [id] })),
+                (
+                    "relatedEntries",
+                    "getEntriesRelations",
+                    json!({ "entryIds": [id] }),
+                ),
+            ],
+            None => Vec::new(),
+        },
+    }
+}
+
+/// Folds summary call results into a compact description of the affected entries.
+pub(crate) fn summarize(
+    results: Vec<(&'static str, &'static str, Result<Value, String>)>,
+) -> Value {
+    let mut sections: Map<String, Value> = Map::new();
+    let mut totals: Map<String, Value> = Map::new();
+    let mut errors = Vec::new();
+    for (section, method, result) in results {
+        let response = match result {
+            Ok(response) => response,
+            Err(message) => {
+                errors.push(json!({ "method": method, "message": message }));
+                continue;
+            }
+        };
+        let items = ["entries", "relations", "items"]
+            .iter()
+            .find_map(|key| response.get(*key).and_then(Value::as_array))
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        let total = totals.get(section).and_then(Value::as_u64).unwrap_or(0) + items.len() as u64;
+        totals.insert(section.to_owned(), json!(total));
+        let listed = sections
+            .entry(section)
+            .or_insert_with(|| Value::Array(Vec::new()));
+        if let Value::Array(listed) = listed {
+            let room = MAX_SUMMARY_ITEMS.saturating_sub(listed.len());
+            listed.extend(items.iter().take(room).map(compact_entry));
+        }
+    }
+
+    let mut summary = sections;
+    for (section, total) in totals {
+        summary.insert(format!("{section}Total"), total);
+    }
+    if !errors.is_empty() {
+        summary.insert("errors".to_owned(), Value::Array(errors));
+    }
+    Value::Object(summary)
+}
+
+pub(crate) fn compact_entry(item: &Value) -> Value {
+    let keys = [
+        "entryId",
+        "collectionId",
+        "workbookId",
+        "entity",
+        "scope",
+        "type",
+        "key",
+        "displayKey",
+        "title",
+    ];
+    let compact = keys
+        .iter()
+        .filter_map(|key| {
+            item.get(*key)
+                .map(|value| ((*key).to_owned(), value.clone()))
+        })
+        .collect();
+    Value::Object(compact)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::SubjectToken;
+
+    #[test]
+    fn tokens_are_single_use_and_bound_to_the_call() {
+        let confirmations = Confirmations::default();
+        let payload = json!({"datasetId": "ds-1"});
+        let ttl = Duration::from_secs(60);
+
+        let token = confirmations
+            .issue(None, "default", "deleteDataset", &payload, ttl)
+            .unwrap();
+        assert_eq!(
+            confirmations.redeem(
+                &token,
+                None,
+                "default",
+                "deleteDataset",
+                &json!({"datasetId": "ds-2"})
+            ),
+            Err(RedeemError::Mismatch)
+        );
+        assert_eq!(
+            confirmations.redeem(&token, None, "default", "deleteDataset", &payload),
+            Err(RedeemError::Unknown)
+        );
+
+        let token = confirmations
+            .issue(None, "default", "deleteDataset", &payload, ttl)
+            .unwrap();
+        assert_eq!(
+            confirmations.redeem(&token, None, "default", "deleteDataset", &payload),
+            Ok(())
+        );
+        assert_eq!(
+            confirmations.redeem(&token, None, "default", "deleteDataset", &payload),
+            Err(RedeemError::Unknown)
+        );
+    }
+
+    #[test]
+    fn tokens_are_bound_to_the_caller() {
+        let confirmations = Confirmations::default();
+        let payload = json!({"datasetId": "ds-1"});
+        let alice = ClientCredentials {
+            org_id: Some("org-1".to_owned()),
+            subject_token: Some(SubjectToken::Iam("alice".to_owned())),
+        };
+        let bob = ClientCredentials {
+            subject_token: Some(SubjectToken::Iam("bob".to_owned())),
+            ..alice.clone()
+        };
+
+        let token = confirmations
+            .issue(
+                Some(&alice),
+                "default",
+                "deleteDataset",
+                &payload,
+                Duration::from_secs(60),
+            )
+            .unwrap();
+        assert!(token.starts_with("confirm-") && token.len() == "confirm-".len() + 32);
+        for caller in [Some(&bob), None] {
+            assert_eq!(
+                confirmations.redeem(&token, caller, "default", "deleteDataset", &payload),
+                Err(RedeemError::Unknown)
+            );
+        }
+        assert_eq!(
+            confirmations.redeem(&token, Some(&alice), "default", "deleteDataset", &payload),
+            Ok(()),
+            "another caller's attempt must not spend the token"
+        );
+    }
+
+    #[test]
+    fn expired_tokens_are_rejected() {
+        let confirmations = Confirmations::default();
+        let payload = json!({"workbookId": "wb-1"});
+        let token = confirmations
+            .issue(None, "default", "deleteWorkbook", &payload, Duration::ZERO)
+            .unwrap();
+        assert_ne!(
+            token,
+            confirmations
+                .issue(None, "default", "deleteWorkbook", &payload, Duration::ZERO)
+                .unwrap()
+        );
+        assert_eq!(
+            confirmations.redeem(&token, None, "default", "deleteWorkbook", &payload),
+            Err(RedeemError::Unknown)
+        );
+    }
+
+    #[test]
+    fn summary_requests_follow_the_deleted_object() {
+        let requests = summary_requests("deleteWorkbooks", &json!({"workbookIds": ["a", "b"]}));
+        assert_eq!(
+            requests,
+            [
+                ("entries", "getWorkbookEntries", json!({"workbookId": "a"})),
+                ("entries", "getWorkbookEntries", json!({"workbookId": "b"})),
+            ]
+        );
+
+        let requests = summary_requests("deleteDataset", &json!({"datasetId": "ds-1"}));
+        assert_eq!(
+            requests[0],
+            ("entries", "getEntries", json!({"ids": ["ds-1"]}))
+        );
+        assert_eq!(
+            requests[1],
+            (
+                "relatedEntries",
+                "getEntriesRelations",
+                json!({"entryIds": ["ds-1"]})
+            )
+        );
+        assert!(summary_requests("deleteEmbed", &json!({"embedId": "e-1"})).is_empty());
+    }
+
+    #[test]
+    fn summarize_keeps_identifying_fields_and_counts() {
+        let summary = summarize(vec![
+            (
+                "entries",
+                "getEntries",
+                Ok(
+                    json!({"entries": [{"entryId": "ds-1", "scope": "dataset", "meta": {"big": true}}]}),
+                ),
+            ),
+            (
+                "relatedEntries",
+                "getEntriesRelations",
+                Ok(
+                    json!({"relations": [{"entryId": "c-1", "scope": "widget"}, {"entryId": "d-1", "scope": "dash"}]}),
+                ),
+            ),
+            (
+                "relatedEntries",
+                "getEntriesRelations",
+                Err("forbidden".to_owned()),
+            ),
+        ]);
+
+        assert_eq!(
+            summary,
+            json!({
+                "entries": [{"entryId": "ds-1", "scope": "dataset"}],
+                "entriesTotal": 1,
+                "relatedEntries": [{"entryId": "c-1", "scope": "widget"}, {"entryId": "d-1", "scope": "dash"}],
+                "relatedEntriesTotal": 2,
+                "errors": [{"method": "getEntriesRelations", "message": "forbidden"}],
+            })
+        );
+    }
+}
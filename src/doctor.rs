@@ -0,0 +1,465 @@
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use reqwest::Url;
+use rmcp::ErrorData as McpError;
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use tokio::net::lookup_host;
+
+use crate::{AppConfig, DataLensServer, auth::SubjectToken, http_server, rpc_headers};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CheckStatus {
+    Ok,
+    Skip,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Check {
+    name: &'static str,
+    status: CheckStatus,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Ok,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn problem(
+        name: &'static str,
+        status: CheckStatus,
+        detail: impl Into<String>,
+        hint: impl Into<String>,
+    ) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+/// Result of checking one profile's credentials and connectivity.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DoctorReport {
+    profile: String,
+    base_url: String,
+    pub(crate) status: CheckStatus,
+    checks: Vec<Check>,
+    /// Headers `call_rpc` would send, with the token redacted.
+    headers: BTreeMap<String, String>,
+}
+
+impl DoctorReport {
+    pub(crate) fn to_json(&self) -> Map<String, Value> {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(map)) => map,
+            _ => Map::new(),
+        }
+    }
+
+    pub(crate) fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "datalens-mcp doctor: profile `{}`, {}",
+            self.profile, self.base_url
+        );
+        for check in &self.checks {
+            let label = match check.status {
+                CheckStatus::Ok => "[ok]  ",
+                CheckStatus::Skip => "[skip]",
+                CheckStatus::Warn => "[warn]",
+                CheckStatus::Fail => "[FAIL]",
+            };
+            let _ = writeln!(out, "  {label} {}: {}", check.name, check.detail);
+            if let Some(hint) = &check.hint {
+                let _ = writeln!(out, "         hint: {hint}");
+            }
+        }
+        if !self.headers.is_empty() {
+            let _ = writeln!(out, "Headers sent with each RPC call:");
+            for (name, value) in &self.headers {
+                let _ = writeln!(out, "  {name}: {value}");
+            }
+        }
+        out
+    }
+}
+
+/// Checks the selected profile (and caller-supplied HTTP credentials, if any) the same way
+/// `call_rpc` would use them, finishing with a cheap `listDirectory` call on `/`.
+pub(crate) async fn diagnose(
+    server: &DataLensServer,
+    profile: Option<&str>,
+) -> Result<DoctorReport, McpError> {
+    let (profile_name, profile) = server.profiles.resolve(profile)?;
+    let cfg = &profile.cfg;
+    let client = http_server::current_client_credentials().unwrap_or_default();
+    let mut checks = Vec::new();
+
+    let org_id = match (client.org_id, cfg.org_id.clone()) {
+        (Some(org_id), _) => {
+            checks.push(Check::ok(
+                "org_id",
+                format!("{org_id} (from the x-dl-org-id request header)"),
+            ));
+            Some(org_id)
+        }
+        (None, Some(org_id)) => {
+            checks.push(Check::ok(
+                "org_id",
+                format!("{org_id} (from profile `{profile_name}`)"),
+            ));
+            Some(org_id)
+        }
+        (None, None) => {
+            checks.push(Check::problem(
+                "org_id",
+                CheckStatus::Fail,
+                "no organization ID configured",
+                "Set DATALENS_ORG_ID (or `org_id` in the profile). It is shown in DataLens under Organization settings.",
+            ));
+            None
+        }
+    };
+
+    let subject_token = match (client.subject_token, &profile.tokens) {
+        (Some(token), _) => {
+            checks.push(Check::ok(
+                "token",
+                "caller-supplied token from request headers",
+            ));
+            Some(token)
+        }
+        (None, Some(tokens)) => {
+            let source = cfg
+                .token_source
+                .as_ref()
+                .map(|source| source.describe())
+                .unwrap_or("token provider");
+            match tokens.token(&server.http).await {
+                Ok(token) => {
+                    checks.push(Check::ok("token", format!("obtained from {source}")));
+                    Some(token)
+                }
+                Err(error) => {
+                    checks.push(Check::problem(
+                        "token",
+                        CheckStatus::Fail,
+                        format!("{source} did not produce a token: {}", error.message),
+                        "Check the token source settings (key file path, OAuth token, or token command) and that the IAM endpoint is reachable.",
+                    ));
+                    None
+                }
+            }
+        }
+        (None, None) => {
+            checks.push(Check::problem(
+                "token",
+                CheckStatus::Fail,
+                "no token configured",
+                "Set YC_IAM_TOKEN, DATALENS_SA_KEY_FILE, DATALENS_OAUTH_TOKEN or DATALENS_TOKEN_COMMAND (or `token` in the profile).",
+            ));
+            None
+        }
+    };
+    if let Some(token) = &subject_token {
+        checks.push(token_kind_check(token));
+    }
+
+    let base_url = base_url_check(&cfg.base_url).await;
+    let base_url_ok = base_url.status == CheckStatus::Ok;
+    checks.push(base_url);
+
+    let headers = match (&org_id, &subject_token) {
+        (Some(org_id), Some(token)) => redacted_headers(cfg, org_id, token)?,
+        _ => BTreeMap::new(),
+    };
+
+    if org_id.is_some() && subject_token.is_some() && base_url_ok {
+        let check = match server
+            .call_rpc(Some(profile_name), "listDirectory", json!({"path": "/"}))
+            .await
+        {
+            Ok(response) => {
+                let entries = response
+                    .0
+                    .get("entries")
+                    .and_then(Value::as_array)
+                    .map_or(0, Vec::len);
+                Check::ok(
+                    "list_directory",
+                    format!("listDirectory on `/` returned {entries} entries"),
+                )
+            }
+            Err(error) => Check::problem(
+                "list_directory",
+                CheckStatus::Fail,
+                error.message.to_string(),
+                rpc_failure_hint(error.data.as_ref()),
+            ),
+        };
+        checks.push(check);
+    } else {
+        checks.push(Check::problem(
+            "list_directory",
+            CheckStatus::Skip,
+            "not attempted",
+            "Fix the failing checks above first.",
+        ));
+    }
+
+    let status = checks
+        .iter()
+        .map(|check| check.status)
+        .filter(|status| *status != CheckStatus::Skip)
+        .max()
+        .unwrap_or(CheckStatus::Ok);
+
+    Ok(DoctorReport {
+        profile: profile_name.to_owned(),
+        base_url: cfg.base_url.clone(),
+        status,
+        checks,
+        headers,
+    })
+}
+
+fn token_kind_check(token: &SubjectToken) -> Check {
+    match token {
+        SubjectToken::Iam(value) if looks_like_oauth(value) => Check::problem(
+            "token_kind",
+            CheckStatus::Warn,
+            "looks like a Yandex OAuth token but is sent as an IAM token (x-yacloud-subjecttoken)",
+            "Use DATALENS_OAUTH_TOKEN to exchange it for IAM tokens, or prefix the value with `OAuth `.",
+        ),
+        SubjectToken::Iam(value) if value.starts_with("t1.") => {
+            Check::ok("token_kind", "IAM token, sent as x-yacloud-subjecttoken")
+        }
+        SubjectToken::Iam(_) => Check::problem(
+            "token_kind",
+            CheckStatus::Warn,
+            "sent as an IAM token but does not have the usual `t1.` prefix",
+            "Make sure the value comes from `yc iam create-token` or the IAM token exchange.",
+        ),
+        SubjectToken::OAuth(_) => Check::ok(
+            "token_kind",
+            "OAuth token, passed through as x-dl-auth-token",
+        ),
+    }
+}
+
+fn looks_like_oauth(token: &str) -> bool {
+    ["y0_", "y1_", "y2_", "y3_", "AQAAAA"]
+        .iter()
+        .any(|prefix| token.starts_with(prefix))
+}
+
+async fn base_url_check(base_url: &str) -> Check {
+    const HINT: &str = "Set DATALENS_BASE_URL (or `base_url` in the profile) to a reachable API host, e.g. https://api.datalens.tech.";
+
+    let url = match Url::parse(base_url) {
+        Ok(url) => url,
+        Err(error) => {
+            return Check::problem(
+                "base_url",
+                CheckStatus::Fail,
+                format!("{base_url} is not a valid URL: {error}"),
+                HINT,
+            );
+        }
+    };
+    let Some(host) = url.host_str() else {
+        return Check::problem(
+            "base_url",
+            CheckStatus::Fail,
+            format!("{base_url} has no host"),
+            HINT,
+        );
+    };
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    match lookup_host((host, port)).await {
+        Ok(addrs) => {
+            let addrs = addrs
+                .map(|addr| addr.ip().to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            Check::ok("base_url", format!("{host} resolves to {addrs}"))
+        }
+        Err(error) => Check::problem(
+            "base_url",
+            CheckStatus::Fail,
+            format!("{host} does not resolve: {error}"),
+            "Check DNS, proxy and network access, or fix DATALENS_BASE_URL.",
+        ),
+    }
+}
+
+fn rpc_failure_hint(data: Option<&Value>) -> String {
+    let status = data
+        .and_then(|data| data.get("status"))
+        .and_then(Value::as_u64);
+    match status {
+        Some(401) => "The token was rejected: it may be expired or of the wrong kind (IAM vs OAuth).",
+        Some(403) => {
+            "The token is valid but has no access to this organization: check the org ID and the account's DataLens role."
+        }
+        Some(404) => "Check DATALENS_BASE_URL and DATALENS_API_VERSION.",
+        Some(_) => "DataLens returned an error; see the response above.",
+        None => "The request did not reach DataLens; check network access to the base URL.",
+    }
+    .to_owned()
+}
+
+/// Headers `call_rpc` sends for this profile and token, with the token redacted.
+pub(crate) fn redacted_headers(
+    cfg: &AppConfig,
+    org_id: &str,
+    token: &SubjectToken,
+) -> Result<BTreeMap<String, String>, McpError> {
+    let (auth_header, _) = token.header();
+    Ok(rpc_headers(cfg, org_id, token)?
+        .iter()
+        .map(|(name, value)| {
+            let value = if name.as_str() == auth_header {
+                redacted_auth_value(token)
+            } else {
+                value.to_str().unwrap_or_default().to_owned()
+            };
+            (name.as_str().to_owned(), value)
+        })
+        .collect())
+}
+
+fn redacted_auth_value(token: &SubjectToken) -> String {
+    match token {
+        SubjectToken::Iam(value) => redact(value),
+        SubjectToken::OAuth(value) => format!("OAuth {}", redact(value)),
+    }
+}
+
+fn redact(secret: &str) -> String {
+    let visible = secret.chars().take(4).collect::<String>();
+    format!("{visible}…({} chars, redacted)", secret.chars().count())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{method, path},
+    };
+
+    use super::*;
+    use crate::{
+        AppConfig, auth::TokenSource, config::ServerConfig, retry::RetryPolicy,
+        schema::ValidationMode, throttle::ThrottleConfig,
+    };
+
+    fn server_for(cfg: AppConfig) -> DataLensServer {
+        DataLensServer::new(ServerConfig::single(cfg)).expect("server must initialize")
+    }
+
+    fn config(base_url: String, token: Option<&str>) -> AppConfig {
+        AppConfig {
+            base_url,
+            api_version: "0".to_owned(),
+            org_id: Some("org-123".to_owned()),
+            token_source: token.map(|token| TokenSource::Static(token.to_owned())),
+            timeout: Duration::from_secs(5),
+            retry: RetryPolicy::default(),
+            throttle: ThrottleConfig::default(),
+            validation: ValidationMode::default(),
+            read_only: false,
+            policies: Vec::new(),
+            dry_run: false,
+            confirm_ttl: Duration::from_secs(120),
+        }
+    }
+
+    fn check<'a>(report: &'a DoctorReport, name: &str) -> &'a Check {
+        report
+            .checks
+            .iter()
+            .find(|check| check.name == name)
+            .unwrap_or_else(|| panic!("report must include {name}"))
+    }
+
+    #[tokio::test]
+    async fn diagnose_reports_healthy_profile_with_redacted_headers() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"entries": [{}, {}]})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let server = server_for(config(mock_server.uri(), Some("t1.secret-iam-token")));
+        let report = diagnose(&server, None).await.expect("doctor must run");
+
+        assert_eq!(report.status, CheckStatus::Ok, "{}", report.render());
+        assert_eq!(
+            check(&report, "list_directory").detail,
+            "listDirectory on `/` returned 2 entries"
+        );
+        assert_eq!(report.headers["x-dl-org-id"], "org-123");
+        assert_eq!(
+            report.headers["x-yacloud-subjecttoken"],
+            "t1.s…(19 chars, redacted)"
+        );
+        assert!(!report.render().contains("secret-iam-token"));
+    }
+
+    #[tokio::test]
+    async fn diagnose_skips_rpc_when_token_is_missing() {
+        let server = server_for(config("http://127.0.0.1:9".to_owned(), None));
+        let report = diagnose(&server, None).await.expect("doctor must run");
+
+        assert_eq!(report.status, CheckStatus::Fail);
+        assert_eq!(check(&report, "token").status, CheckStatus::Fail);
+        assert_eq!(check(&report, "list_directory").status, CheckStatus::Skip);
+        assert!(report.headers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn diagnose_warns_about_oauth_token_sent_as_iam_and_explains_403() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&mock_server)
+            .await;
+
+        let server = server_for(config(mock_server.uri(), Some("y0_AgAAAAoauth")));
+        let report = diagnose(&server, None).await.expect("doctor must run");
+
+        assert_eq!(check(&report, "token_kind").status, CheckStatus::Warn);
+        let rpc = check(&report, "list_directory");
+        assert_eq!(rpc.status, CheckStatus::Fail);
+        assert!(rpc.hint.as_deref().unwrap().contains("org ID"));
+    }
+
+    #[tokio::test]
+    async fn base_url_check_rejects_invalid_url() {
+        let check = base_url_check("not a url").await;
+        assert_eq!(check.status, CheckStatus::Fail);
+    }
+}
@@ -0,0 +1,472 @@
+use std::{future::Future, net::SocketAddr, sync::Arc};
+
+use anyhow::{Context, Result};
+use axum::{
+    Json, Router,
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, header::AUTHORIZATION, request::Parts},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use rmcp::transport::{
+    StreamableHttpServerConfig, StreamableHttpService,
+    streamable_http_server::session::local::LocalSessionManager,
+};
+use serde_json::{Value, json};
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::{DataLensServer, auth::SubjectToken, env_flag, env_non_empty};
+
+pub(crate) const MCP_PATH: &str = "/mcp";
+pub(crate) const HEALTH_PATH: &str = "/health";
+
+const CLIENT_ORG_ID_HEADER: &str = "x-dl-org-id";
+const CLIENT_IAM_TOKEN_HEADER: &str = "x-yacloud-subjecttoken";
+const CLIENT_OAUTH_TOKEN_HEADER: &str = "x-dl-auth-token";
+
+tokio::task_local! {
+    static CLIENT_CREDENTIALS: ClientCredentials;
+}
+
+/// Who may use the HTTP endpoint and whether callers must bring their own DataLens credentials.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HttpAccessConfig {
+    api_keys: Vec<String>,
+    require_client_credentials: bool,
+}
+
+impl HttpAccessConfig {
+    pub(crate) fn from_env() -> Self {
+        Self {
+            api_keys: env_non_empty("DATALENS_MCP_API_KEYS")
+                .map(|raw| {
+                    raw.split(',')
+                        .map(str::trim)
+                        .filter(|key| !key.is_empty())
+                        .map(str::to_owned)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            require_client_credentials: env_flag("DATALENS_MCP_REQUIRE_CLIENT_CREDENTIALS"),
+        }
+    }
+
+    fn accepts_api_key(&self, headers: &HeaderMap) -> bool {
+        if self.api_keys.is_empty() {
+            return true;
+        }
+
+        let Some(presented) = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+        else {
+            return false;
+        };
+
+        self.api_keys
+            .iter()
+            .any(|key| constant_time_eq(key.as_bytes(), presented.trim().as_bytes()))
+    }
+}
+
+/// DataLens credentials sent by the MCP client with the current HTTP request. They take
+/// precedence over the process-wide `DATALENS_ORG_ID` and token source in `call_rpc`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ClientCredentials {
+    pub(crate) org_id: Option<String>,
+    pub(crate) subject_token: Option<SubjectToken>,
+}
+
+impl ClientCredentials {
+    pub(crate) fn from_parts(parts: &Parts) -> Self {
+        Self::from_headers(&parts.headers)
+    }
+
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_owned)
+        };
+
+        let subject_token = header(CLIENT_IAM_TOKEN_HEADER)
+            .map(SubjectToken::Iam)
+            .or_else(|| {
+                header(CLIENT_OAUTH_TOKEN_HEADER).map(|value| {
+                    SubjectToken::OAuth(
+                        value
+                            .strip_prefix("OAuth ")
+                            .unwrap_or(&value)
+                            .trim()
+                            .to_owned(),
+                    )
+                })
+            });
+
+        Self {
+            org_id: header(CLIENT_ORG_ID_HEADER),
+            subject_token,
+        }
+    }
+}
+
+/// Runs `future` with the caller's credentials visible to `current_client_credentials`.
+pub(crate) async fn with_client_credentials<F: Future>(
+    credentials: ClientCredentials,
+    future: F,
+) -> F::Output {
+    CLIENT_CREDENTIALS.scope(credentials, future).await
+}
+
+pub(crate) fn current_client_credentials() -> Option<ClientCredentials> {
+    CLIENT_CREDENTIALS.try_with(Clone::clone).ok()
+}
+
+/// Serves MCP over streamable HTTP until Ctrl+C / SIGTERM, then drains sessions and exits.
+pub(crate) async fn serve_http(
+    server: DataLensServer,
+    listen: SocketAddr,
+    access: HttpAccessConfig,
+) -> Result<()> {
+    let listener = TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("failed to bind {listen}"))?;
+    let local_addr = listener
+        .local_addr()
+        .context("failed to read bound address")?;
+    let shutdown = CancellationToken::new();
+
+    info!(
+        address = %local_addr,
+        mcp_path = MCP_PATH,
+        health_path = HEALTH_PATH,
+        api_keys = access.api_keys.len(),
+        require_client_credentials = access.require_client_credentials,
+        "serving MCP over streamable HTTP"
+    );
+    if access.api_keys.is_empty() {
+        warn!(
+            "DATALENS_MCP_API_KEYS is not set; any client that can reach {local_addr} may use this server"
+        );
+    }
+
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            wait_for_shutdown_signal().await;
+            info!("shutdown signal received, closing MCP sessions");
+            shutdown.cancel();
+        }
+    });
+
+    axum::serve(listener, router(server, access, shutdown.clone()))
+        .with_graceful_shutdown(async move { shutdown.cancelled_owned().await })
+        .await
+        .context("HTTP server terminated unexpectedly")
+}
+
+fn router(server: DataLensServer, access: HttpAccessConfig, shutdown: CancellationToken) -> Router {
+    let mcp_service = StreamableHttpService::new(
+        move || Ok(server.for_session()),
+        Arc::new(LocalSessionManager::default()),
+        StreamableHttpServerConfig {
+            cancellation_token: shutdown.child_token(),
+            ..Default::default()
+        },
+    );
+
+    let mcp_routes =
+        Router::new()
+            .nest_service(MCP_PATH, mcp_service)
+            .layer(middleware::from_fn_with_state(
+                Arc::new(access),
+                guard_mcp_access,
+            ));
+
+    Router::new()
+        .route(HEALTH_PATH, get(health))
+        .merge(mcp_routes)
+}
+
+async fn guard_mcp_access(
+    State(access): State<Arc<HttpAccessConfig>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !access.accepts_api_key(request.headers()) {
+        return access_denied(
+            StatusCode::UNAUTHORIZED,
+            "missing or invalid API key; send `Authorization: Bearer <key>`",
+        );
+    }
+    let credentials = ClientCredentials::from_headers(request.headers());
+    if access.require_client_credentials
+        && (credentials.subject_token.is_none() || credentials.org_id.is_none())
+    {
+        return access_denied(
+            StatusCode::UNAUTHORIZED,
+            "this server requires your own DataLens credentials; send `x-yacloud-subjecttoken: <IAM token>` and `x-dl-org-id: <org id>`",
+        );
+    }
+    // The server's own token must not be pointed at an organization the operator did not configure.
+    if credentials.org_id.is_some() && credentials.subject_token.is_none() {
+        return access_denied(
+            StatusCode::BAD_REQUEST,
+            "`x-dl-org-id` is only accepted together with your own DataLens token in `x-yacloud-subjecttoken` or `x-dl-auth-token`",
+        );
+    }
+
+    next.run(request).await
+}
+
+fn access_denied(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+fn constant_time_eq(expected: &[u8], presented: &[u8]) -> bool {
+    if expected.len() != presented.len() {
+        return false;
+    }
+
+    expected
+        .iter()
+        .zip(presented)
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+async fn health() -> Json<Value> {
+    Json(json!({
+        "status": "ok",
+        "version": env!("CARGO_PKG_VERSION"),
+    }))
+}
+
+async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        AppConfig, auth::TokenSource, config::ServerConfig, retry::RetryPolicy,
+        schema::ValidationMode, throttle::ThrottleConfig,
+    };
+    use std::time::Duration;
+
+    async fn spawn_router() -> (SocketAddr, CancellationToken) {
+        spawn_router_with_access(HttpAccessConfig::default()).await
+    }
+
+    async fn spawn_router_with_access(access: HttpAccessConfig) -> (SocketAddr, CancellationToken) {
+        let server = DataLensServer::new(ServerConfig::single(AppConfig {
+            base_url: "http://127.0.0.1".to_owned(),
+            api_version: "0".to_owned(),
+            org_id: Some("org-123".to_owned()),
+            token_source: Some(TokenSource::Static("token-abc".to_owned())),
+            timeout: Duration::from_secs(5),
+            retry: RetryPolicy::default(),
+            throttle: ThrottleConfig::default(),
+            validation: ValidationMode::default(),
+            read_only: false,
+            policies: Vec::new(),
+            dry_run: false,
+            confirm_ttl: Duration::from_secs(120),
+        }))
+        .expect("server must initialize");
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shutdown = CancellationToken::new();
+
+        tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move {
+                axum::serve(listener, router(server, access, shutdown.clone()))
+                    .with_graceful_shutdown(async move { shutdown.cancelled_owned().await })
+                    .await
+                    .unwrap();
+            }
+        });
+
+        (addr, shutdown)
+    }
+
+    #[tokio::test]
+    async fn health_endpoint_reports_ok() {
+        let (addr, shutdown) = spawn_router().await;
+
+        let response = reqwest::get(format!("http://{addr}{HEALTH_PATH}"))
+            .await
+            .expect("health request must succeed");
+        assert!(response.status().is_success());
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["status"], json!("ok"));
+
+        shutdown.cancel();
+    }
+
+    fn initialize_request(addr: SocketAddr) -> reqwest::RequestBuilder {
+        reqwest::Client::new()
+            .post(format!("http://{addr}{MCP_PATH}"))
+            .header("accept", "application/json, text/event-stream")
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": {
+                    "protocolVersion": "2025-06-18",
+                    "capabilities": {},
+                    "clientInfo": {"name": "http-test", "version": "0.0.0"}
+                }
+            }))
+    }
+
+    #[tokio::test]
+    async fn mcp_endpoint_accepts_initialize_and_opens_session() {
+        let (addr, shutdown) = spawn_router().await;
+
+        let response = initialize_request(addr)
+            .send()
+            .await
+            .expect("initialize request must succeed");
+
+        assert!(response.status().is_success());
+        assert!(response.headers().contains_key("mcp-session-id"));
+
+        shutdown.cancel();
+    }
+
+    #[tokio::test]
+    async fn mcp_endpoint_requires_configured_api_key() {
+        let (addr, shutdown) = spawn_router_with_access(HttpAccessConfig {
+            api_keys: vec!["team-key".to_owned()],
+            require_client_credentials: false,
+        })
+        .await;
+
+        let rejected = initialize_request(addr).send().await.unwrap();
+        assert_eq!(rejected.status(), StatusCode::UNAUTHORIZED);
+
+        let wrong = initialize_request(addr)
+            .bearer_auth("other-key")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(wrong.status(), StatusCode::UNAUTHORIZED);
+
+        let accepted = initialize_request(addr)
+            .bearer_auth("team-key")
+            .send()
+            .await
+            .unwrap();
+        assert!(accepted.status().is_success());
+
+        let health = reqwest::get(format!("http://{addr}{HEALTH_PATH}"))
+            .await
+            .unwrap();
+        assert!(health.status().is_success(), "health must stay public");
+
+        shutdown.cancel();
+    }
+
+    #[tokio::test]
+    async fn mcp_endpoint_can_require_client_credentials() {
+        let (addr, shutdown) = spawn_router_with_access(HttpAccessConfig {
+            api_keys: Vec::new(),
+            require_client_credentials: true,
+        })
+        .await;
+
+        let rejected = initialize_request(addr).send().await.unwrap();
+        assert_eq!(rejected.status(), StatusCode::UNAUTHORIZED);
+        let rejected = initialize_request(addr)
+            .header(CLIENT_IAM_TOKEN_HEADER, "client-iam")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            rejected.status(),
+            StatusCode::UNAUTHORIZED,
+            "the org ID is required too"
+        );
+
+        let accepted = initialize_request(addr)
+            .header(CLIENT_ORG_ID_HEADER, "org-client")
+            .header(CLIENT_IAM_TOKEN_HEADER, "client-iam")
+            .send()
+            .await
+            .unwrap();
+        assert!(accepted.status().is_success());
+
+        shutdown.cancel();
+    }
+
+    #[tokio::test]
+    async fn mcp_endpoint_rejects_org_id_without_client_token() {
+        let (addr, shutdown) = spawn_router_with_access(HttpAccessConfig::default()).await;
+
+        let rejected = initialize_request(addr)
+            .header(CLIENT_ORG_ID_HEADER, "org-other")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(rejected.status(), StatusCode::BAD_REQUEST);
+
+        let accepted = initialize_request(addr).send().await.unwrap();
+        assert!(accepted.status().is_success());
+
+        shutdown.cancel();
+    }
+
+    #[test]
+    fn client_credentials_are_read_from_datalens_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CLIENT_ORG_ID_HEADER, "org-client".parse().unwrap());
+        headers.insert(
+            CLIENT_OAUTH_TOKEN_HEADER,
+            "OAuth y0_client".parse().unwrap(),
+        );
+
+        assert_eq!(
+            ClientCredentials::from_headers(&headers),
+            ClientCredentials {
+                org_id: Some("org-client".to_owned()),
+                subject_token: Some(SubjectToken::OAuth("y0_client".to_owned())),
+            }
+        );
+
+        headers.insert(CLIENT_IAM_TOKEN_HEADER, "client-iam".parse().unwrap());
+        assert_eq!(
+            ClientCredentials::from_headers(&headers).subject_token,
+            Some(SubjectToken::Iam("client-iam".to_owned())),
+            "IAM token header takes precedence"
+        );
+    }
+}
@@ -1,18 +1,51 @@
-use std::{collections::BTreeMap, env, sync::OnceLock, time::Duration};
+mod audit;
+mod auth;
+mod cli;
+mod config;
+mod confirm;
+mod doctor;
+mod http_server;
+mod policy;
+mod prompts;
+mod registry;
+mod registry_diff;
+mod resources;
+mod responses;
+mod retry;
+mod schema;
+mod subscriptions;
+mod throttle;
+
+use std::{
+    collections::BTreeMap,
+    env,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use anyhow::{Context, Result};
+use clap::Parser;
 use reqwest::{
-    Client,
+    Client, StatusCode,
     header::{ACCEPT, CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue},
 };
 use rmcp::{
-    ErrorData as McpError, ServerHandler, ServiceExt,
+    ErrorData as McpError, RoleServer, ServerHandler, ServiceExt,
     handler::server::{
         router::tool::ToolRouter,
+        tool::ToolCallContext,
         wrapper::{Json, Parameters},
     },
-    model::{ServerCapabilities, ServerInfo},
-    tool, tool_handler, tool_router,
+    model::{
+        CallToolRequestParams, CallToolResult, GetPromptRequestParams, GetPromptResult,
+        ListPromptsResult, ListResourceTemplatesResult, ListResourcesResult, ListToolsResult,
+        PaginatedRequestParams, ReadResourceRequestParams, ReadResourceResult, ResourceContents,
+        ResourceUpdatedNotificationParam, ServerCapabilities, ServerInfo, SubscribeRequestParams,
+        Tool, ToolAnnotations, UnsubscribeRequestParams,
+    },
+    service::RequestContext,
+    tool, tool_router,
     transport::stdio,
 };
 use serde::Deserialize;
@@ -20,55 +53,48 @@ use serde_json::{Map, Value, json};
 use tracing::{debug, info, warn};
 use tracing_subscriber::EnvFilter;
 
+use audit::{AuditLog, CallOrigin};
+use auth::{DEFAULT_IAM_ENDPOINT, SubjectToken, TokenSource};
+use cli::{Cli, Command, ServeArgs, Transport};
+use config::{Profile, Profiles, ServerConfig};
+use confirm::{Confirmations, RedeemError};
+use http_server::{ClientCredentials, HttpAccessConfig};
+use prompts::PromptRequest;
+use registry::method_registry;
+use registry_diff::RegistryDiff;
+use resources::{Crawl, ResourceRef};
+use responses::{
+    ConnectionResponse, DashboardResponse, DatasetResponse, GetEntriesResponse,
+    ListDirectoryResponse, WorkbookResponse,
+};
+use retry::RetryPolicy;
+use schema::ValidationMode;
+use subscriptions::{Session, Subscriptions};
+use throttle::ThrottleConfig;
+
 type ToolJson = Json<Map<String, Value>>;
 
 const DEFAULT_BASE_URL: &str = "https://api.datalens.tech";
 const DEFAULT_API_VERSION: &str = "0";
 const DEFAULT_TIMEOUT_SECONDS: u64 = 30;
-
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct MethodRegistry {
-    snapshot_date: String,
-    source_url: String,
-    openapi_version: Option<String>,
-    api_info: Value,
-    methods: Vec<MethodRegistryItem>,
-}
-
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct MethodRegistryItem {
-    method: String,
-    category: String,
-    experimental: bool,
-    typed_tool: Option<String>,
-    invoke_with: String,
-    summary: Option<String>,
-    description: Option<String>,
-    request_schema: Value,
-    #[serde(default)]
-    request_example: Option<Value>,
-    #[serde(default)]
-    response_example: Option<Value>,
-}
-
-static METHOD_REGISTRY: OnceLock<MethodRegistry> = OnceLock::new();
-
-fn method_registry() -> &'static MethodRegistry {
-    METHOD_REGISTRY.get_or_init(|| {
-        serde_json::from_str(include_str!("../openapi/datalens-rpc-methods.json"))
-            .expect("embedded DataLens method registry must be valid JSON")
-    })
-}
+const DEFAULT_TOKEN_COMMAND_TTL_SECONDS: u64 = 3600;
+const DEFAULT_HTTP_LISTEN: &str = "127.0.0.1:8080";
 
 #[derive(Clone, Debug)]
 struct AppConfig {
     base_url: String,
     api_version: String,
     org_id: Option<String>,
-    subject_token: Option<String>,
+    token_source: Option<TokenSource>,
     timeout: Duration,
+    retry: RetryPolicy,
+    throttle: ThrottleConfig,
+    validation: ValidationMode,
+    read_only: bool,
+    /// Policy files a call must pass, in order: `DATALENS_POLICY`, then the profile's own.
+    policies: Vec<PathBuf>,
+    dry_run: bool,
+    confirm_ttl: Duration,
 }
 
 impl AppConfig {
@@ -81,10 +107,21 @@ impl AppConfig {
             api_version: env_non_empty("DATALENS_API_VERSION")
                 .unwrap_or_else(|| DEFAULT_API_VERSION.to_owned()),
             org_id: env_non_empty("DATALENS_ORG_ID"),
-            subject_token: env_non_empty("DATALENS_IAM_TOKEN")
-                .or_else(|| env_non_empty("YC_IAM_TOKEN"))
-                .or_else(|| env_non_empty("DATALENS_SUBJECT_TOKEN")),
+            token_source: token_source_from_env(),
             timeout: Duration::from_secs(timeout_seconds),
+            retry: RetryPolicy::from_env(),
+            throttle: ThrottleConfig::from_env(),
+            validation: ValidationMode::from_env(),
+            read_only: env_flag("DATALENS_READ_ONLY"),
+            policies: env_non_empty("DATALENS_POLICY")
+                .map(PathBuf::from)
+                .into_iter()
+                .collect(),
+            dry_run: env_flag("DATALENS_DRY_RUN"),
+            confirm_ttl: Duration::from_secs(parse_non_negative(
+                "DATALENS_CONFIRM_TTL_SECONDS",
+                confirm::DEFAULT_CONFIRM_TTL_SECONDS,
+            )),
         }
     }
 }
@@ -93,7 +130,14 @@ impl AppConfig {
 struct DataLensServer {
     tool_router: ToolRouter<Self>,
     http: Client,
-    cfg: AppConfig,
+    profiles: Arc<Profiles>,
+    confirmations: Arc<Confirmations>,
+    audit: Option<Arc<AuditLog>>,
+    subscriptions: Option<Arc<Subscriptions>>,
+    /// Resources this MCP session subscribed to; see [`DataLensServer::for_session`].
+    session: Arc<Session>,
+    /// Where `datalens_registry_diff` may read registry files; `None` allows none.
+    registry_dir: Option<Arc<Path>>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -101,6 +145,12 @@ struct DatalensRpcArgs {
     method: String,
     #[serde(default = "empty_json_object")]
     payload: Value,
+    #[serde(default)]
+    profile: Option<String>,
+    #[serde(default, alias = "dryRun")]
+    dry_run: Option<bool>,
+    #[serde(default, alias = "confirmToken")]
+    confirm_token: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -119,6 +169,8 @@ struct ListDirectoryArgs {
     page_size: Option<serde_json::Number>,
     #[serde(default, alias = "includePermissionsInfo")]
     include_permissions_info: Option<bool>,
+    #[serde(default)]
+    profile: Option<String>,
     #[serde(flatten)]
     extra: BTreeMap<String, Value>,
 }
@@ -131,6 +183,8 @@ struct GetDatasetArgs {
     workbook_id: Option<String>,
     #[serde(default, alias = "revId", alias = "rev_id")]
     rev_id: Option<String>,
+    #[serde(default)]
+    profile: Option<String>,
     #[serde(flatten)]
     extra: BTreeMap<String, Value>,
 }
@@ -145,6 +199,8 @@ struct GetConnectionArgs {
     binded_dataset_id: Option<String>,
     #[serde(default, alias = "revId", alias = "rev_id")]
     rev_id: Option<String>,
+    #[serde(default)]
+    profile: Option<String>,
     #[serde(flatten)]
     extra: BTreeMap<String, Value>,
 }
@@ -169,6 +225,8 @@ struct GetDashboardArgs {
     branch: Option<String>,
     #[serde(default, alias = "workbookId")]
     workbook_id: Option<String>,
+    #[serde(default)]
+    profile: Option<String>,
     #[serde(flatten)]
     extra: BTreeMap<String, Value>,
 }
@@ -199,6 +257,8 @@ struct GetEntriesArgs {
     scope: Option<String>,
     #[serde(default)]
     ids: Option<Value>,
+    #[serde(default)]
+    profile: Option<String>,
     #[serde(flatten)]
     extra: BTreeMap<String, Value>,
 }
@@ -217,6 +277,8 @@ struct GetEntriesRelationsArgs {
     page_token: Option<String>,
     #[serde(default)]
     scope: Option<String>,
+    #[serde(default)]
+    profile: Option<String>,
     #[serde(flatten)]
     extra: BTreeMap<String, Value>,
 }
@@ -225,6 +287,8 @@ struct GetEntriesRelationsArgs {
 struct GetEntriesPermissionsArgs {
     #[serde(alias = "entryIds")]
     entry_ids: Vec<String>,
+    #[serde(default)]
+    profile: Option<String>,
     #[serde(flatten)]
     extra: BTreeMap<String, Value>,
 }
@@ -249,6 +313,8 @@ struct GetChartArgs {
     include_favorite: Option<bool>,
     #[serde(default)]
     branch: Option<String>,
+    #[serde(default)]
+    profile: Option<String>,
     #[serde(flatten)]
     extra: BTreeMap<String, Value>,
 }
@@ -259,6 +325,8 @@ struct GetWorkbookArgs {
     workbook_id: String,
     #[serde(default, alias = "includePermissionsInfo")]
     include_permissions_info: Option<bool>,
+    #[serde(default)]
+    profile: Option<String>,
     #[serde(flatten)]
     extra: BTreeMap<String, Value>,
 }
@@ -267,6 +335,10 @@ struct GetWorkbookArgs {
 struct CreateConnectionArgs {
     #[serde(rename = "type")]
     connection_type: String,
+    #[serde(default)]
+    profile: Option<String>,
+    #[serde(default, alias = "dryRun")]
+    dry_run: Option<bool>,
     #[serde(flatten)]
     extra: BTreeMap<String, Value>,
 }
@@ -286,6 +358,10 @@ struct CreateDatasetArgs {
     preview: Option<bool>,
     #[serde(default, alias = "workbookId")]
     workbook_id: Option<String>,
+    #[serde(default)]
+    profile: Option<String>,
+    #[serde(default, alias = "dryRun")]
+    dry_run: Option<bool>,
     #[serde(flatten)]
     extra: BTreeMap<String, Value>,
 }
@@ -298,6 +374,8 @@ struct ValidateDatasetArgs {
     workbook_id: Option<String>,
     #[serde(default)]
     data: Option<Value>,
+    #[serde(default)]
+    profile: Option<String>,
     #[serde(flatten)]
     extra: BTreeMap<String, Value>,
 }
@@ -305,126 +383,312 @@ struct ValidateDatasetArgs {
 #[derive(Debug, Default, Deserialize, schemars::JsonSchema)]
 struct NoArgs {}
 
+#[derive(Debug, Default, Deserialize, schemars::JsonSchema)]
+struct DoctorArgs {
+    #[serde(default)]
+    profile: Option<String>,
+}
+
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 struct GetMethodSchemaArgs {
     #[serde(alias = "methodName")]
     method: String,
 }
 
+#[derive(Debug, Default, Deserialize, schemars::JsonSchema)]
+struct AuditLogQueryArgs {
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(default)]
+    profile: Option<String>,
+    #[serde(default)]
+    tool: Option<String>,
+    #[serde(default)]
+    outcome: Option<String>,
+    #[serde(default)]
+    since: Option<String>,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+struct RegistryDiffArgs {
+    #[serde(default = "default_diff_base")]
+    base: String,
+    #[serde(default = "default_diff_target")]
+    target: String,
+}
+
+fn default_diff_base() -> String {
+    "embedded".to_owned()
+}
+
+fn default_diff_target() -> String {
+    "active".to_owned()
+}
+
 #[tool_router]
 impl DataLensServer {
-    fn new(cfg: AppConfig) -> Result<Self> {
+    fn new(config: ServerConfig) -> Result<Self> {
+        let audit = config.audit.clone().map(|cfg| Arc::new(AuditLog::new(cfg)));
+        let subscriptions = config
+            .subscription_poll
+            .map(|interval| Arc::new(Subscriptions::new(interval)));
+        let registry_dir = config.registry_dir.as_deref().map(Arc::from);
+        let profiles = Profiles::from_config(config)?;
         let http = Client::builder()
-            .timeout(cfg.timeout)
+            .timeout(profiles.default_profile().cfg.timeout)
             .build()
             .context("failed to build HTTP client")?;
 
+        let mut tool_router = Self::tool_router();
+        for item in &method_registry().methods {
+            if let Some(route) = item
+                .typed_tool
+                .as_deref()
+                .and_then(|tool| tool_router.map.get_mut(tool))
+            {
+                route.attr.annotations = Some(item.tool_annotations());
+            }
+        }
+        // Write tools cannot succeed on any profile, so they are not offered at all.
+        if profiles.iter().all(|(_, profile)| profile.cfg.read_only) {
+            for item in &method_registry().methods {
+                if let Some(tool) = item.typed_tool.as_deref()
+                    && item.category != "read"
+                {
+                    tool_router.remove_route(tool);
+                }
+            }
+            // Only read methods get through `datalens_rpc` now.
+            if let Some(route) = tool_router.map.get_mut("datalens_rpc") {
+                route.attr.annotations = Some(
+                    ToolAnnotations::new()
+                        .read_only(true)
+                        .destructive(false)
+                        .idempotent(true)
+                        .open_world(true),
+                );
+            }
+        }
+
         Ok(Self {
-            tool_router: Self::tool_router(),
+            tool_router,
             http,
-            cfg,
+            profiles: Arc::new(profiles),
+            confirmations: Arc::default(),
+            audit,
+            subscriptions,
+            session: Arc::default(),
+            registry_dir,
         })
     }
 
+    /// A server for a new HTTP session: shared state, but its own resource subscriptions and
+    /// confirmation tokens.
+    fn for_session(&self) -> Self {
+        Self {
+            session: Arc::default(),
+            confirmations: Arc::default(),
+            ..self.clone()
+        }
+    }
+
     #[tool(
         name = "datalens_rpc",
-        description = "Call any DataLens RPC method by its method name and JSON payload."
+        description = "Call any DataLens RPC method by its method name and JSON payload. With dry_run=true, returns the URL, redacted headers, payload and schema validation result instead of calling DataLens. delete* methods first return a summary of affected entries and a confirm_token; show it to the user and repeat the identical call with confirm_token to run it.",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false,
+            open_world_hint = true
+        )
     )]
     async fn datalens_rpc(
         &self,
         Parameters(args): Parameters<DatalensRpcArgs>,
     ) -> Result<ToolJson, McpError> {
         let payload = normalize_json_value(args.payload, "payload")?;
-        self.call_rpc(&args.method, payload).await
+        if let Some(confirmation) = self
+            .confirm_destructive(
+                args.profile.as_deref(),
+                &args.method,
+                &payload,
+                args.dry_run,
+                args.confirm_token.as_deref(),
+            )
+            .await?
+        {
+            return Ok(confirmation);
+        }
+        self.call_rpc_with(args.profile.as_deref(), &args.method, payload, args.dry_run)
+            .await
     }
 
     #[tool(
-        name = "datalens_list_methods",
-        description = "List DataLens API methods known to this server, with MCP tool names and method categories."
+        name = "datalens_list_profiles",
+        description = "List configured DataLens profiles (organizations). Pass `profile` to other tools to target a non-default one.",
+        annotations(read_only_hint = true, open_world_hint = false)
     )]
-    async fn datalens_list_methods(
+    async fn datalens_list_profiles(
         &self,
         Parameters(_args): Parameters<NoArgs>,
     ) -> Result<ToolJson, McpError> {
-        let registry = method_registry();
-        let methods = registry
-            .methods
+        let profiles = self
+            .profiles
             .iter()
-            .map(|item| {
-                let mcp_tool = item
-                    .typed_tool
-                    .clone()
-                    .unwrap_or_else(|| "datalens_rpc".to_owned());
+            .map(|(name, profile)| {
                 json!({
-                    "method": item.method,
-                    "mcpTool": mcp_tool,
-                    "typedTool": item.typed_tool,
-                    "invokeWith": item.invoke_with,
-                    "category": item.category,
-                    "experimental": item.experimental,
-                    "summary": item.summary,
+                    "name": name,
+                    "default": name == self.profiles.default_name(),
+                    "orgId": profile.cfg.org_id,
+                    "baseUrl": profile.cfg.base_url,
+                    "apiVersion": profile.cfg.api_version,
+                    "tokenSource": profile.cfg.token_source.as_ref().map(TokenSource::describe),
+                    "readOnly": profile.cfg.read_only,
+                    "policies": profile.cfg.policies,
+                    "dryRun": profile.cfg.dry_run,
                 })
             })
             .collect::<Vec<_>>();
 
         let response = json!({
-            "snapshotDate": registry.snapshot_date,
-            "sourceUrl": registry.source_url,
-            "openapiVersion": registry.openapi_version,
-            "apiInfo": registry.api_info,
-            "totalMethods": methods.len(),
-            "genericTool": "datalens_rpc",
-            "methods": methods,
+            "defaultProfile": self.profiles.default_name(),
+            "profiles": profiles,
         });
         let response = response.as_object().cloned().ok_or_else(|| {
-            McpError::internal_error("failed to build method catalog response object", None)
+            McpError::internal_error("failed to build profile list response object", None)
         })?;
 
         Ok(Json(response))
     }
 
+    #[tool(
+        name = "datalens_list_methods",
+        description = "List DataLens API methods known to this server, with MCP tool names and method categories.",
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    async fn datalens_list_methods(
+        &self,
+        Parameters(_args): Parameters<NoArgs>,
+    ) -> Result<ToolJson, McpError> {
+        method_catalog().map(Json)
+    }
+
     #[tool(
         name = "datalens_get_method_schema",
-        description = "Return OpenAPI request schema and invocation hints for a DataLens RPC method."
+        description = "Return OpenAPI request schema and invocation hints for a DataLens RPC method.",
+        annotations(read_only_hint = true, open_world_hint = false)
     )]
     async fn datalens_get_method_schema(
         &self,
         Parameters(args): Parameters<GetMethodSchemaArgs>,
     ) -> Result<ToolJson, McpError> {
-        let registry = method_registry();
-        let method = registry
-            .methods
-            .iter()
-            .find(|item| item.method.eq_ignore_ascii_case(&args.method))
-            .ok_or_else(|| {
-                McpError::invalid_params(
-                    format!("Unknown DataLens RPC method: {}", args.method),
-                    Some(json!({
-                        "hint": "Call datalens_list_methods first to discover valid methods."
-                    })),
+        method_schema(&args.method).map(Json)
+    }
+
+    #[tool(
+        name = "datalens_audit_log_query",
+        description = "Search recent entries of the local audit log of DataLens RPC calls, newest first. Filters: method (name or glob), profile, tool, outcome ('ok', 'dry_run', 'error'), since (RFC 3339 timestamp), limit (default 50, max 500).",
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    async fn datalens_audit_log_query(
+        &self,
+        Parameters(args): Parameters<AuditLogQueryArgs>,
+    ) -> Result<ToolJson, McpError> {
+        let audit = self.audit.clone().ok_or_else(|| {
+            McpError::invalid_request(
+                "audit log is disabled",
+                Some(json!({"hint": "Set DATALENS_AUDIT_LOG to a file path to record RPC calls."})),
+            )
+        })?;
+        let since = args
+            .since
+            .map(|raw| {
+                chrono::DateTime::parse_from_rfc3339(&raw)
+                    .map(|since| since.with_timezone(&chrono::Utc))
+                    .map_err(|error| {
+                        McpError::invalid_params(
+                            format!("`since` must be an RFC 3339 timestamp: {error}"),
+                            Some(json!({"since": raw})),
+                        )
+                    })
+            })
+            .transpose()?;
+        let query = audit::Query {
+            method: args.method,
+            profile: args.profile,
+            tool: args.tool,
+            outcome: args.outcome,
+            since,
+            limit: args
+                .limit
+                .unwrap_or(audit::DEFAULT_QUERY_LIMIT)
+                .clamp(1, audit::MAX_QUERY_LIMIT),
+        };
+
+        let path = audit.path().to_path_buf();
+        let entries = tokio::task::spawn_blocking(move || audit.query(&query))
+            .await
+            .map_err(|error| {
+                McpError::internal_error(format!("audit log query failed: {error}"), None)
+            })?
+            .map_err(|error| {
+                McpError::internal_error(
+                    format!("failed to read audit log: {error}"),
+                    Some(json!({"path": path})),
                 )
             })?;
 
         let response = json!({
-            "snapshotDate": registry.snapshot_date,
-            "sourceUrl": registry.source_url,
-            "openapiVersion": registry.openapi_version,
-            "method": method.method,
-            "category": method.category,
-            "experimental": method.experimental,
-            "typedTool": method.typed_tool,
-            "invokeWith": method.invoke_with,
-            "summary": method.summary,
-            "description": method.description,
-            "requestSchema": method.request_schema,
-            "requestExample": method.request_example,
-            "responseExample": method.response_example,
+            "path": path,
+            "count": entries.len(),
+            "entries": entries,
         });
-        let response = response.as_object().cloned().ok_or_else(|| {
-            McpError::internal_error("failed to build method schema response object", None)
-        })?;
+        response.as_object().cloned().map(Json).ok_or_else(|| {
+            McpError::internal_error("failed to build audit log response object", None)
+        })
+    }
 
-        Ok(Json(response))
+    #[tool(
+        name = "datalens_registry_diff",
+        description = "Compare two method registries: added/removed methods, newly required fields, removed properties, changed enums and experimental-flag flips. `base` and `target` are 'embedded' (compiled-in snapshot), 'active' (the registry in use, see DATALENS_OPENAPI_PATH) or the name of a registry JSON or OpenAPI document in the server's DATALENS_REGISTRY_DIR. Defaults: embedded vs active.",
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    async fn datalens_registry_diff(
+        &self,
+        Parameters(args): Parameters<RegistryDiffArgs>,
+    ) -> Result<ToolJson, McpError> {
+        let dir = self.registry_dir.clone();
+        tokio::task::spawn_blocking(move || {
+            let open = |spec: &str| {
+                registry_diff::open_for_tool(spec, dir.as_deref()).map_err(|err| {
+                    McpError::invalid_params(
+                        format!("failed to load registry {spec}: {err:#}"),
+                        Some(json!({ "registry": spec })),
+                    )
+                })
+            };
+            let base = open(&args.base)?;
+            let target = open(&args.target)?;
+            let diff = RegistryDiff::between(&args.base, &base, &args.target, &target);
+            Ok(Json(diff.to_json()))
+        })
+        .await
+        .map_err(|err| McpError::internal_error(format!("registry diff failed: {err}"), None))?
+    }
+
+    #[tool(
+        name = "datalens_doctor",
+        description = "Check DataLens setup: org ID and token presence, token kind, base URL resolution, a listDirectory call on '/', and the (redacted) auth headers sent. Returns actionable hints for failing checks.",
+        annotations(read_only_hint = true, open_world_hint = true)
+    )]
+    async fn datalens_doctor(
+        &self,
+        Parameters(args): Parameters<DoctorArgs>,
+    ) -> Result<ToolJson, McpError> {
+        let report = doctor::diagnose(self, args.profile.as_deref()).await?;
+        Ok(Json(report.to_json()))
     }
 
     #[tool(
@@ -434,7 +698,7 @@ impl DataLensServer {
     async fn datalens_list_directory(
         &self,
         Parameters(args): Parameters<ListDirectoryArgs>,
-    ) -> Result<ToolJson, McpError> {
+    ) -> Result<Json<ListDirectoryResponse>, McpError> {
         let mut payload = Map::new();
         payload.insert("path".to_owned(), Value::String(args.path));
         if let Some(created_by) = args.created_by {
@@ -469,7 +733,13 @@ impl DataLensServer {
         }
         extend_with_extra(&mut payload, args.extra);
 
-        self.call_rpc("listDirectory", Value::Object(payload)).await
+        self.call_rpc(
+            args.profile.as_deref(),
+            "listDirectory",
+            Value::Object(payload),
+        )
+        .await
+        .and_then(typed_response)
     }
 
     #[tool(
@@ -479,7 +749,7 @@ impl DataLensServer {
     async fn datalens_get_entries(
         &self,
         Parameters(args): Parameters<GetEntriesArgs>,
-    ) -> Result<ToolJson, McpError> {
+    ) -> Result<Json<GetEntriesResponse>, McpError> {
         let mut payload = Map::new();
         if let Some(exclude_locked) = args.exclude_locked {
             payload.insert("excludeLocked".to_owned(), Value::Bool(exclude_locked));
@@ -534,7 +804,13 @@ impl DataLensServer {
         }
         extend_with_extra(&mut payload, args.extra);
 
-        self.call_rpc("getEntries", Value::Object(payload)).await
+        self.call_rpc(
+            args.profile.as_deref(),
+            "getEntries",
+            Value::Object(payload),
+        )
+        .await
+        .and_then(typed_response)
     }
 
     #[tool(
@@ -570,8 +846,12 @@ impl DataLensServer {
         }
         extend_with_extra(&mut payload, args.extra);
 
-        self.call_rpc("getEntriesRelations", Value::Object(payload))
-            .await
+        self.call_rpc(
+            args.profile.as_deref(),
+            "getEntriesRelations",
+            Value::Object(payload),
+        )
+        .await
     }
 
     #[tool(
@@ -589,8 +869,12 @@ impl DataLensServer {
         );
         extend_with_extra(&mut payload, args.extra);
 
-        self.call_rpc("getEntriesPermissions", Value::Object(payload))
-            .await
+        self.call_rpc(
+            args.profile.as_deref(),
+            "getEntriesPermissions",
+            Value::Object(payload),
+        )
+        .await
     }
 
     #[tool(
@@ -626,8 +910,12 @@ impl DataLensServer {
         }
         extend_with_extra(&mut payload, args.extra);
 
-        self.call_rpc("getWizardChart", Value::Object(payload))
-            .await
+        self.call_rpc(
+            args.profile.as_deref(),
+            "getWizardChart",
+            Value::Object(payload),
+        )
+        .await
     }
 
     #[tool(
@@ -637,7 +925,7 @@ impl DataLensServer {
     async fn datalens_get_workbook(
         &self,
         Parameters(args): Parameters<GetWorkbookArgs>,
-    ) -> Result<ToolJson, McpError> {
+    ) -> Result<Json<WorkbookResponse>, McpError> {
         let mut payload = Map::new();
         payload.insert("workbookId".to_owned(), Value::String(args.workbook_id));
         if let Some(include_permissions_info) = args.include_permissions_info {
@@ -648,7 +936,13 @@ impl DataLensServer {
         }
         extend_with_extra(&mut payload, args.extra);
 
-        self.call_rpc("getWorkbook", Value::Object(payload)).await
+        self.call_rpc(
+            args.profile.as_deref(),
+            "getWorkbook",
+            Value::Object(payload),
+        )
+        .await
+        .and_then(typed_response)
     }
 
     #[tool(
@@ -684,8 +978,12 @@ impl DataLensServer {
         }
         extend_with_extra(&mut payload, args.extra);
 
-        self.call_rpc("getEditorChart", Value::Object(payload))
-            .await
+        self.call_rpc(
+            args.profile.as_deref(),
+            "getEditorChart",
+            Value::Object(payload),
+        )
+        .await
     }
 
     #[tool(
@@ -721,7 +1019,12 @@ impl DataLensServer {
         }
         extend_with_extra(&mut payload, args.extra);
 
-        self.call_rpc("getQLChart", Value::Object(payload)).await
+        self.call_rpc(
+            args.profile.as_deref(),
+            "getQLChart",
+            Value::Object(payload),
+        )
+        .await
     }
 
     #[tool(
@@ -731,7 +1034,7 @@ impl DataLensServer {
     async fn datalens_get_dataset(
         &self,
         Parameters(args): Parameters<GetDatasetArgs>,
-    ) -> Result<ToolJson, McpError> {
+    ) -> Result<Json<DatasetResponse>, McpError> {
         let mut payload = Map::new();
         payload.insert("datasetId".to_owned(), Value::String(args.dataset_id));
 
@@ -743,7 +1046,13 @@ impl DataLensServer {
         }
         extend_with_extra(&mut payload, args.extra);
 
-        self.call_rpc("getDataset", Value::Object(payload)).await
+        self.call_rpc(
+            args.profile.as_deref(),
+            "getDataset",
+            Value::Object(payload),
+        )
+        .await
+        .and_then(typed_response)
     }
 
     #[tool(
@@ -753,7 +1062,7 @@ impl DataLensServer {
     async fn datalens_get_dashboard(
         &self,
         Parameters(args): Parameters<GetDashboardArgs>,
-    ) -> Result<ToolJson, McpError> {
+    ) -> Result<Json<DashboardResponse>, McpError> {
         let mut payload = Map::new();
         payload.insert("dashboardId".to_owned(), Value::String(args.dashboard_id));
 
@@ -780,7 +1089,13 @@ impl DataLensServer {
         }
         extend_with_extra(&mut payload, args.extra);
 
-        self.call_rpc("getDashboard", Value::Object(payload)).await
+        self.call_rpc(
+            args.profile.as_deref(),
+            "getDashboard",
+            Value::Object(payload),
+        )
+        .await
+        .and_then(typed_response)
     }
 
     #[tool(
@@ -790,7 +1105,7 @@ impl DataLensServer {
     async fn datalens_get_connection(
         &self,
         Parameters(args): Parameters<GetConnectionArgs>,
-    ) -> Result<ToolJson, McpError> {
+    ) -> Result<Json<ConnectionResponse>, McpError> {
         let mut payload = Map::new();
         payload.insert("connectionId".to_owned(), Value::String(args.connection_id));
         if let Some(workbook_id) = args.workbook_id {
@@ -807,12 +1122,18 @@ impl DataLensServer {
         }
         extend_with_extra(&mut payload, args.extra);
 
-        self.call_rpc("getConnection", Value::Object(payload)).await
+        self.call_rpc(
+            args.profile.as_deref(),
+            "getConnection",
+            Value::Object(payload),
+        )
+        .await
+        .and_then(typed_response)
     }
 
     #[tool(
         name = "datalens_create_connection",
-        description = "Call createConnection. Include required connection fields for the selected `type`."
+        description = "Call createConnection. Include required connection fields for the selected `type`. Pass dry_run=true to preview the request without sending it."
     )]
     async fn datalens_create_connection(
         &self,
@@ -822,13 +1143,18 @@ impl DataLensServer {
         payload.insert("type".to_owned(), Value::String(args.connection_type));
         extend_with_extra(&mut payload, args.extra);
 
-        self.call_rpc("createConnection", Value::Object(payload))
-            .await
+        self.call_rpc_with(
+            args.profile.as_deref(),
+            "createConnection",
+            Value::Object(payload),
+            args.dry_run,
+        )
+        .await
     }
 
     #[tool(
         name = "datalens_create_dataset",
-        description = "Call createDataset. Required: dataset. For workbook-scoped creation, pass workbook_id."
+        description = "Call createDataset. Required: dataset. For workbook-scoped creation, pass workbook_id. Pass dry_run=true to preview the request without sending it."
     )]
     async fn datalens_create_dataset(
         &self,
@@ -865,7 +1191,13 @@ impl DataLensServer {
         }
         extend_with_extra(&mut payload, args.extra);
 
-        self.call_rpc("createDataset", Value::Object(payload)).await
+        self.call_rpc_with(
+            args.profile.as_deref(),
+            "createDataset",
+            Value::Object(payload),
+            args.dry_run,
+        )
+        .await
     }
 
     #[tool(
@@ -885,137 +1217,1061 @@ impl DataLensServer {
         payload.insert("data".to_owned(), normalize_json_value(data, "data")?);
         extend_with_extra(&mut payload, args.extra);
 
-        self.call_rpc("validateDataset", Value::Object(payload))
-            .await
+        self.call_rpc(
+            args.profile.as_deref(),
+            "validateDataset",
+            Value::Object(payload),
+        )
+        .await
     }
 }
 
-#[tool_handler(router = self.tool_router)]
 impl ServerHandler for DataLensServer {
     fn get_info(&self) -> ServerInfo {
+        let mut capabilities = ServerCapabilities::builder()
+            .enable_prompts()
+            .enable_resources()
+            .enable_tools()
+            .build();
+        if let Some(resources) = &mut capabilities.resources {
+            resources.subscribe = Some(self.subscriptions.is_some());
+        }
         ServerInfo {
             instructions: Some(
-                "Yandex DataLens MCP server. Configure DATALENS_ORG_ID and YC_IAM_TOKEN (or DATALENS_IAM_TOKEN) before calling tools. For broad RPC usage: call datalens_list_methods, then datalens_get_method_schema for the chosen method, then call either a typed tool or datalens_rpc."
+                "Yandex DataLens MCP server. Configure DATALENS_ORG_ID and YC_IAM_TOKEN (or DATALENS_IAM_TOKEN) before calling tools. For broad RPC usage: call datalens_list_methods, then datalens_get_method_schema for the chosen method, then call either a typed tool or datalens_rpc. When several organizations are configured, call datalens_list_profiles and pass `profile` to target one. Datasets, dashboards, charts, workbooks and connections are also resources: datalens://dataset/{id}, datalens://dashboard/{id}, datalens://chart/{wizard|ql|editor}/{id}, datalens://workbook/{id}, datalens://connection/{id}. Prompts cover common workflows: explaining a dashboard, auditing workbook permissions, creating a dataset from a connection table and finding the charts that use a dataset."
                     .to_owned(),
             ),
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            capabilities,
             ..Default::default()
         }
     }
-}
-
-impl DataLensServer {
-    async fn call_rpc(&self, method: &str, payload: Value) -> Result<ToolJson, McpError> {
-        if !payload.is_object() {
-            return Err(McpError::invalid_params(
-                "payload must be a JSON object",
-                Some(json!({"method": method})),
-            ));
-        }
-
-        let org_id = self.cfg.org_id.as_deref().ok_or_else(|| {
-            McpError::invalid_request("DATALENS_ORG_ID environment variable is required", None)
-        })?;
-        let subject_token = self.cfg.subject_token.as_deref().ok_or_else(|| {
-            McpError::invalid_request(
-                "YC_IAM_TOKEN (or DATALENS_IAM_TOKEN) environment variable is required",
-                None,
-            )
-        })?;
 
-        let url = format!("{}/rpc/{}", self.cfg.base_url.trim_end_matches('/'), method);
-        debug!(method = %method, url = %url, "calling DataLens API");
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParams,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let scope = RequestScope::new(&context, request.name.to_string());
+        let tcc = ToolCallContext::new(self, request, context);
+        scope.run(self.tool_router.call(tcc)).await
+    }
 
-        let mut headers = HeaderMap::new();
-        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-        add_header(&mut headers, "x-dl-api-version", &self.cfg.api_version)?;
-        add_header(&mut headers, "x-dl-org-id", org_id)?;
-        add_header(&mut headers, "x-yacloud-subjecttoken", subject_token)?;
+    async fn list_resources(
+        &self,
+        request: Option<PaginatedRequestParams>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        let cursor = request.and_then(|request| request.cursor);
+        RequestScope::new(&context, "resources/list".to_owned())
+            .run(self.list_resources_page(cursor))
+            .await
+    }
 
-        let legacy_auth_header = if subject_token.starts_with("OAuth ") {
-            subject_token.to_owned()
-        } else {
-            format!("OAuth {subject_token}")
-        };
-        add_header(&mut headers, "x-dl-auth-token", &legacy_auth_header)?;
+    async fn list_resource_templates(
+        &self,
+        _request: Option<PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourceTemplatesResult, McpError> {
+        Ok(ListResourceTemplatesResult {
+            resource_templates: resources::templates(),
+            meta: None,
+            next_cursor: None,
+        })
+    }
 
-        let response = self
-            .http
-            .post(url)
-            .headers(headers)
-            .json(&payload)
-            .send()
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParams,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        RequestScope::new(&context, "resources/read".to_owned())
+            .run(self.read_resource_uri(request.uri))
             .await
-            .map_err(|error| {
-                McpError::internal_error(
-                    format!("failed to reach DataLens API: {error}"),
-                    Some(json!({"method": method})),
-                )
-            })?;
+    }
 
-        let status = response.status();
-        let body = response.text().await.map_err(|error| {
-            McpError::internal_error(format!("failed to read response: {error}"), None)
+    async fn subscribe(
+        &self,
+        request: SubscribeRequestParams,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        let subscriptions = self.subscriptions.clone().ok_or_else(|| {
+            McpError::invalid_request(
+                "resource subscriptions are disabled",
+                Some(json!({"hint": "Set DATALENS_SUBSCRIPTION_POLL_SECONDS to a positive number of seconds."})),
+            )
+        })?;
+        let target = ResourceRef::parse(&request.uri).map_err(|message| {
+            McpError::resource_not_found(message, Some(json!({ "uri": request.uri })))
         })?;
 
-        if !status.is_success() {
-            let response_data = parse_response_data(&body);
-            return Err(McpError::internal_error(
-                format!("DataLens API returned {status} for method {method}"),
-                Some(json!({
-                    "method": method,
-                    "status": status.as_u16(),
-                    "response": response_data,
-                })),
-            ));
+        self.session.subscribe(
+            request.uri,
+            target,
+            context.peer.clone(),
+            RequestScope::new(&context, "resources/subscribe".to_owned()),
+            chrono::Utc::now(),
+        );
+        if subscriptions.register(&self.session) {
+            tokio::spawn(self.for_session().poll_subscriptions(subscriptions));
         }
+        Ok(())
+    }
 
-        if body.trim().is_empty() {
-            return Ok(Json(Map::new()));
-        }
+    async fn unsubscribe(
+        &self,
+        request: UnsubscribeRequestParams,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        self.session.unsubscribe(&request.uri);
+        Ok(())
+    }
 
-        let parsed = serde_json::from_str::<Map<String, Value>>(&body).map_err(|error| {
-            McpError::internal_error(
-                format!("DataLens API returned invalid or non-object JSON: {error}"),
-                Some(json!({
-                    "method": method,
-                    "body": truncate_utf8(&body, 2000),
-                })),
-            )
-        })?;
+    async fn list_prompts(
+        &self,
+        _request: Option<PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListPromptsResult, McpError> {
+        Ok(ListPromptsResult {
+            prompts: prompts::list(),
+            meta: None,
+            next_cursor: None,
+        })
+    }
+
+    async fn get_prompt(
+        &self,
+        request: GetPromptRequestParams,
+        context: RequestContext<RoleServer>,
+    ) -> Result<GetPromptResult, McpError> {
+        let prompt = PromptRequest::parse(&request.name, request.arguments.as_ref())?;
+        RequestScope::new(&context, "prompts/get".to_owned())
+            .run(self.render_prompt(prompt))
+            .await
+    }
 
-        Ok(Json(parsed))
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        Ok(ListToolsResult {
+            tools: self.tool_router.list_all(),
+            meta: None,
+            next_cursor: None,
+        })
     }
-}
 
-fn extend_with_extra(target: &mut Map<String, Value>, extra: BTreeMap<String, Value>) {
-    for (key, value) in extra {
-        target.insert(key, value);
+    fn get_tool(&self, name: &str) -> Option<Tool> {
+        self.tool_router.get(name).cloned()
     }
 }
 
-fn normalize_json_value(value: Value, field_name: &str) -> Result<Value, McpError> {
-    let Value::String(raw) = value else {
-        return Ok(value);
-    };
+/// Per-request context that `call_rpc` picks up: over HTTP, the caller's own DataLens
+/// credentials from the request parts, and the audit log origin.
+#[derive(Clone)]
+struct RequestScope {
+    credentials: Option<ClientCredentials>,
+    origin: CallOrigin,
+}
 
-    let trimmed = raw.trim();
-    if !(trimmed.starts_with('{') || trimmed.starts_with('[')) {
-        return Ok(Value::String(raw));
+impl RequestScope {
+    fn new(context: &RequestContext<RoleServer>, tool: String) -> Self {
+        Self {
+            credentials: context
+                .extensions
+                .get::<axum::http::request::Parts>()
+                .map(ClientCredentials::from_parts),
+            origin: CallOrigin {
+                client: context
+                    .peer
+                    .peer_info()
+                    .map(|info| format!("{} {}", info.client_info.name, info.client_info.version)),
+                tool: Some(tool),
+            },
+        }
     }
 
-    serde_json::from_str::<Value>(trimmed).map_err(|error| {
-        McpError::invalid_params(
-            format!("field `{field_name}` must be valid JSON when passed as a string: {error}"),
-            None,
-        )
-    })
+    async fn run<F: Future>(self, future: F) -> F::Output {
+        let call = async {
+            match self.credentials {
+                Some(credentials) => {
+                    http_server::with_client_credentials(credentials, future).await
+                }
+                None => future.await,
+            }
+        };
+        audit::with_origin(self.origin, call).await
+    }
 }
 
-fn add_header(headers: &mut HeaderMap, key: &str, value: &str) -> Result<(), McpError> {
-    let name = HeaderName::from_bytes(key.as_bytes()).map_err(|error| {
-        McpError::invalid_params(format!("invalid header name `{key}`: {error}"), None)
+impl DataLensServer {
+    /// One page of `resources/list` from the default profile. Sources that fail (e.g. no
+    /// folder access in a workbook-only organization) are skipped; the error is returned
+    /// only when the first page of the listing finds nothing at all.
+    async fn list_resources_page(
+        &self,
+        cursor: Option<String>,
+    ) -> Result<ListResourcesResult, McpError> {
+        let first_page = cursor.is_none();
+        let mut crawl = match cursor {
+            Some(cursor) => Crawl::from_cursor(&cursor)
+                .map_err(|message| McpError::invalid_params(message, None))?,
+            None => Crawl::start(),
+        };
+
+        let mut first_error = None;
+        while let Some(source) = crawl.next_source() {
+            let (method, payload) = source.request();
+            match self.call_rpc(None, method, payload).await {
+                Ok(Json(response)) => {
+                    let resources = crawl.absorb(source, Value::Object(response));
+                    return Ok(ListResourcesResult {
+                        resources,
+                        meta: None,
+                        next_cursor: crawl.cursor(),
+                    });
+                }
+                Err(error) => {
+                    warn!(method = %method, "skipping resource listing source: {}", error.message);
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+        match first_error {
+            Some(error) if first_page => Err(error),
+            _ => Ok(ListResourcesResult::default()),
+        }
+    }
+
+    /// Fetches what `prompt` summarizes; failed calls end up in the prompt, not as an error.
+    async fn render_prompt(&self, prompt: PromptRequest) -> Result<GetPromptResult, McpError> {
+        let mut results = Vec::new();
+        for fetch in prompt.fetches() {
+            let (_, method, payload) = &fetch;
+            let result = self
+                .call_rpc(prompt.profile.as_deref(), method, payload.clone())
+                .await
+                .map(|Json(response)| Value::Object(response))
+                .map_err(|error| error.message.into_owned());
+            results.push((fetch, result));
+        }
+        Ok(prompt.render(results))
+    }
+
+    /// Runs for the life of the process, checking every subscribed session once per interval.
+    async fn poll_subscriptions(self, subscriptions: Arc<Subscriptions>) {
+        let mut ticks = tokio::time::interval(subscriptions.interval());
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        ticks.tick().await;
+        loop {
+            ticks.tick().await;
+            for session in subscriptions.live_sessions() {
+                self.poll_session(&session).await;
+            }
+        }
+    }
+
+    async fn poll_session(&self, session: &Session) {
+        let Some(plan) = session.plan() else {
+            return;
+        };
+        let started = chrono::Utc::now();
+        let changed = if plan.revisions {
+            self.changed_revisions(session, &plan).await
+        } else {
+            match plan
+                .scope
+                .clone()
+                .run(self.audit_entry_updates(plan.since, plan.page_token.clone()))
+                .await
+            {
+                Ok((entries, next_page_token)) => {
+                    session.record_updates(started, next_page_token);
+                    subscriptions::updated_uris(&plan.targets, &entries)
+                }
+                Err(error) if subscriptions::updates_unavailable(&error) => {
+                    warn!(
+                        "getAuditEntriesUpdates is unavailable, comparing revision IDs of subscribed resources instead: {}",
+                        error.message
+                    );
+                    session.use_revisions();
+                    self.changed_revisions(session, &plan).await
+                }
+                // The window is not advanced, so the next poll covers these changes too.
+                Err(error) => {
+                    warn!(
+                        "getAuditEntriesUpdates failed, retrying on the next poll: {}",
+                        error.message
+                    );
+                    Vec::new()
+                }
+            }
+        };
+
+        for uri in changed {
+            debug!(uri = %uri, "subscribed resource changed");
+            if let Err(error) = plan
+                .peer
+                .notify_resource_updated(ResourceUpdatedNotificationParam { uri })
+                .await
+            {
+                warn!("failed to send resource update notification: {error}");
+            }
+        }
+    }
+
+    /// Entries changed since `since`, from up to `MAX_UPDATE_PAGES` pages starting at
+    /// `page_token`, and the token of the next page when that cap cut the window short.
+    async fn audit_entry_updates(
+        &self,
+        since: chrono::DateTime<chrono::Utc>,
+        mut page_token: Option<String>,
+    ) -> Result<(Vec<Value>, Option<String>), McpError> {
+        let from = since.to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        let mut entries = Vec::new();
+        for _ in 0..subscriptions::MAX_UPDATE_PAGES {
+            let mut payload = json!({
+                "from": from,
+                "limit": subscriptions::UPDATES_PAGE_LIMIT,
+            });
+            if let Some(token) = page_token.take() {
+                payload["pageToken"] = Value::String(token);
+            }
+            let Json(mut response) = self
+                .call_rpc(None, "getAuditEntriesUpdates", payload)
+                .await?;
+            if let Some(Value::Array(page)) = response.remove("entries") {
+                entries.extend(page);
+            }
+            match response.remove("nextPageToken") {
+                Some(Value::String(token)) if !token.is_empty() => page_token = Some(token),
+                _ => return Ok((entries, None)),
+            }
+        }
+        Ok((entries, page_token))
+    }
+
+    /// Watched URIs whose revision ID changed since the previous poll. The first poll of a
+    /// URI only records its revision.
+    async fn changed_revisions(
+        &self,
+        session: &Session,
+        plan: &subscriptions::PollPlan,
+    ) -> Vec<String> {
+        let mut changed = Vec::new();
+        for (uri, target) in &plan.targets {
+            let (method, payload) = target.request();
+            match plan
+                .scope
+                .clone()
+                .run(self.call_rpc(None, method, payload))
+                .await
+            {
+                Ok(Json(response)) => {
+                    let fingerprint = subscriptions::fingerprint(&Value::Object(response));
+                    if session.record_fingerprint(uri, fingerprint) {
+                        changed.push(uri.clone());
+                    }
+                }
+                Err(error) => {
+                    warn!(uri = %uri, "failed to check subscribed resource: {}", error.message);
+                }
+            }
+        }
+        changed
+    }
+
+    async fn read_resource_uri(&self, uri: String) -> Result<ReadResourceResult, McpError> {
+        let target = ResourceRef::parse(&uri).map_err(|message| {
+            McpError::resource_not_found(message, Some(json!({ "uri": uri })))
+        })?;
+        let (method, payload) = target.request();
+        let Json(response) = self.call_rpc(None, method, payload).await?;
+        let text = serde_json::to_string(&response).map_err(|error| {
+            McpError::internal_error(
+                format!("failed to serialize {method} response: {error}"),
+                None,
+            )
+        })?;
+
+        Ok(ReadResourceResult {
+            contents: vec![ResourceContents::TextResourceContents {
+                uri,
+                mime_type: Some(resources::MIME_TYPE.to_owned()),
+                text,
+                meta: None,
+            }],
+        })
+    }
+
+    async fn call_rpc(
+        &self,
+        profile: Option<&str>,
+        method: &str,
+        payload: Value,
+    ) -> Result<ToolJson, McpError> {
+        self.call_rpc_with(profile, method, payload, None).await
+    }
+
+    /// `call_rpc` with a per-call dry-run request; see [`is_dry_run`]. Every call, including
+    /// rejected ones, goes to the audit log.
+    async fn call_rpc_with(
+        &self,
+        profile: Option<&str>,
+        method: &str,
+        payload: Value,
+        dry_run: Option<bool>,
+    ) -> Result<ToolJson, McpError> {
+        let Some(audit) = &self.audit else {
+            return self
+                .execute_rpc(profile, method, payload, dry_run)
+                .await
+                .map(|(_, response)| response);
+        };
+
+        let started = Instant::now();
+        let audited_payload = payload.clone();
+        let result = self.execute_rpc(profile, method, payload, dry_run).await;
+        audit.record(audit::Record {
+            profile: profile.unwrap_or(self.profiles.default_name()),
+            method,
+            category: effective_category(method),
+            payload: &audited_payload,
+            outcome: audit_outcome(&result),
+            latency: started.elapsed(),
+        });
+        result.map(|(_, response)| response)
+    }
+
+    /// Runs one call; the response comes with the HTTP status, `None` for a dry run.
+    async fn execute_rpc(
+        &self,
+        profile: Option<&str>,
+        method: &str,
+        payload: Value,
+        dry_run: Option<bool>,
+    ) -> Result<(Option<StatusCode>, ToolJson), McpError> {
+        if !payload.is_object() {
+            return Err(McpError::invalid_params(
+                "payload must be a JSON object",
+                Some(json!({"method": method})),
+            ));
+        }
+
+        let (profile_name, profile) = self.profiles.resolve(profile)?;
+        let cfg = &profile.cfg;
+        check_writable(profile_name, cfg, method)?;
+        check_policies(profile_name, profile, method, &payload)?;
+        let dry_run = is_dry_run(cfg, method, dry_run);
+        if !dry_run {
+            check_payload(cfg.validation, method, &payload)?;
+        }
+        let client = http_server::current_client_credentials().unwrap_or_default();
+        // A caller's org ID only goes with the caller's own token, never with the server's.
+        let org_id = client
+            .org_id
+            .as_deref()
+            .filter(|_| client.subject_token.is_some())
+            .or(cfg.org_id.as_deref())
+            .ok_or_else(|| {
+                McpError::invalid_request(
+                    "DATALENS_ORG_ID environment variable (or x-dl-org-id request header) is required",
+                    Some(json!({"profile": profile_name})),
+                )
+            })?;
+
+        let url = format!("{}/rpc/{}", cfg.base_url.trim_end_matches('/'), method);
+        debug!(method = %method, profile = %profile_name, url = %url, "calling DataLens API");
+
+        // Caller-supplied tokens are used as-is; only the server's own token source is refreshed.
+        let (subject_token, tokens) = match client.subject_token {
+            Some(token) => (token, None),
+            None => {
+                let tokens = profile.tokens.as_ref().ok_or_else(|| {
+                    McpError::invalid_request(
+                        "YC_IAM_TOKEN (or DATALENS_IAM_TOKEN, DATALENS_SA_KEY_FILE, DATALENS_OAUTH_TOKEN, DATALENS_TOKEN_COMMAND) environment variable is required",
+                        Some(json!({"profile": profile_name})),
+                    )
+                })?;
+                (tokens.token(&self.http).await?, Some(tokens))
+            }
+        };
+        if dry_run {
+            return dry_run_response(
+                profile_name,
+                cfg,
+                method,
+                &url,
+                org_id,
+                &subject_token,
+                payload,
+            )
+            .map(|response| (None, response));
+        }
+        // One deadline covers the first send and the re-send after a token refresh.
+        let mut deadline = None;
+        let (mut status, mut body) = self
+            .send_rpc_with_retry(
+                profile_name,
+                profile,
+                method,
+                &url,
+                org_id,
+                &subject_token,
+                &payload,
+                &mut deadline,
+            )
+            .await?;
+        if let Some(tokens) = tokens
+            && status == StatusCode::UNAUTHORIZED
+            && tokens.invalidate().await
+        {
+            warn!(
+                method = %method,
+                "DataLens API rejected the subject token; refreshing it and retrying once"
+            );
+            let subject_token = tokens.token(&self.http).await?;
+            (status, body) = self
+                .send_rpc_with_retry(
+                    profile_name,
+                    profile,
+                    method,
+                    &url,
+                    org_id,
+                    &subject_token,
+                    &payload,
+                    &mut deadline,
+                )
+                .await?;
+        }
+
+        if !status.is_success() {
+            let response_data = parse_response_data(&body);
+            return Err(McpError::internal_error(
+                format!("DataLens API returned {status} for method {method}"),
+                Some(json!({
+                    "method": method,
+                    "status": status.as_u16(),
+                    "response": response_data,
+                })),
+            ));
+        }
+
+        if body.trim().is_empty() {
+            return Ok((Some(status), Json(Map::new())));
+        }
+
+        let parsed = serde_json::from_str::<Map<String, Value>>(&body).map_err(|error| {
+            McpError::internal_error(
+                format!("DataLens API returned invalid or non-object JSON: {error}"),
+                Some(json!({
+                    "method": method,
+                    "body": truncate_utf8(&body, 2000),
+                })),
+            )
+        })?;
+
+        Ok((Some(status), Json(parsed)))
+    }
+
+    /// Two-phase confirmation of destructive methods. Without a token, checks that the call
+    /// would be allowed and returns a summary of affected entries with a new token; with a
+    /// token, consumes it and returns `None` so the call proceeds. Dry runs need no token.
+    async fn confirm_destructive(
+        &self,
+        profile: Option<&str>,
+        method: &str,
+        payload: &Value,
+        dry_run: Option<bool>,
+        token: Option<&str>,
+    ) -> Result<Option<ToolJson>, McpError> {
+        let (profile_name, profile) = self.profiles.resolve(profile)?;
+        let cfg = &profile.cfg;
+        if !confirm::is_destructive(method)
+            || cfg.confirm_ttl.is_zero()
+            || is_dry_run(cfg, method, dry_run)
+        {
+            return Ok(None);
+        }
+
+        let caller = http_server::current_client_credentials();
+        if let Some(token) = token {
+            return match self.confirmations.redeem(
+                token,
+                caller.as_ref(),
+                profile_name,
+                method,
+                payload,
+            ) {
+                Ok(()) => Ok(None),
+                Err(RedeemError::Unknown) => Err(McpError::invalid_params(
+                    "confirm_token is unknown, already used or expired",
+                    Some(json!({
+                        "method": method,
+                        "hint": "Call again without confirm_token to get a new token and summary."
+                    })),
+                )),
+                Err(RedeemError::Mismatch) => Err(McpError::invalid_params(
+                    "confirm_token was issued for a different method, payload or profile",
+                    Some(json!({
+                        "method": method,
+                        "hint": "Repeat the confirmed call unchanged, or call again without confirm_token to confirm the new one."
+                    })),
+                )),
+            };
+        }
+
+        // Fail before the user is asked to confirm something that cannot run.
+        check_writable(profile_name, cfg, method)?;
+        check_policies(profile_name, profile, method, payload)?;
+        check_payload(cfg.validation, method, payload)?;
+
+        let mut results = Vec::new();
+        for (section, summary_method, summary_payload) in confirm::summary_requests(method, payload)
+        {
+            let result = self
+                .call_rpc(Some(profile_name), summary_method, summary_payload)
+                .await
+                .map(|response| Value::Object(response.0))
+                .map_err(|err| err.message.into_owned());
+            results.push((section, summary_method, result));
+        }
+        let token = self
+            .confirmations
+            .issue(
+                caller.as_ref(),
+                profile_name,
+                method,
+                payload,
+                cfg.confirm_ttl,
+            )
+            .map_err(|_| {
+                McpError::internal_error("failed to generate a confirmation token", None)
+            })?;
+
+        let response = json!({
+            "confirmationRequired": true,
+            "confirmToken": token,
+            "expiresInSeconds": cfg.confirm_ttl.as_secs(),
+            "profile": profile_name,
+            "method": method,
+            "payload": payload,
+            "summary": confirm::summarize(results),
+            "hint": "Nothing was deleted. Show this summary to the user; if they agree, call datalens_rpc again with the same method, payload and profile plus confirm_token."
+        });
+        response
+            .as_object()
+            .cloned()
+            .map(|response| Some(Json(response)))
+            .ok_or_else(|| {
+                McpError::internal_error("failed to build confirmation response object", None)
+            })
+    }
+
+    /// Sends one RPC through the profile's throttle, retrying transient failures of
+    /// retry-safe methods within `cfg.timeout` (counted from the first attempt, not queueing).
+    /// `deadline` is set on the first attempt and shared by later sends of the same call.
+    #[allow(clippy::too_many_arguments)]
+    async fn send_rpc_with_retry(
+        &self,
+        profile_name: &str,
+        profile: &Profile,
+        method: &str,
+        url: &str,
+        org_id: &str,
+        subject_token: &SubjectToken,
+        payload: &Value,
+        deadline: &mut Option<Instant>,
+    ) -> Result<(StatusCode, String), McpError> {
+        let cfg = &profile.cfg;
+        let headers = rpc_headers(cfg, org_id, subject_token)?;
+        let retry_allowed = cfg.retry.allows(method_category(method));
+        let mut retries = 0;
+
+        loop {
+            let permit = profile.throttle.acquire(profile_name, method).await;
+            let deadline = *deadline.get_or_insert_with(|| Instant::now() + cfg.timeout);
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let outcome = self
+                .send_rpc(url, headers.clone(), payload, remaining)
+                .await;
+            drop(permit);
+            let delay = match &outcome {
+                Ok((status, retry_after, _)) if retry::is_retryable_status(*status) => {
+                    Some(retry_after.unwrap_or_else(|| cfg.retry.backoff(retries + 1)))
+                }
+                Err(error) if retry::is_transient_error(error) => {
+                    Some(cfg.retry.backoff(retries + 1))
+                }
+                _ => None,
+            };
+
+            let delay = delay.filter(|delay| {
+                retry_allowed
+                    && retries < cfg.retry.max_retries
+                    && Instant::now() + *delay < deadline
+            });
+            let Some(delay) = delay else {
+                return match outcome {
+                    Ok((status, _, body)) => Ok((status, body)),
+                    Err(error) => Err(McpError::internal_error(
+                        format!("failed to reach DataLens API: {error}"),
+                        Some(json!({"method": method, "attempts": retries + 1})),
+                    )),
+                };
+            };
+
+            retries += 1;
+            match &outcome {
+                Ok((status, _, _)) => warn!(
+                    method = %method,
+                    status = status.as_u16(),
+                    retry = retries,
+                    delay_ms = delay.as_millis() as u64,
+                    "transient DataLens API response; retrying"
+                ),
+                Err(error) => warn!(
+                    method = %method,
+                    error = %error,
+                    retry = retries,
+                    delay_ms = delay.as_millis() as u64,
+                    "DataLens API connection failed; retrying"
+                ),
+            }
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    async fn send_rpc(
+        &self,
+        url: &str,
+        headers: HeaderMap,
+        payload: &Value,
+        timeout: Duration,
+    ) -> Result<(StatusCode, Option<Duration>, String), reqwest::Error> {
+        let response = self
+            .http
+            .post(url)
+            .timeout(timeout)
+            .headers(headers)
+            .json(payload)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let retry_after = retry::retry_after(response.headers());
+        let body = response.text().await?;
+
+        Ok((status, retry_after, body))
+    }
+}
+
+fn method_catalog() -> Result<Map<String, Value>, McpError> {
+    let registry = method_registry();
+    let methods = registry
+        .methods
+        .iter()
+        .map(|item| {
+            let mcp_tool = item
+                .typed_tool
+                .clone()
+                .unwrap_or_else(|| "datalens_rpc".to_owned());
+            json!({
+                "method": item.method,
+                "mcpTool": mcp_tool,
+                "typedTool": item.typed_tool,
+                "invokeWith": item.invoke_with,
+                "category": item.category,
+                "experimental": item.experimental,
+                "summary": item.summary,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let response = json!({
+        "snapshotDate": registry.snapshot_date,
+        "sourceUrl": registry.source_url,
+        "openapiVersion": registry.openapi_version,
+        "apiInfo": registry.api_info,
+        "totalMethods": methods.len(),
+        "genericTool": "datalens_rpc",
+        "methods": methods,
+    });
+    let response = response.as_object().cloned().ok_or_else(|| {
+        McpError::internal_error("failed to build method catalog response object", None)
+    })?;
+
+    Ok(response)
+}
+
+fn method_schema(method_name: &str) -> Result<Map<String, Value>, McpError> {
+    let registry = method_registry();
+    let method = registry
+        .methods
+        .iter()
+        .find(|item| item.method.eq_ignore_ascii_case(method_name))
+        .ok_or_else(|| {
+            McpError::invalid_params(
+                format!("Unknown DataLens RPC method: {method_name}"),
+                Some(json!({
+                    "hint": "Call datalens_list_methods first to discover valid methods."
+                })),
+            )
+        })?;
+
+    let response = json!({
+        "snapshotDate": registry.snapshot_date,
+        "sourceUrl": registry.source_url,
+        "openapiVersion": registry.openapi_version,
+        "method": method.method,
+        "category": method.category,
+        "experimental": method.experimental,
+        "typedTool": method.typed_tool,
+        "invokeWith": method.invoke_with,
+        "summary": method.summary,
+        "description": method.description,
+        "requestSchema": schema::resolve_refs(&method.request_schema, &registry.components.schemas),
+        "requestExample": method.request_example,
+        "responseExample": method.response_example,
+    });
+    let mut response = response.as_object().cloned().ok_or_else(|| {
+        McpError::internal_error("failed to build method schema response object", None)
+    })?;
+    if let Some(source) = &registry.components.source
+        && method.request_schema.to_string().contains("#/components/")
+    {
+        response.insert("componentsSource".to_owned(), json!(source));
+    }
+
+    Ok(response)
+}
+
+/// Registry category (`read` or `write`) of a known RPC method.
+fn method_category(method: &str) -> Option<&'static str> {
+    method_registry()
+        .find(method)
+        .map(|item| item.category.as_str())
+}
+
+/// Category the policy and the audit log see; methods missing from the registry count as
+/// writes.
+fn effective_category(method: &str) -> &'static str {
+    method_category(method).unwrap_or("write")
+}
+
+/// Rejects methods that may modify DataLens on a read-only profile. Methods missing from the
+/// registry could do anything, so they count as writes.
+fn check_writable(profile_name: &str, cfg: &AppConfig, method: &str) -> Result<(), McpError> {
+    if !cfg.read_only {
+        return Ok(());
+    }
+    let category = method_category(method);
+    if category == Some("read") {
+        return Ok(());
+    }
+
+    Err(McpError::invalid_request(
+        format!("{method} is not allowed: profile `{profile_name}` is read-only"),
+        Some(json!({
+            "method": method,
+            "profile": profile_name,
+            "category": category.unwrap_or("unknown"),
+            "hint": "Only read methods can be called in read-only mode. Unset DATALENS_READ_ONLY or `read_only` in the profile to allow writes."
+        })),
+    ))
+}
+
+/// Applies the profile's method policies; every one must allow the call, so a profile's
+/// own policy can only narrow `DATALENS_POLICY`. The error names the denying file and rule.
+fn check_policies(
+    profile_name: &str,
+    profile: &Profile,
+    method: &str,
+    payload: &Value,
+) -> Result<(), McpError> {
+    let item = method_registry().find(method);
+    let call = policy::Call {
+        method,
+        category: effective_category(method),
+        experimental: item.is_some_and(|item| item.experimental),
+        payload,
+    };
+    for (path, policy) in &profile.policies {
+        let decision = policy.evaluate(&call);
+        if decision.effect == policy::Effect::Allow {
+            continue;
+        }
+        return Err(McpError::invalid_request(
+            format!(
+                "{method} is denied by {} of policy {} on the `{profile_name}` profile",
+                decision.describe(),
+                path.display()
+            ),
+            Some(json!({
+                "method": method,
+                "profile": profile_name,
+                "policy": path,
+                "rule": decision,
+            })),
+        ));
+    }
+    Ok(())
+}
+
+/// Validates a payload against the method's registry schema according to `mode`.
+/// Methods missing from the registry are not checked.
+fn check_payload(mode: ValidationMode, method: &str, payload: &Value) -> Result<(), McpError> {
+    if mode == ValidationMode::Off {
+        return Ok(());
+    }
+    let errors = payload_errors(method, payload);
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    let total = errors.len();
+    let reported = &errors[..total.min(schema::MAX_REPORTED_ERRORS)];
+    if mode == ValidationMode::Warn {
+        warn!(
+            method = %method,
+            errors = total,
+            first = %format!("{}: {}", reported[0].path, reported[0].message),
+            "payload does not match the request schema; sending it anyway"
+        );
+        return Ok(());
+    }
+
+    Err(McpError::invalid_params(
+        format!("payload does not match the {method} request schema"),
+        Some(json!({
+            "method": method,
+            "errors": reported,
+            "totalErrors": total,
+            "hint": "Call datalens_get_method_schema for the expected payload. Set DATALENS_VALIDATION=warn to send mismatching payloads anyway."
+        })),
+    ))
+}
+
+fn audit_outcome(result: &Result<(Option<StatusCode>, ToolJson), McpError>) -> audit::Outcome {
+    match result {
+        Ok((Some(status), _)) => audit::Outcome::Ok {
+            http_status: u64::from(status.as_u16()),
+        },
+        Ok((None, _)) => audit::Outcome::DryRun,
+        Err(err) => audit::Outcome::Error {
+            message: err.message.to_string(),
+            http_status: err
+                .data
+                .as_ref()
+                .and_then(|data| data.get("status"))
+                .and_then(Value::as_u64),
+        },
+    }
+}
+
+/// A call is a dry run when the caller asks for one, or when the profile's `dry_run` switch
+/// is on and the method is not a read. The caller cannot turn the switch off with `false`.
+fn is_dry_run(cfg: &AppConfig, method: &str, requested: Option<bool>) -> bool {
+    requested == Some(true) || (cfg.dry_run && method_category(method) != Some("read"))
+}
+
+/// What `call_rpc` would send, without sending it.
+fn dry_run_response(
+    profile_name: &str,
+    cfg: &AppConfig,
+    method: &str,
+    url: &str,
+    org_id: &str,
+    subject_token: &SubjectToken,
+    payload: Value,
+) -> Result<ToolJson, McpError> {
+    let errors = payload_errors(method, &payload);
+    let total = errors.len();
+    let response = json!({
+        "dryRun": true,
+        "profile": profile_name,
+        "method": method,
+        "url": url,
+        "headers": doctor::redacted_headers(cfg, org_id, subject_token)?,
+        "payload": payload,
+        "validation": {
+            "mode": cfg.validation,
+            "valid": errors.is_empty(),
+            "errors": &errors[..total.min(schema::MAX_REPORTED_ERRORS)],
+            "totalErrors": total,
+        },
+    });
+    response
+        .as_object()
+        .cloned()
+        .map(Json)
+        .ok_or_else(|| McpError::internal_error("failed to build dry-run response object", None))
+}
+
+/// Schema mismatches of a payload; empty for valid payloads and methods missing from the registry.
+fn payload_errors(method: &str, payload: &Value) -> Vec<schema::SchemaError> {
+    let registry = method_registry();
+    match registry.find(method) {
+        Some(item) => schema::validate(
+            &schema::resolve_refs(&item.request_schema, &registry.components.schemas),
+            payload,
+        ),
+        None => Vec::new(),
+    }
+}
+
+fn rpc_headers(
+    cfg: &AppConfig,
+    org_id: &str,
+    subject_token: &SubjectToken,
+) -> Result<HeaderMap, McpError> {
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    add_header(&mut headers, "x-dl-api-version", &cfg.api_version)?;
+    add_header(&mut headers, "x-dl-org-id", org_id)?;
+    let (auth_header, auth_value) = subject_token.header();
+    add_header(&mut headers, auth_header, &auth_value)?;
+
+    Ok(headers)
+}
+
+fn extend_with_extra(target: &mut Map<String, Value>, extra: BTreeMap<String, Value>) {
+    for (key, value) in extra {
+        target.insert(key, value);
+    }
+}
+
+/// Converts a raw DataLens response into the response type a typed tool advertises.
+fn typed_response<T: responses::ResponseType>(
+    Json(response): ToolJson,
+) -> Result<Json<T>, McpError> {
+    responses::from_object(response).map(Json).map_err(|error| {
+        McpError::internal_error(format!("failed to read DataLens response: {error}"), None)
+    })
+}
+
+fn normalize_json_value(value: Value, field_name: &str) -> Result<Value, McpError> {
+    let Value::String(raw) = value else {
+        return Ok(value);
+    };
+
+    let trimmed = raw.trim();
+    if !(trimmed.starts_with('{') || trimmed.starts_with('[')) {
+        return Ok(Value::String(raw));
+    }
+
+    serde_json::from_str::<Value>(trimmed).map_err(|error| {
+        McpError::invalid_params(
+            format!("field `{field_name}` must be valid JSON when passed as a string: {error}"),
+            None,
+        )
+    })
+}
+
+fn add_header(headers: &mut HeaderMap, key: &str, value: &str) -> Result<(), McpError> {
+    let name = HeaderName::from_bytes(key.as_bytes()).map_err(|error| {
+        McpError::invalid_params(format!("invalid header name `{key}`: {error}"), None)
     })?;
     let value = HeaderValue::from_str(value).map_err(|error| {
         McpError::invalid_params(format!("invalid header value for `{key}`: {error}"), None)
@@ -1052,24 +2308,71 @@ fn env_non_empty(name: &str) -> Option<String> {
         .filter(|value| !value.is_empty())
 }
 
+fn token_source_from_env() -> Option<TokenSource> {
+    if let Some(key_file) = env_non_empty("DATALENS_SA_KEY_FILE") {
+        return Some(TokenSource::ServiceAccountKey {
+            key_file: PathBuf::from(key_file),
+            iam_endpoint: iam_endpoint_from_env(),
+        });
+    }
+    if let Some(oauth_token) = env_non_empty("DATALENS_OAUTH_TOKEN") {
+        return Some(TokenSource::OAuthExchange {
+            oauth_token,
+            iam_endpoint: iam_endpoint_from_env(),
+        });
+    }
+    if let Some(command) = env_non_empty("DATALENS_TOKEN_COMMAND") {
+        return Some(TokenSource::Command {
+            command,
+            ttl: Duration::from_secs(parse_positive_seconds(
+                "DATALENS_TOKEN_COMMAND_TTL_SECONDS",
+                DEFAULT_TOKEN_COMMAND_TTL_SECONDS,
+            )),
+        });
+    }
+
+    env_non_empty("DATALENS_IAM_TOKEN")
+        .or_else(|| env_non_empty("YC_IAM_TOKEN"))
+        .or_else(|| env_non_empty("DATALENS_SUBJECT_TOKEN"))
+        .map(TokenSource::Static)
+}
+
+fn iam_endpoint_from_env() -> String {
+    env_non_empty("DATALENS_IAM_ENDPOINT").unwrap_or_else(|| DEFAULT_IAM_ENDPOINT.to_owned())
+}
+
 fn parse_timeout_seconds() -> u64 {
-    match env_non_empty("DATALENS_TIMEOUT_SECONDS") {
+    parse_positive_seconds("DATALENS_TIMEOUT_SECONDS", DEFAULT_TIMEOUT_SECONDS)
+}
+
+fn env_flag(name: &str) -> bool {
+    env_non_empty(name).is_some_and(|value| matches!(value.as_str(), "1" | "true" | "yes"))
+}
+
+fn parse_non_negative(name: &str, default: u64) -> u64 {
+    match env_non_empty(name) {
+        Some(raw) => raw.parse::<u64>().unwrap_or_else(|error| {
+            warn!("Failed to parse {name}='{raw}': {error}; using default {default}");
+            default
+        }),
+        None => default,
+    }
+}
+
+fn parse_positive_seconds(name: &str, default: u64) -> u64 {
+    match env_non_empty(name) {
         Some(raw) => match raw.parse::<u64>() {
             Ok(value) if value > 0 => value,
             Ok(_) => {
-                warn!(
-                    "DATALENS_TIMEOUT_SECONDS must be a positive integer, using default {DEFAULT_TIMEOUT_SECONDS}"
-                );
-                DEFAULT_TIMEOUT_SECONDS
+                warn!("{name} must be a positive integer, using default {default}");
+                default
             }
             Err(error) => {
-                warn!(
-                    "Failed to parse DATALENS_TIMEOUT_SECONDS='{raw}': {error}; using default {DEFAULT_TIMEOUT_SECONDS}"
-                );
-                DEFAULT_TIMEOUT_SECONDS
+                warn!("Failed to parse {name}='{raw}': {error}; using default {default}");
+                default
             }
         },
-        None => DEFAULT_TIMEOUT_SECONDS,
+        None => default,
     }
 }
 
@@ -1092,8 +2395,9 @@ fn empty_json_object() -> Value {
     Value::Object(Map::new())
 }
 
-fn init_tracing() {
-    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+fn init_tracing(default_level: &str) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_level));
 
     tracing_subscriber::fmt()
         .with_env_filter(filter)
@@ -1105,31 +2409,106 @@ fn init_tracing() {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    init_tracing();
+    let cli = Cli::parse();
+    // One-shot commands print results on stdout; keep stderr quiet unless something is wrong.
+    init_tracing(match cli.command {
+        None | Some(Command::Serve(_)) => "info",
+        Some(_) => "warn",
+    });
+
+    match cli.command {
+        Some(Command::Methods { json }) => return cli::print_methods(json),
+        Some(Command::Schema { ref method }) => return cli::print_schema(method),
+        Some(Command::Registry(ref args)) => return cli::run_registry(args),
+        Some(Command::RegistryDiff(ref args)) => return cli::run_registry_diff(args),
+        _ => {}
+    }
 
-    let cfg = AppConfig::from_env();
-    info!(
-        base_url = %cfg.base_url,
-        api_version = %cfg.api_version,
+    let config_path = cli
+        .config
+        .clone()
+        .or_else(|| env_non_empty("DATALENS_CONFIG").map(PathBuf::from));
+    let selected_profile = cli
+        .profile
+        .clone()
+        .or_else(|| env_non_empty("DATALENS_PROFILE"));
+    let config = ServerConfig::load(config_path.as_deref(), selected_profile.as_deref())?;
+
+    match cli.command {
+        Some(Command::Call { method, payload }) => {
+            let server = DataLensServer::new(config).context("failed to initialize client")?;
+            cli::run_call(&server, &method, &payload).await
+        }
+        Some(Command::Doctor { json }) => {
+            let server = DataLensServer::new(config).context("failed to initialize client")?;
+            cli::run_doctor(&server, json).await
+        }
+        Some(Command::Serve(serve)) => serve_mcp(config, serve).await,
+        _ => serve_mcp(config, cli.serve).await,
+    }
+}
+
+async fn serve_mcp(config: ServerConfig, serve: ServeArgs) -> Result<()> {
+    info!(
+        default_profile = %config.default_profile,
+        profiles = config.profiles.len(),
+        transport = ?serve.transport,
         "starting datalens-mcp server"
     );
+    for (name, cfg) in &config.profiles {
+        log_profile(name, cfg);
+    }
+    let registry = method_registry();
+    info!(
+        methods = registry.methods.len(),
+        snapshot_date = %registry.snapshot_date,
+        source = %registry.source_url,
+        "DataLens method registry ready"
+    );
 
-    if cfg.org_id.is_none() {
-        warn!("DATALENS_ORG_ID is not set; tool calls will fail until it is configured");
+    let server = DataLensServer::new(config).context("failed to initialize server")?;
+    match serve.transport {
+        Transport::Stdio => serve_stdio(server).await,
+        Transport::Http => {
+            http_server::serve_http(server, serve.listen, HttpAccessConfig::from_env()).await
+        }
     }
-    if cfg.subject_token.is_none() {
+}
+
+fn log_profile(name: &str, cfg: &AppConfig) {
+    info!(
+        profile = %name,
+        base_url = %cfg.base_url,
+        api_version = %cfg.api_version,
+        "DataLens profile configured"
+    );
+
+    if cfg.org_id.is_none() {
         warn!(
-            "YC_IAM_TOKEN / DATALENS_IAM_TOKEN is not set; tool calls will fail until it is configured"
+            profile = %name,
+            "DATALENS_ORG_ID is not set; tool calls will fail until it is configured"
         );
     }
+    match &cfg.token_source {
+        Some(source) => info!(
+            profile = %name,
+            token_source = source.describe(),
+            "DataLens credentials configured"
+        ),
+        None => warn!(
+            profile = %name,
+            "YC_IAM_TOKEN / DATALENS_IAM_TOKEN / DATALENS_SA_KEY_FILE / DATALENS_OAUTH_TOKEN / DATALENS_TOKEN_COMMAND is not set; tool calls will fail until it is configured"
+        ),
+    }
+}
 
-    let server = DataLensServer::new(cfg).context("failed to initialize server")?;
+async fn serve_stdio(server: DataLensServer) -> Result<()> {
     let service = server.serve(stdio()).await.map_err(|error| {
         if error_chain_contains(&error, "connection closed: initialized request")
             || error_chain_contains(&error, "initialized request")
         {
             anyhow::anyhow!(
-                "MCP client is not connected: this binary is a stdio MCP server and must be launched by an MCP host (Codex/Cursor/Claude), not directly from a shell."
+                "MCP client is not connected: in stdio mode this binary must be launched by an MCP host (Codex/Cursor/Claude), not directly from a shell. Use `--transport http` to serve MCP over HTTP instead, or `datalens-mcp call|methods|schema` to use the API from a shell."
             )
         } else {
             anyhow::Error::new(error).context("failed to start MCP stdio service")
@@ -1152,179 +2531,1549 @@ mod tests {
         matchers::{body_json, header, method, path},
     };
 
-    fn test_config(base_url: String) -> AppConfig {
-        AppConfig {
-            base_url,
-            api_version: "0".to_owned(),
-            org_id: Some("org-123".to_owned()),
-            subject_token: Some("token-abc".to_owned()),
-            timeout: Duration::from_secs(5),
-        }
+    fn test_config(base_url: String) -> AppConfig {
+        AppConfig {
+            base_url,
+            api_version: "0".to_owned(),
+            org_id: Some("org-123".to_owned()),
+            token_source: Some(TokenSource::Static("token-abc".to_owned())),
+            timeout: Duration::from_secs(5),
+            retry: RetryPolicy::default(),
+            throttle: ThrottleConfig::default(),
+            validation: ValidationMode::default(),
+            read_only: false,
+            policies: Vec::new(),
+            dry_run: false,
+            confirm_ttl: Duration::from_secs(120),
+        }
+    }
+
+    fn test_server(base_url: String) -> DataLensServer {
+        test_server_with_config(test_config(base_url))
+    }
+
+    fn test_server_with_config(cfg: AppConfig) -> DataLensServer {
+        test_server_with_profiles(ServerConfig::single(cfg))
+    }
+
+    fn test_server_with_profiles(config: ServerConfig) -> DataLensServer {
+        let profiles = Profiles::from_config(config).expect("test profiles must initialize");
+        let http = Client::builder()
+            .timeout(profiles.default_profile().cfg.timeout)
+            .build()
+            .expect("test HTTP client must initialize");
+
+        DataLensServer {
+            tool_router: ToolRouter::new(),
+            http,
+            profiles: Arc::new(profiles),
+            confirmations: Arc::default(),
+            audit: None,
+            subscriptions: None,
+            session: Arc::default(),
+            registry_dir: None,
+        }
+    }
+
+    #[test]
+    fn parse_response_data_returns_json_when_valid() {
+        let value = parse_response_data(r#"{"ok":true,"n":1}"#);
+        assert_eq!(value, json!({"ok": true, "n": 1}));
+    }
+
+    #[test]
+    fn truncate_utf8_keeps_char_boundaries() {
+        let input = "abc🙂🙂";
+        let out = truncate_utf8(input, 5);
+        assert!(out.starts_with("abc"));
+        assert!(!out.contains('\u{fffd}'));
+    }
+
+    #[test]
+    fn normalize_json_value_parses_stringified_object() {
+        let value = normalize_json_value(Value::String(r#"{"a":1}"#.to_owned()), "payload")
+            .expect("must parse stringified JSON object");
+        assert_eq!(value, json!({"a": 1}));
+    }
+
+    #[test]
+    fn normalize_json_value_keeps_plain_string() {
+        let value = normalize_json_value(Value::String("plain-text".to_owned()), "payload")
+            .expect("plain string must be preserved");
+        assert_eq!(value, Value::String("plain-text".to_owned()));
+    }
+
+    #[test]
+    fn get_dashboard_args_accept_legacy_include_permissions_info_alias() {
+        let args: GetDashboardArgs = serde_json::from_value(json!({
+            "dashboardId": "dash-1",
+            "includePermissionsInfo": true
+        }))
+        .expect("deserialization must succeed");
+
+        assert_eq!(args.include_permissions, Some(true));
+    }
+
+    #[tokio::test]
+    async fn datalens_list_methods_includes_write_methods() {
+        let server = test_server("http://127.0.0.1".to_owned());
+
+        let response = server
+            .datalens_list_methods(Parameters(NoArgs::default()))
+            .await
+            .expect("list methods must succeed");
+
+        let methods = response
+            .0
+            .get("methods")
+            .and_then(Value::as_array)
+            .expect("methods must be an array");
+
+        assert!(
+            methods.iter().any(|method| {
+                method.get("method") == Some(&Value::String("createDataset".to_owned()))
+                    && method.get("mcpTool")
+                        == Some(&Value::String("datalens_create_dataset".to_owned()))
+                    && method.get("category") == Some(&Value::String("write".to_owned()))
+            }),
+            "method catalog must include createDataset write wrapper"
+        );
+    }
+
+    #[tokio::test]
+    async fn datalens_list_methods_includes_full_openapi_snapshot() {
+        let server = test_server("http://127.0.0.1".to_owned());
+
+        let response = server
+            .datalens_list_methods(Parameters(NoArgs::default()))
+            .await
+            .expect("list methods must succeed");
+
+        let total_methods = response
+            .0
+            .get("totalMethods")
+            .and_then(Value::as_u64)
+            .expect("totalMethods must be present and numeric");
+        assert_eq!(
+            total_methods, 60,
+            "embedded method snapshot should expose all RPC methods"
+        );
+    }
+
+    #[tokio::test]
+    async fn datalens_get_method_schema_returns_invoke_hints() {
+        let server = test_server("http://127.0.0.1".to_owned());
+
+        let response = server
+            .datalens_get_method_schema(Parameters(GetMethodSchemaArgs {
+                method: "createDataset".to_owned(),
+            }))
+            .await
+            .expect("get method schema must succeed");
+
+        assert_eq!(
+            response.0.get("method"),
+            Some(&Value::String("createDataset".to_owned()))
+        );
+        assert_eq!(
+            response.0.get("typedTool"),
+            Some(&Value::String("datalens_create_dataset".to_owned()))
+        );
+        assert_eq!(
+            response.0.get("invokeWith"),
+            Some(&Value::String("datalens_create_dataset".to_owned()))
+        );
+        assert!(response.0.get("requestSchema").is_some());
+    }
+
+    #[tokio::test]
+    async fn datalens_registry_diff_compares_embedded_with_file() {
+        let dir =
+            std::env::temp_dir().join(format!("datalens-mcp-registry-diff-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("openapi.json"),
+            json!({
+                "openapi": "3.1.0",
+                "info": { "title": "DataLens API", "version": "1" },
+                "paths": {
+                    "/rpc/getDataset": { "post": { "requestBody": { "content": { "application/json": {
+                        "schema": { "type": "object", "properties": {
+                            "datasetId": { "type": "string" },
+                            "workbookId": { "type": "string" }
+                        }, "required": ["datasetId", "workbookId"] }
+                    } } } } }
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let args = |target: &str| {
+            Parameters(RegistryDiffArgs {
+                base: "embedded".to_owned(),
+                target: target.to_owned(),
+            })
+        };
+
+        let server = test_server("http://127.0.0.1".to_owned());
+        let err = match server.datalens_registry_diff(args("openapi.json")).await {
+            Ok(_) => panic!("files must not be read without DATALENS_REGISTRY_DIR"),
+            Err(err) => err,
+        };
+        assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+
+        let server = DataLensServer {
+            registry_dir: Some(Arc::from(dir.as_path())),
+            ..server
+        };
+        let response = server
+            .datalens_registry_diff(args("openapi.json"))
+            .await
+            .expect("registry diff must succeed");
+
+        let removed = response.0["removedMethods"].as_array().unwrap();
+        assert!(removed.contains(&json!("listDirectory")));
+        let changed = response.0["changedMethods"].as_array().unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0]["method"], "getDataset");
+        assert_eq!(changed[0]["newlyRequired"], json!(["/workbookId"]));
+
+        for target in [
+            "missing.json",
+            "/dev/zero",
+            "../openapi.json",
+            &dir.join("openapi.json").display().to_string(),
+        ] {
+            let err = match server.datalens_registry_diff(args(target)).await {
+                Ok(_) => panic!("{target} must be rejected"),
+                Err(err) => err,
+            };
+            assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn method_schema_dereferences_component_schemas() {
+        for item in &method_registry().methods {
+            let schema = method_schema(&item.method).expect("registry method must have a schema");
+            let request_schema = schema["requestSchema"].to_string();
+            assert!(
+                !request_schema.contains("#/components/"),
+                "{} has an unresolved component ref: {request_schema}",
+                item.method
+            );
+        }
+
+        let schema = method_schema("updateDataset").unwrap();
+        assert!(schema["componentsSource"].is_string());
+        assert!(
+            method_schema("listDirectory")
+                .unwrap()
+                .get("componentsSource")
+                .is_none()
+        );
+        let dataset = &schema["requestSchema"]["properties"]["data"]["properties"]["dataset"];
+        assert_eq!(dataset["type"], json!("object"));
+        assert_eq!(dataset["x-source"], "hand-written");
+        assert!(
+            payload_errors("createConnection", &json!({"type": "clickhouse"})).is_empty(),
+            "hand-written components must not require fields"
+        );
+        assert!(dataset["properties"]["result_schema"]["items"]["properties"]["guid"].is_object());
+    }
+
+    #[tokio::test]
+    async fn call_rpc_validates_payload_object() {
+        let server = test_server("http://127.0.0.1".to_owned());
+
+        let err = match server
+            .call_rpc(None, "listDirectory", json!(["not-an-object"]))
+            .await
+        {
+            Ok(_) => panic!("must reject non-object payload"),
+            Err(err) => err,
+        };
+
+        assert_eq!(err.message, "payload must be a JSON object");
+    }
+
+    #[tokio::test]
+    async fn call_rpc_rejects_payload_that_does_not_match_schema() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/updateDataset"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"dataset": {}})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let payload = json!({"dataset_id": "ds-1", "data": {"dataset": []}});
+        let mut cfg = test_config(mock_server.uri());
+        cfg.validation = ValidationMode::Strict;
+        let server = test_server_with_config(cfg);
+        let err = match server
+            .call_rpc(None, "updateDataset", payload.clone())
+            .await
+        {
+            Ok(_) => panic!("strict validation must reject the payload"),
+            Err(err) => err,
+        };
+        assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+        let data = err.data.unwrap();
+        let paths: Vec<_> = data["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|error| error["path"].as_str().unwrap().to_owned())
+            .collect();
+        assert_eq!(paths, ["/datasetId", "/data/dataset", "/dataset_id"]);
+
+        let server = test_server(mock_server.uri());
+        server
+            .call_rpc(None, "updateDataset", payload)
+            .await
+            .expect("warn mode must send the payload anyway");
+    }
+
+    #[tokio::test]
+    async fn typed_tool_extra_fields_pass_default_validation() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/validateDataset"))
+            .and(body_json(
+                json!({"datasetId": "ds-1", "data": {}, "previewRowCount": 10}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": "OK"})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let args = || ValidateDatasetArgs {
+            dataset_id: "ds-1".to_owned(),
+            workbook_id: None,
+            data: None,
+            profile: None,
+            extra: BTreeMap::from([("previewRowCount".to_owned(), json!(10))]),
+        };
+        let server = test_server(mock_server.uri());
+        server
+            .datalens_validate_dataset(Parameters(args()))
+            .await
+            .expect("an extra field must reach DataLens by default");
+
+        let mut cfg = test_config(mock_server.uri());
+        cfg.validation = ValidationMode::Strict;
+        let server = test_server_with_config(cfg);
+        let err = match server.datalens_validate_dataset(Parameters(args())).await {
+            Ok(_) => panic!("strict validation must reject the extra field"),
+            Err(err) => err,
+        };
+        assert_eq!(err.data.unwrap()["errors"][0]["path"], "/previewRowCount");
+    }
+
+    #[tokio::test]
+    async fn call_rpc_blocks_write_and_unknown_methods_in_read_only_mode() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"entries": []})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mut cfg = test_config(mock_server.uri());
+        cfg.read_only = true;
+        let server = test_server_with_config(cfg);
+        server
+            .call_rpc(None, "listDirectory", json!({"path": "/"}))
+            .await
+            .expect("read methods must be allowed");
+
+        for (rpc_method, category) in [("deleteDataset", "write"), ("dropEverything", "unknown")] {
+            let err = match server
+                .call_rpc(None, rpc_method, json!({"datasetId": "ds-1"}))
+                .await
+            {
+                Ok(_) => panic!("{rpc_method} must be rejected in read-only mode"),
+                Err(err) => err,
+            };
+            assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_REQUEST);
+            assert_eq!(err.data.unwrap()["category"], category);
+        }
+    }
+
+    #[tokio::test]
+    async fn call_rpc_enforces_policy_and_names_denying_rule() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/deleteWorkbook"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let policy =
+            std::env::temp_dir().join(format!("datalens-mcp-policy-{}.toml", std::process::id()));
+        std::fs::write(
+            &policy,
+            r#"
+            default = "deny"
+
+            [[rules]]
+            name = "sandbox-workbook"
+            effect = "allow"
+            methods = ["deleteWorkbook"]
+            workbook_ids = ["wb-sandbox"]
+            "#,
+        )
+        .unwrap();
+        let mut cfg = test_config(mock_server.uri());
+        cfg.policies = vec![policy.clone()];
+        let server = test_server_with_config(cfg);
+        let _ = std::fs::remove_file(&policy);
+
+        server
+            .call_rpc(None, "deleteWorkbook", json!({"workbookId": "wb-sandbox"}))
+            .await
+            .expect("policy must allow the sandbox workbook");
+
+        let err = match server
+            .call_rpc(None, "deleteWorkbook", json!({"workbookId": "wb-prod"}))
+            .await
+        {
+            Ok(_) => panic!("policy must deny other workbooks"),
+            Err(err) => err,
+        };
+        assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_REQUEST);
+        assert_eq!(
+            err.message,
+            format!(
+                "deleteWorkbook is denied by default effect of policy {} on the `default` profile",
+                policy.display()
+            )
+        );
+        assert_eq!(err.data.unwrap()["rule"], json!({"effect": "deny"}));
+    }
+
+    #[tokio::test]
+    async fn profile_policy_cannot_loosen_global_policy() {
+        let dir =
+            std::env::temp_dir().join(format!("datalens-mcp-policies-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let global = dir.join("global.toml");
+        std::fs::write(
+            &global,
+            r#"
+            [[rules]]
+            name = "no-deletes"
+            effect = "deny"
+            methods = ["delete*"]
+            "#,
+        )
+        .unwrap();
+        let own = dir.join("profile.toml");
+        std::fs::write(
+            &own,
+            r#"
+            [[rules]]
+            name = "deletes-ok"
+            effect = "allow"
+            methods = ["delete*"]
+            "#,
+        )
+        .unwrap();
+
+        let mut defaults = test_config("http://127.0.0.1".to_owned());
+        defaults.policies = vec![global.clone()];
+        let config = ServerConfig::parse(
+            &format!(
+                "[profiles.prod]\norg_id = \"org-prod\"\npolicy = {:?}\n",
+                own.display().to_string()
+            ),
+            None,
+            &defaults,
+        )
+        .unwrap();
+        let server = test_server_with_profiles(config);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let err = match server
+            .call_rpc(None, "deleteWorkbook", json!({"workbookId": "wb-1"}))
+            .await
+        {
+            Ok(_) => panic!("the global policy must still deny"),
+            Err(err) => err,
+        };
+        assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_REQUEST);
+        let data = err.data.unwrap();
+        assert_eq!(data["policy"], json!(global));
+        assert_eq!(data["rule"]["name"], "no-deletes");
+    }
+
+    #[tokio::test]
+    async fn datalens_create_dataset_dry_run_returns_request_without_sending() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let server = test_server(mock_server.uri());
+        let response = server
+            .datalens_create_dataset(Parameters(CreateDatasetArgs {
+                dataset: json!(r#"{"sources": []}"#),
+                created_via: None,
+                dir_path: None,
+                name: Some("my-dataset".to_owned()),
+                options: None,
+                preview: None,
+                workbook_id: Some("wb-1".to_owned()),
+                profile: None,
+                dry_run: Some(true),
+                extra: BTreeMap::new(),
+            }))
+            .await
+            .expect("dry run must succeed");
+
+        let response = Value::Object(response.0);
+        assert_eq!(response["dryRun"], true);
+        assert_eq!(
+            response["url"],
+            format!("{}/rpc/createDataset", mock_server.uri())
+        );
+        assert_eq!(response["headers"]["x-dl-org-id"], "org-123");
+        assert_eq!(
+            response["headers"]["x-yacloud-subjecttoken"],
+            "toke…(9 chars, redacted)"
+        );
+        assert_eq!(
+            response["payload"],
+            json!({"dataset": {"sources": []}, "name": "my-dataset", "workbook_id": "wb-1"})
+        );
+        assert_eq!(response["validation"]["mode"], "warn");
+        assert_eq!(response["validation"]["valid"], true);
+    }
+
+    #[tokio::test]
+    async fn global_dry_run_applies_to_write_methods_only() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"entries": []})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mut cfg = test_config(mock_server.uri());
+        cfg.dry_run = true;
+        let server = test_server_with_config(cfg);
+        let response = server
+            .call_rpc(None, "listDirectory", json!({"path": "/"}))
+            .await
+            .expect("read methods must be sent");
+        assert_eq!(response.0.get("dryRun"), None);
+
+        let response = server
+            .call_rpc(None, "deleteDataset", json!({"dataset_id": "ds-1"}))
+            .await
+            .expect("invalid payloads are reported, not rejected, in dry run");
+        assert_eq!(response.0["dryRun"], true);
+        assert_eq!(response.0["validation"]["valid"], false);
+        assert_eq!(response.0["validation"]["totalErrors"], 2);
+
+        let response = server
+            .call_rpc_with(
+                None,
+                "deleteDataset",
+                json!({"datasetId": "ds-1"}),
+                Some(false),
+            )
+            .await
+            .expect("dry run must still apply");
+        assert_eq!(
+            response.0["dryRun"], true,
+            "`dry_run: false` cannot lift the switch"
+        );
+    }
+
+    #[tokio::test]
+    async fn datalens_rpc_requires_confirmation_token_for_delete_methods() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/getEntries"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "entries": [{"entryId": "ds-1", "scope": "dataset", "key": "sales/orders"}]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/getEntriesRelations"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "relations": [{"entryId": "chart-1", "scope": "widget"}]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/deleteDataset"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let server = test_server(mock_server.uri());
+        let args = |confirm_token: Option<String>| {
+            Parameters(DatalensRpcArgs {
+                method: "deleteDataset".to_owned(),
+                payload: json!({"datasetId": "ds-1"}),
+                profile: None,
+                dry_run: None,
+                confirm_token,
+            })
+        };
+
+        let response = server
+            .datalens_rpc(args(None))
+            .await
+            .expect("first call must return a confirmation");
+        let response = Value::Object(response.0);
+        assert_eq!(response["confirmationRequired"], true);
+        assert_eq!(
+            response["summary"],
+            json!({
+                "entries": [{"entryId": "ds-1", "scope": "dataset", "key": "sales/orders"}],
+                "entriesTotal": 1,
+                "relatedEntries": [{"entryId": "chart-1", "scope": "widget"}],
+                "relatedEntriesTotal": 1,
+            })
+        );
+        let token = response["confirmToken"].as_str().unwrap().to_owned();
+
+        server
+            .datalens_rpc(args(Some(token.clone())))
+            .await
+            .expect("confirmed call must run");
+
+        let err = match server.datalens_rpc(args(Some(token))).await {
+            Ok(_) => panic!("a token must not be reusable"),
+            Err(err) => err,
+        };
+        assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn call_rpc_appends_audit_entries_that_can_be_queried() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"entries": []})))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/getDataset"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(json!({"code": "NOT_FOUND"})))
+            .mount(&mock_server)
+            .await;
+
+        let dir = std::env::temp_dir().join(format!("datalens-mcp-audit-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let server = DataLensServer::new(ServerConfig {
+            audit: Some(audit::AuditConfig {
+                path: dir.join("audit.jsonl"),
+                include_payload: false,
+                max_bytes: 0,
+                max_files: 0,
+            }),
+            ..ServerConfig::single(test_config(mock_server.uri()))
+        })
+        .unwrap();
+
+        server
+            .call_rpc(None, "listDirectory", json!({"path": "/"}))
+            .await
+            .expect("listDirectory must succeed");
+        assert!(
+            server
+                .call_rpc(None, "getDataset", json!({"datasetId": "ds-1"}))
+                .await
+                .is_err()
+        );
+        assert!(
+            server
+                .call_rpc(None, "dropEverything", json!({}))
+                .await
+                .is_err()
+        );
+
+        let unknown = server
+            .datalens_audit_log_query(Parameters(AuditLogQueryArgs {
+                method: Some("dropEverything".to_owned()),
+                ..AuditLogQueryArgs::default()
+            }))
+            .await
+            .expect("audit query must succeed");
+        let response = server
+            .datalens_audit_log_query(Parameters(AuditLogQueryArgs {
+                method: Some("get*".to_owned()),
+                outcome: Some("error".to_owned()),
+                ..AuditLogQueryArgs::default()
+            }))
+            .await
+            .expect("audit query must succeed");
+        let ok = server
+            .datalens_audit_log_query(Parameters(AuditLogQueryArgs {
+                outcome: Some("ok".to_owned()),
+                ..AuditLogQueryArgs::default()
+            }))
+            .await
+            .expect("audit query must succeed");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(ok.0["entries"][0]["method"], "listDirectory");
+        assert_eq!(ok.0["entries"][0]["httpStatus"], 200);
+        assert_eq!(
+            unknown.0["entries"][0]["category"], "write",
+            "methods missing from the registry are audited as writes, as the policy sees them"
+        );
+
+        assert_eq!(response.0["count"], 1);
+        let entry = &response.0["entries"][0];
+        assert_eq!(entry["method"], "getDataset");
+        assert_eq!(entry["category"], "read");
+        assert_eq!(entry["httpStatus"], 404);
+        assert_eq!(entry["profile"], "default");
+        assert!(entry.get("payload").is_none());
+
+        let err = match test_server(mock_server.uri())
+            .datalens_audit_log_query(Parameters(AuditLogQueryArgs::default()))
+            .await
+        {
+            Ok(_) => panic!("query must fail when the audit log is disabled"),
+            Err(err) => err,
+        };
+        assert_eq!(err.message, "audit log is disabled");
+    }
+
+    #[test]
+    fn read_only_server_hides_write_typed_tools() {
+        let mut cfg = test_config("http://127.0.0.1".to_owned());
+        cfg.read_only = true;
+        let server = DataLensServer::new(ServerConfig::single(cfg)).unwrap();
+
+        assert!(server.get_tool("datalens_create_dataset").is_none());
+        assert!(server.get_tool("datalens_create_connection").is_none());
+        assert!(server.get_tool("datalens_get_dataset").is_some());
+        assert!(server.get_tool("datalens_rpc").is_some());
+
+        let server = DataLensServer::new(ServerConfig::single(test_config(
+            "http://127.0.0.1".to_owned(),
+        )))
+        .unwrap();
+        assert!(server.get_tool("datalens_create_dataset").is_some());
+    }
+
+    #[test]
+    fn typed_read_tools_advertise_output_schemas() {
+        let server = DataLensServer::new(ServerConfig::single(test_config(
+            "http://127.0.0.1".to_owned(),
+        )))
+        .unwrap();
+        let properties = |tool: &str| {
+            let schema = server
+                .get_tool(tool)
+                .and_then(|tool| tool.output_schema)
+                .unwrap_or_else(|| panic!("{tool} has no output schema"));
+            assert_eq!(schema["type"], json!("object"));
+            schema["properties"].clone()
+        };
+
+        assert!(properties("datalens_get_dataset")["dataset"].is_object());
+        assert!(properties("datalens_get_connection")["db_type"].is_object());
+        assert!(properties("datalens_get_dashboard")["entry"].is_object());
+        assert!(properties("datalens_get_workbook")["workbookId"].is_object());
+        assert!(properties("datalens_list_directory")["entries"].is_object());
+        assert!(properties("datalens_get_entries")["entries"].is_object());
+    }
+
+    #[test]
+    fn tools_carry_annotations() {
+        let server = DataLensServer::new(ServerConfig::single(test_config(
+            "http://127.0.0.1".to_owned(),
+        )))
+        .unwrap();
+        let annotations = |name: &str| {
+            server
+                .get_tool(name)
+                .and_then(|tool| tool.annotations)
+                .unwrap_or_else(|| panic!("{name} has no annotations"))
+        };
+
+        for tool in server.tool_router.list_all() {
+            assert!(
+                tool.annotations.is_some(),
+                "{} has no annotations",
+                tool.name
+            );
+        }
+        assert_eq!(
+            annotations("datalens_get_dataset").read_only_hint,
+            Some(true)
+        );
+        assert_eq!(
+            annotations("datalens_create_dataset").destructive_hint,
+            Some(false)
+        );
+        assert_eq!(
+            annotations("datalens_list_methods").open_world_hint,
+            Some(false)
+        );
+        let rpc = annotations("datalens_rpc");
+        assert_eq!(rpc.read_only_hint, Some(false));
+        assert_eq!(rpc.destructive_hint, Some(true));
+        assert_eq!(rpc.idempotent_hint, Some(false));
+
+        let mut cfg = test_config("http://127.0.0.1".to_owned());
+        cfg.read_only = true;
+        let server = DataLensServer::new(ServerConfig::single(cfg)).unwrap();
+        let rpc = server
+            .get_tool("datalens_rpc")
+            .unwrap()
+            .annotations
+            .unwrap();
+        assert_eq!(rpc.read_only_hint, Some(true));
+    }
+
+    #[tokio::test]
+    async fn resources_are_listed_and_read_through_get_calls() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .respond_with(ResponseTemplate::new(403).set_body_json(json!({"code": "FORBIDDEN"})))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/getCollectionContent"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "items": [{"entity": "workbook", "workbookId": "wb-1", "title": "Sales"}]
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/getWorkbookEntries"))
+            .and(body_json(
+                json!({"workbookId": "wb-1", "page": 0, "pageSize": 100}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "entries": [{"entryId": "d-1", "scope": "dash", "key": "Overview"}]
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/getDashboard"))
+            .and(body_json(json!({"dashboardId": "d-1"})))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({"entry": {"entryId": "d-1"}})),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let server = test_server(mock_server.uri());
+
+        let page = server.list_resources_page(None).await.unwrap();
+        assert_eq!(page.resources[0].uri, "datalens://workbook/wb-1");
+        let page = server.list_resources_page(page.next_cursor).await.unwrap();
+        assert_eq!(page.resources[0].uri, "datalens://dashboard/d-1");
+        assert_eq!(page.resources[0].name, "Overview");
+        assert_eq!(page.next_cursor, None);
+
+        let read = server
+            .read_resource_uri("datalens://dashboard/d-1".to_owned())
+            .await
+            .unwrap();
+        let ResourceContents::TextResourceContents {
+            text, mime_type, ..
+        } = &read.contents[0]
+        else {
+            panic!("expected text contents");
+        };
+        assert_eq!(mime_type.as_deref(), Some("application/json"));
+        assert_eq!(
+            serde_json::from_str::<Value>(text).unwrap(),
+            json!({"entry": {"entryId": "d-1"}})
+        );
+
+        let err = match server
+            .read_resource_uri("datalens://folder/f-1".to_owned())
+            .await
+        {
+            Ok(_) => panic!("unknown resource kinds must be rejected"),
+            Err(err) => err,
+        };
+        assert_eq!(err.code, rmcp::model::ErrorCode::RESOURCE_NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn prompts_embed_prefetched_summaries() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/getDataset"))
+            .and(body_json(json!({"datasetId": "ds-1"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "ds-1",
+                "key": "sales/orders",
+                "dataset": {"result_schema": [{"guid": "f-1", "title": "Revenue", "type": "MEASURE"}]}
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/getEntriesRelations"))
+            .and(body_json(json!({"entryIds": ["ds-1"], "scope": "widget"})))
+            .respond_with(ResponseTemplate::new(500).set_body_json(json!({"code": "ERR"})))
+            .mount(&mock_server)
+            .await;
+
+        let server = test_server(mock_server.uri());
+        let prompt = PromptRequest::parse(
+            "find_charts_using_dataset",
+            json!({"dataset_id": "ds-1"}).as_object(),
+        )
+        .unwrap();
+        let result = server.render_prompt(prompt).await.unwrap();
+
+        let rmcp::model::PromptMessageContent::Text { text } = &result.messages[0].content else {
+            panic!("expected a text message");
+        };
+        assert!(text.contains("\"title\": \"Revenue\""));
+        assert!(text.contains("\"fieldsTotal\": 1"));
+        assert!(text.contains("\"method\": \"getEntriesRelations\""));
+    }
+
+    #[tokio::test]
+    async fn audit_entry_updates_follow_page_tokens() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/getAuditEntriesUpdates"))
+            .and(body_json(json!({
+                "from": "2026-10-16T09:00:00.000Z",
+                "limit": 200,
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "entries": [{"entryId": "d-1"}],
+                "nextPageToken": "p-2",
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/getAuditEntriesUpdates"))
+            .and(body_json(json!({
+                "from": "2026-10-16T09:00:00.000Z",
+                "limit": 200,
+                "pageToken": "p-2",
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "entries": [{"entryId": "c-1", "workbookId": "wb-1"}],
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let server = test_server(mock_server.uri());
+        let since = chrono::DateTime::parse_from_rfc3339("2026-10-16T09:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let (entries, next_page_token) = server.audit_entry_updates(since, None).await.unwrap();
+
+        assert_eq!(
+            entries,
+            [
+                json!({"entryId": "d-1"}),
+                json!({"entryId": "c-1", "workbookId": "wb-1"})
+            ]
+        );
+        assert!(next_page_token.is_none());
+    }
+
+    #[tokio::test]
+    async fn audit_entry_updates_return_the_page_after_the_cap() {
+        let mock_server = MockServer::start().await;
+
+        for page in 1..=subscriptions::MAX_UPDATE_PAGES + 1 {
+            let mut request = json!({"from": "2026-10-16T09:00:00.000Z", "limit": 200});
+            if page > 1 {
+                request["pageToken"] = json!(format!("p-{page}"));
+            }
+            let mut response = json!({"entries": [{"entryId": format!("d-{page}")}]});
+            if page <= subscriptions::MAX_UPDATE_PAGES {
+                response["nextPageToken"] = json!(format!("p-{}", page + 1));
+            }
+            Mock::given(method("POST"))
+                .and(path("/rpc/getAuditEntriesUpdates"))
+                .and(body_json(request))
+                .respond_with(ResponseTemplate::new(200).set_body_json(response))
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+
+        let server = test_server(mock_server.uri());
+        let since = chrono::DateTime::parse_from_rfc3339("2026-10-16T09:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let (entries, next_page_token) = server.audit_entry_updates(since, None).await.unwrap();
+        assert_eq!(entries.len(), subscriptions::MAX_UPDATE_PAGES);
+        let next_page_token = next_page_token.expect("unread pages must be resumable");
+        assert_eq!(
+            next_page_token,
+            format!("p-{}", subscriptions::MAX_UPDATE_PAGES + 1)
+        );
+
+        let (entries, next_page_token) = server
+            .audit_entry_updates(since, Some(next_page_token))
+            .await
+            .unwrap();
+        assert!(next_page_token.is_none());
+        assert_eq!(
+            entries,
+            [json!({"entryId": format!("d-{}", subscriptions::MAX_UPDATE_PAGES + 1)})]
+        );
+    }
+
+    #[test]
+    fn subscribe_capability_follows_poll_interval() {
+        let server = test_server("http://127.0.0.1".to_owned());
+        let resources = server.get_info().capabilities.resources.unwrap();
+        assert_eq!(resources.subscribe, Some(false));
+
+        let server = DataLensServer::new(ServerConfig {
+            subscription_poll: Some(Duration::from_secs(30)),
+            ..ServerConfig::single(test_config("http://127.0.0.1".to_owned()))
+        })
+        .unwrap();
+        let resources = server.get_info().capabilities.resources.unwrap();
+        assert_eq!(resources.subscribe, Some(true));
+    }
+
+    #[tokio::test]
+    async fn call_rpc_sends_expected_request_shape() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .and(header("x-dl-api-version", "0"))
+            .and(header("x-dl-org-id", "org-123"))
+            .and(header("x-yacloud-subjecttoken", "token-abc"))
+            .and(body_json(json!({"path": "/"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"entries": []})))
+            .mount(&mock_server)
+            .await;
+
+        let server = test_server(mock_server.uri());
+
+        let response = server
+            .call_rpc(None, "listDirectory", json!({"path": "/"}))
+            .await
+            .expect("request must succeed");
+
+        assert_eq!(Value::Object(response.0), json!({"entries": []}));
+
+        let requests = mock_server
+            .received_requests()
+            .await
+            .expect("request recording must be enabled");
+        assert!(
+            !requests[0].headers.contains_key("x-dl-auth-token"),
+            "IAM tokens must not be sent as OAuth tokens"
+        );
+    }
+
+    #[tokio::test]
+    async fn call_rpc_passes_legacy_oauth_token_in_auth_token_header() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .and(header("x-dl-auth-token", "OAuth y0_oauth"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"entries": []})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mut cfg = test_config(mock_server.uri());
+        cfg.token_source = Some(TokenSource::Static("OAuth y0_oauth".to_owned()));
+        let server = test_server_with_config(cfg);
+
+        server
+            .call_rpc(None, "listDirectory", json!({"path": "/"}))
+            .await
+            .expect("request must succeed");
+
+        let requests = mock_server
+            .received_requests()
+            .await
+            .expect("request recording must be enabled");
+        assert!(
+            !requests[0].headers.contains_key("x-yacloud-subjecttoken"),
+            "OAuth tokens must not be sent as IAM subject tokens"
+        );
+    }
+
+    #[tokio::test]
+    async fn call_rpc_uses_iam_token_from_service_account_key() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/iam/v1/tokens"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "iamToken": "sa-iam-token",
+                "expiresAt": "2099-01-01T00:00:00.000000000Z"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .and(header("x-yacloud-subjecttoken", "sa-iam-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"entries": []})))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let mut cfg = test_config(mock_server.uri());
+        cfg.token_source = Some(TokenSource::ServiceAccountKey {
+            key_file: PathBuf::from(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/service-account-key.json"
+            )),
+            iam_endpoint: format!("{}/iam/v1/tokens", mock_server.uri()),
+        });
+        let server = test_server_with_config(cfg);
+
+        for _ in 0..2 {
+            server
+                .call_rpc(None, "listDirectory", json!({"path": "/"}))
+                .await
+                .expect("request must succeed with exchanged IAM token");
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn call_rpc_refreshes_command_token_after_unauthorized() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .and(header("x-yacloud-subjecttoken", "cmd-token-1"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(json!({"code": "UNAUTHORIZED"})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .and(header("x-yacloud-subjecttoken", "cmd-token-2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"entries": []})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let counter =
+            std::env::temp_dir().join(format!("datalens-mcp-rpc-401-{}", std::process::id()));
+        let _ = std::fs::remove_file(&counter);
+        let mut cfg = test_config(mock_server.uri());
+        cfg.token_source = Some(TokenSource::Command {
+            command: format!(
+                "echo run >> '{0}' && echo \"cmd-token-$(wc -l < '{0}' | tr -d ' ')\"",
+                counter.display()
+            ),
+            ttl: Duration::from_secs(3600),
+        });
+        let server = test_server_with_config(cfg);
+
+        let response = server
+            .call_rpc(None, "listDirectory", json!({"path": "/"}))
+            .await
+            .expect("request must succeed after token refresh");
+        assert_eq!(Value::Object(response.0), json!({"entries": []}));
+
+        let _ = std::fs::remove_file(counter);
     }
 
-    fn test_server(base_url: String) -> DataLensServer {
-        let cfg = test_config(base_url);
-        let http = Client::builder()
-            .timeout(cfg.timeout)
-            .build()
-            .expect("test HTTP client must initialize");
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn call_rpc_token_refresh_shares_the_call_deadline() {
+        let mock_server = MockServer::start().await;
 
-        DataLensServer {
-            tool_router: ToolRouter::new(),
-            http,
-            cfg,
-        }
-    }
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .and(header("x-yacloud-subjecttoken", "cmd-token-1"))
+            .respond_with(ResponseTemplate::new(401).set_delay(Duration::from_millis(700)))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .and(header("x-yacloud-subjecttoken", "cmd-token-2"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({"entries": []}))
+                    .set_delay(Duration::from_millis(700)),
+            )
+            .mount(&mock_server)
+            .await;
 
-    #[test]
-    fn parse_response_data_returns_json_when_valid() {
-        let value = parse_response_data(r#"{"ok":true,"n":1}"#);
-        assert_eq!(value, json!({"ok": true, "n": 1}));
-    }
+        let counter = std::env::temp_dir().join(format!(
+            "datalens-mcp-rpc-401-deadline-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&counter);
+        let mut cfg = test_config(mock_server.uri());
+        cfg.timeout = Duration::from_secs(1);
+        cfg.token_source = Some(TokenSource::Command {
+            command: format!(
+                "echo run >> '{0}' && echo \"cmd-token-$(wc -l < '{0}' | tr -d ' ')\"",
+                counter.display()
+            ),
+            ttl: Duration::from_secs(3600),
+        });
+        let server = test_server_with_config(cfg);
 
-    #[test]
-    fn truncate_utf8_keeps_char_boundaries() {
-        let input = "abc🙂🙂";
-        let out = truncate_utf8(input, 5);
-        assert!(out.starts_with("abc"));
-        assert!(!out.contains('\u{fffd}'));
-    }
+        let started = Instant::now();
+        let result = server
+            .call_rpc(None, "listDirectory", json!({"path": "/"}))
+            .await;
+        assert!(
+            result.is_err(),
+            "the re-send must not get a fresh timeout budget"
+        );
+        assert!(started.elapsed() < Duration::from_millis(1500));
 
-    #[test]
-    fn normalize_json_value_parses_stringified_object() {
-        let value = normalize_json_value(Value::String(r#"{"a":1}"#.to_owned()), "payload")
-            .expect("must parse stringified JSON object");
-        assert_eq!(value, json!({"a": 1}));
+        let _ = std::fs::remove_file(counter);
     }
 
-    #[test]
-    fn normalize_json_value_keeps_plain_string() {
-        let value = normalize_json_value(Value::String("plain-text".to_owned()), "payload")
-            .expect("plain string must be preserved");
-        assert_eq!(value, Value::String("plain-text".to_owned()));
-    }
+    #[tokio::test]
+    async fn call_rpc_does_not_retry_static_token_after_unauthorized() {
+        let mock_server = MockServer::start().await;
 
-    #[test]
-    fn get_dashboard_args_accept_legacy_include_permissions_info_alias() {
-        let args: GetDashboardArgs = serde_json::from_value(json!({
-            "dashboardId": "dash-1",
-            "includePermissionsInfo": true
-        }))
-        .expect("deserialization must succeed");
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
 
-        assert_eq!(args.include_permissions, Some(true));
+        let server = test_server(mock_server.uri());
+        let err = match server
+            .call_rpc(None, "listDirectory", json!({"path": "/"}))
+            .await
+        {
+            Ok(_) => panic!("401 with a static token must fail"),
+            Err(err) => err,
+        };
+        assert_eq!(err.data.as_ref().unwrap()["status"], json!(401));
     }
 
     #[tokio::test]
-    async fn datalens_list_methods_includes_write_methods() {
-        let server = test_server("http://127.0.0.1".to_owned());
+    async fn call_rpc_retries_read_method_after_transient_status() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .respond_with(ResponseTemplate::new(503).insert_header("retry-after", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"entries": []})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
 
+        let server = test_server(mock_server.uri());
         let response = server
-            .datalens_list_methods(Parameters(NoArgs::default()))
+            .call_rpc(None, "listDirectory", json!({"path": "/"}))
             .await
-            .expect("list methods must succeed");
+            .expect("read method must succeed after a retry");
+        assert_eq!(Value::Object(response.0), json!({"entries": []}));
+    }
 
-        let methods = response
-            .0
-            .get("methods")
-            .and_then(Value::as_array)
-            .expect("methods must be an array");
+    #[tokio::test]
+    async fn call_rpc_retries_write_method_only_when_opted_in() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/createDataset"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let server = test_server(mock_server.uri());
+        let err = match server
+            .call_rpc(None, "createDataset", json!({"dataset": {}}))
+            .await
+        {
+            Ok(_) => panic!("429 must fail the write call"),
+            Err(err) => err,
+        };
+        assert_eq!(err.data.as_ref().unwrap()["status"], json!(429));
 
+        let mut cfg = test_config(mock_server.uri());
+        cfg.retry.max_retries = 1;
+        cfg.retry.retry_writes = true;
+        let server = test_server_with_config(cfg);
         assert!(
-            methods.iter().any(|method| {
-                method.get("method") == Some(&Value::String("createDataset".to_owned()))
-                    && method.get("mcpTool")
-                        == Some(&Value::String("datalens_create_dataset".to_owned()))
-                    && method.get("category") == Some(&Value::String("write".to_owned()))
-            }),
-            "method catalog must include createDataset write wrapper"
+            server
+                .call_rpc(None, "createDataset", json!({"dataset": {}}))
+                .await
+                .is_err(),
+            "429 must still fail after the opted-in retry"
         );
     }
 
     #[tokio::test]
-    async fn datalens_list_methods_includes_full_openapi_snapshot() {
-        let server = test_server("http://127.0.0.1".to_owned());
+    async fn call_rpc_stops_retrying_when_retry_after_exceeds_timeout() {
+        let mock_server = MockServer::start().await;
 
-        let response = server
-            .datalens_list_methods(Parameters(NoArgs::default()))
-            .await
-            .expect("list methods must succeed");
+        Mock::given(method("POST"))
+            .and(path("/rpc/getEntries"))
+            .respond_with(ResponseTemplate::new(503).insert_header("retry-after", "60"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
 
-        let total_methods = response
-            .0
-            .get("totalMethods")
-            .and_then(Value::as_u64)
-            .expect("totalMethods must be present and numeric");
-        assert_eq!(
-            total_methods, 60,
-            "embedded method snapshot should expose all RPC methods"
-        );
+        let server = test_server(mock_server.uri());
+        let started = Instant::now();
+        let err = match server
+            .call_rpc(None, "getEntries", json!({"scope": "dataset"}))
+            .await
+        {
+            Ok(_) => panic!("503 must fail once the retry budget is exhausted"),
+            Err(err) => err,
+        };
+        assert_eq!(err.data.as_ref().unwrap()["status"], json!(503));
+        assert!(started.elapsed() < Duration::from_secs(5));
     }
 
     #[tokio::test]
-    async fn datalens_get_method_schema_returns_invoke_hints() {
-        let server = test_server("http://127.0.0.1".to_owned());
+    async fn call_rpc_retries_read_method_after_connection_failure() {
+        let mut cfg = test_config("http://127.0.0.1:9".to_owned());
+        cfg.retry.base_delay = Duration::from_millis(10);
+        let server = test_server_with_config(cfg);
 
-        let response = server
-            .datalens_get_method_schema(Parameters(GetMethodSchemaArgs {
-                method: "createDataset".to_owned(),
-            }))
+        let err = match server
+            .call_rpc(None, "listDirectory", json!({"path": "/"}))
             .await
-            .expect("get method schema must succeed");
+        {
+            Ok(_) => panic!("unreachable API must fail"),
+            Err(err) => err,
+        };
+        assert_eq!(err.data.as_ref().unwrap()["attempts"], json!(4));
+    }
 
-        assert_eq!(
-            response.0.get("method"),
-            Some(&Value::String("createDataset".to_owned()))
-        );
-        assert_eq!(
-            response.0.get("typedTool"),
-            Some(&Value::String("datalens_create_dataset".to_owned()))
+    #[tokio::test]
+    async fn call_rpc_queues_requests_beyond_max_in_flight() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/getEntries"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({"entries": []}))
+                    .set_delay(Duration::from_millis(200)),
+            )
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let mut cfg = test_config(mock_server.uri());
+        cfg.throttle.max_in_flight = 1;
+        let server = test_server_with_config(cfg);
+
+        let started = Instant::now();
+        let (first, second) = tokio::join!(
+            server.call_rpc(None, "getEntries", json!({"scope": "dataset"})),
+            server.call_rpc(None, "getEntries", json!({"scope": "dataset"})),
         );
-        assert_eq!(
-            response.0.get("invokeWith"),
-            Some(&Value::String("datalens_create_dataset".to_owned()))
+        assert!(first.is_ok() && second.is_ok(), "queued calls must succeed");
+        assert!(
+            started.elapsed() >= Duration::from_millis(400),
+            "second call must wait for the first to finish"
         );
-        assert!(response.0.get("requestSchema").is_some());
     }
 
     #[tokio::test]
-    async fn call_rpc_validates_payload_object() {
-        let server = test_server("http://127.0.0.1".to_owned());
+    async fn call_rpc_prefers_client_credentials_from_http_request() {
+        let mock_server = MockServer::start().await;
 
-        let err = match server
-            .call_rpc("listDirectory", json!(["not-an-object"]))
-            .await
-        {
-            Ok(_) => panic!("must reject non-object payload"),
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .and(header("x-dl-org-id", "org-client"))
+            .and(header("x-yacloud-subjecttoken", "client-iam"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let server = test_server(mock_server.uri());
+        let credentials = ClientCredentials {
+            org_id: Some("org-client".to_owned()),
+            subject_token: Some(SubjectToken::Iam("client-iam".to_owned())),
+        };
+
+        let result = http_server::with_client_credentials(
+            credentials,
+            server.call_rpc(None, "listDirectory", json!({"path": "/"})),
+        )
+        .await;
+        let err = match result {
+            Ok(_) => panic!("401 for a caller token must be returned to the caller"),
             Err(err) => err,
         };
+        assert_eq!(err.data.as_ref().unwrap()["status"], json!(401));
+    }
 
-        assert_eq!(err.message, "payload must be a JSON object");
+    #[tokio::test]
+    async fn call_rpc_falls_back_to_server_org_for_client_token() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .and(header("x-dl-org-id", "org-123"))
+            .and(header("x-yacloud-subjecttoken", "client-iam"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"entries": []})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mut cfg = test_config(mock_server.uri());
+        cfg.token_source = None;
+        let server = test_server_with_config(cfg);
+        let credentials = ClientCredentials {
+            org_id: None,
+            subject_token: Some(SubjectToken::Iam("client-iam".to_owned())),
+        };
+
+        http_server::with_client_credentials(
+            credentials,
+            server.call_rpc(None, "listDirectory", json!({"path": "/"})),
+        )
+        .await
+        .expect("caller token must work without a server-side token source");
     }
 
     #[tokio::test]
-    async fn call_rpc_sends_expected_request_shape() {
+    async fn call_rpc_ignores_client_org_without_client_token() {
         let mock_server = MockServer::start().await;
 
         Mock::given(method("POST"))
             .and(path("/rpc/listDirectory"))
-            .and(header("x-dl-api-version", "0"))
             .and(header("x-dl-org-id", "org-123"))
             .and(header("x-yacloud-subjecttoken", "token-abc"))
-            .and(header("x-dl-auth-token", "OAuth token-abc"))
-            .and(body_json(json!({"path": "/"})))
             .respond_with(ResponseTemplate::new(200).set_body_json(json!({"entries": []})))
+            .expect(1)
             .mount(&mock_server)
             .await;
 
         let server = test_server(mock_server.uri());
+        let credentials = ClientCredentials {
+            org_id: Some("org-other".to_owned()),
+            subject_token: None,
+        };
+
+        http_server::with_client_credentials(
+            credentials,
+            server.call_rpc(None, "listDirectory", json!({"path": "/"})),
+        )
+        .await
+        .expect("the server's token must stay on the configured organization");
+    }
+
+    #[tokio::test]
+    async fn call_rpc_uses_requested_profile() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rpc/listDirectory"))
+            .and(header("x-dl-org-id", "org-sandbox"))
+            .and(header("x-dl-api-version", "1"))
+            .and(header("x-yacloud-subjecttoken", "sandbox-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"entries": []})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mut sandbox = test_config(mock_server.uri());
+        sandbox.api_version = "1".to_owned();
+        sandbox.org_id = Some("org-sandbox".to_owned());
+        sandbox.token_source = Some(TokenSource::Static("sandbox-token".to_owned()));
+        let mut config = ServerConfig::single(test_config("http://127.0.0.1:9".to_owned()));
+        config.profiles.insert("sandbox".to_owned(), sandbox);
+        let server = test_server_with_profiles(config);
+
+        server
+            .datalens_rpc(Parameters(DatalensRpcArgs {
+                method: "listDirectory".to_owned(),
+                payload: json!({"path": "/"}),
+                profile: Some("sandbox".to_owned()),
+                dry_run: None,
+                confirm_token: None,
+            }))
+            .await
+            .expect("request must go to the sandbox profile");
+
+        let err = match server
+            .call_rpc(Some("staging"), "listDirectory", json!({"path": "/"}))
+            .await
+        {
+            Ok(_) => panic!("unknown profile must be rejected"),
+            Err(err) => err,
+        };
+        assert_eq!(err.message, "Unknown DataLens profile: staging");
+    }
+
+    #[tokio::test]
+    async fn datalens_list_profiles_marks_default_profile() {
+        let mut config = ServerConfig::single(test_config("http://127.0.0.1".to_owned()));
+        let mut sandbox = test_config("http://127.0.0.1".to_owned());
+        sandbox.read_only = true;
+        config.profiles.insert("sandbox".to_owned(), sandbox);
+        let server = test_server_with_profiles(config);
 
         let response = server
-            .call_rpc("listDirectory", json!({"path": "/"}))
+            .datalens_list_profiles(Parameters(NoArgs::default()))
             .await
-            .expect("request must succeed");
+            .expect("list profiles must succeed");
 
-        assert_eq!(Value::Object(response.0), json!({"entries": []}));
+        assert_eq!(response.0["defaultProfile"], json!("default"));
+        assert_eq!(
+            response.0["profiles"],
+            json!([
+                {"name": "default", "default": true, "orgId": "org-123",
+                 "baseUrl": "http://127.0.0.1", "apiVersion": "0", "tokenSource": "static IAM token",
+                 "readOnly": false, "policies": [], "dryRun": false},
+                {"name": "sandbox", "default": false, "orgId": "org-123",
+                 "baseUrl": "http://127.0.0.1", "apiVersion": "0", "tokenSource": "static IAM token",
+                 "readOnly": true, "policies": [], "dryRun": false},
+            ])
+        );
     }
 
     #[tokio::test]
@@ -1349,12 +4098,13 @@ mod tests {
                 dataset_id: "ds-1".to_owned(),
                 workbook_id: Some("wb-1".to_owned()),
                 rev_id: Some("r-1".to_owned()),
+                profile: None,
                 extra: BTreeMap::new(),
             }))
             .await
             .expect("tool call must succeed");
 
-        assert_eq!(Value::Object(result.0), json!({"ok": true}));
+        assert_eq!(serde_json::to_value(result.0).unwrap(), json!({"ok": true}));
     }
 
     #[tokio::test]
@@ -1382,6 +4132,8 @@ mod tests {
                 options: None,
                 preview: None,
                 workbook_id: Some("wb-1".to_owned()),
+                profile: None,
+                dry_run: None,
                 extra: BTreeMap::new(),
             }))
             .await
@@ -1406,6 +4158,9 @@ mod tests {
             .datalens_rpc(Parameters(DatalensRpcArgs {
                 method: "listDirectory".to_owned(),
                 payload: Value::String(r#"{"path":"/"}"#.to_owned()),
+                profile: None,
+                dry_run: None,
+                confirm_token: None,
             }))
             .await
             .expect("stringified payload must be parsed and sent as JSON object");
@@ -1437,6 +4192,8 @@ mod tests {
                 options: None,
                 preview: None,
                 workbook_id: Some("wb-1".to_owned()),
+                profile: None,
+                dry_run: None,
                 extra: BTreeMap::new(),
             }))
             .await
@@ -1472,6 +4229,7 @@ mod tests {
                 limit: Some(serde_json::Number::from(50)),
                 page_token: Some("next-page".to_owned()),
                 scope: Some("dash".to_owned()),
+                profile: None,
                 extra: BTreeMap::new(),
             }))
             .await
@@ -1497,6 +4255,7 @@ mod tests {
         let result = server
             .datalens_get_entries_permissions(Parameters(GetEntriesPermissionsArgs {
                 entry_ids: vec!["entry-1".to_owned(), "entry-2".to_owned()],
+                profile: None,
                 extra: BTreeMap::new(),
             }))
             .await
@@ -1534,6 +4293,7 @@ mod tests {
                 include_links: Some(true),
                 include_favorite: Some(false),
                 branch: Some("saved".to_owned()),
+                profile: None,
                 extra: BTreeMap::new(),
             }))
             .await
@@ -1552,7 +4312,12 @@ mod tests {
                 "workbookId": "wb-1",
                 "includePermissionsInfo": true
             })))
-            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"ok": true})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "workbookId": "wb-1",
+                "title": "Sales",
+                "permissions": {"view": true},
+                "status": "active"
+            })))
             .mount(&mock_server)
             .await;
 
@@ -1561,12 +4326,17 @@ mod tests {
             .datalens_get_workbook(Parameters(GetWorkbookArgs {
                 workbook_id: "wb-1".to_owned(),
                 include_permissions_info: Some(true),
+                profile: None,
                 extra: BTreeMap::new(),
             }))
             .await
             .expect("tool call must succeed");
 
-        assert_eq!(Value::Object(result.0), json!({"ok": true}));
+        let workbook = result.0;
+        assert_eq!(workbook.workbook_id.as_deref(), Some("wb-1"));
+        assert_eq!(workbook.title.as_deref(), Some("Sales"));
+        assert_eq!(workbook.permissions.unwrap()["view"], json!(true));
+        assert_eq!(workbook.extra["status"], json!("active"));
     }
 
     #[tokio::test]
@@ -1589,6 +4359,7 @@ mod tests {
                 dataset_id: "ds-1".to_owned(),
                 workbook_id: None,
                 data: None,
+                profile: None,
                 extra: BTreeMap::new(),
             }))
             .await
@@ -0,0 +1,402 @@
+use std::{collections::BTreeSet, fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Payload nesting depth searched for workbook and collection IDs.
+const MAX_PAYLOAD_DEPTH: usize = 32;
+
+/// Which RPC methods a profile may call, loaded from a TOML policy file.
+///
+/// Rules are checked in file order and the first one that matches decides; calls no rule
+/// matches get `default`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Policy {
+    #[serde(default)]
+    pub(crate) default: Effect,
+    #[serde(default)]
+    pub(crate) rules: Vec<Rule>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Effect {
+    #[default]
+    Allow,
+    Deny,
+}
+
+/// One policy rule. Every condition that is set must hold for the rule to match.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Rule {
+    pub(crate) name: Option<String>,
+    pub(crate) effect: Effect,
+    /// Method names or globs (`*`, `?`), e.g. `update*Chart`.
+    pub(crate) methods: Option<Vec<String>>,
+    /// Registry categories; methods missing from the registry are `write`.
+    pub(crate) categories: Option<Vec<String>>,
+    pub(crate) experimental: Option<bool>,
+    pub(crate) workbook_ids: Option<Vec<String>>,
+    pub(crate) collection_ids: Option<Vec<String>>,
+}
+
+/// What the policy knows about a call.
+pub(crate) struct Call<'a> {
+    pub(crate) method: &'a str,
+    pub(crate) category: &'a str,
+    pub(crate) experimental: bool,
+    pub(crate) payload: &'a Value,
+}
+
+/// The rule that decided a call; `number` (1-based, as in messages) is `None` when no rule
+/// matched.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct Decision {
+    pub(crate) effect: Effect,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) number: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) name: Option<String>,
+}
+
+impl Decision {
+    /// `rule #2 (charts-in-workbook)`, or `default effect` when no rule matched.
+    pub(crate) fn describe(&self) -> String {
+        match (self.number, &self.name) {
+            (Some(number), Some(name)) => format!("rule #{number} ({name})"),
+            (Some(number), None) => format!("rule #{number}"),
+            (None, _) => "default effect".to_owned(),
+        }
+    }
+}
+
+impl Policy {
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("failed to read policy file {}", path.display()))?;
+        Self::parse(&raw).with_context(|| format!("invalid policy file {}", path.display()))
+    }
+
+    pub(crate) fn parse(raw: &str) -> Result<Self> {
+        let policy: Self = toml::from_str(raw)?;
+        for (index, rule) in policy.rules.iter().enumerate() {
+            let empty = |values: &Option<Vec<String>>| values.as_ref().is_some_and(Vec::is_empty);
+            if empty(&rule.methods)
+                || empty(&rule.categories)
+                || empty(&rule.workbook_ids)
+                || empty(&rule.collection_ids)
+            {
+                bail!(
+                    "rule #{} has an empty list; omit the key to match any value",
+                    index + 1
+                );
+            }
+        }
+        Ok(policy)
+    }
+
+    pub(crate) fn evaluate(&self, call: &Call<'_>) -> Decision {
+        let ids = PayloadIds::collect(call.payload);
+        self.rules
+            .iter()
+            .enumerate()
+            .find(|(_, rule)| rule.matches(call, &ids))
+            .map_or(
+                Decision {
+                    effect: self.default,
+                    number: None,
+                    name: None,
+                },
+                |(index, rule)| Decision {
+                    effect: rule.effect,
+                    number: Some(index + 1),
+                    name: rule.name.clone(),
+                },
+            )
+    }
+}
+
+impl Rule {
+    fn matches(&self, call: &Call<'_>, ids: &PayloadIds) -> bool {
+        let listed = |values: &Option<Vec<String>>, test: &dyn Fn(&str) -> bool| {
+            values
+                .as_ref()
+                .is_none_or(|values| values.iter().any(|value| test(value)))
+        };
+
+        listed(&self.methods, &|pattern| glob_match(pattern, call.method))
+            && listed(&self.categories, &|category| category == call.category)
+            && self
+                .experimental
+                .is_none_or(|experimental| experimental == call.experimental)
+            && self.ids_match(self.workbook_ids.as_deref(), &ids.workbooks)
+            && self.ids_match(self.collection_ids.as_deref(), &ids.collections)
+    }
+
+    /// An allow rule only grants calls confined to its IDs: the payload must name at least one
+    /// and all of them must be listed. A deny rule matches as soon as any listed ID appears.
+    fn ids_match(&self, allowed: Option<&[String]>, found: &BTreeSet<String>) -> bool {
+        let Some(allowed) = allowed else {
+            return true;
+        };
+        let listed = |id: &String| allowed.contains(id);
+        match self.effect {
+            Effect::Allow => !found.is_empty() && found.iter().all(listed),
+            Effect::Deny => found.iter().any(listed),
+        }
+    }
+}
+
+/// Workbook and collection IDs named anywhere in a payload.
+#[derive(Debug, Default)]
+struct PayloadIds {
+    workbooks: BTreeSet<String>,
+    collections: BTreeSet<String>,
+}
+
+impl PayloadIds {
+    fn collect(payload: &Value) -> Self {
+        let mut ids = Self::default();
+        ids.walk(payload, 0);
+        ids
+    }
+
+    fn walk(&mut self, value: &Value, depth: usize) {
+        if depth > MAX_PAYLOAD_DEPTH {
+            return;
+        }
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object {
+                    // Most methods use `workbookId`, but `createDataset` and `createConnection`
+                    // take `workbook_id`; compare keys without case or underscores.
+                    let key = key.replace('_', "").to_ascii_lowercase();
+                    let target = match key.as_str() {
+                        "workbookid" | "workbookids" => Some(&mut self.workbooks),
+                        // Collection methods name the destination collection `parentId`.
+                        "collectionid" | "collectionids" | "parentid" => {
+                            Some(&mut self.collections)
+                        }
+                        _ => None,
+                    };
+                    if let Some(target) = target {
+                        match value {
+                            Value::String(id) => {
+                                target.insert(id.clone());
+                            }
+                            Value::Array(items) => {
+                                target.extend(
+                                    items.iter().filter_map(Value::as_str).map(str::to_owned),
+                                );
+                            }
+                            _ => {}
+                        }
+                    }
+                    self.walk(value, depth + 1);
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    self.walk(item, depth + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Matches `*` (any run of characters) and `?` (one character) against the whole `text`.
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const CHARTS_IN_WORKBOOK: &str = r#"
+        default = "deny"
+
+        [[rules]]
+        name = "no-experimental"
+        effect = "deny"
+        experimental = true
+
+        [[rules]]
+        name = "reads"
+        effect = "allow"
+        categories = ["read"]
+
+        [[rules]]
+        name = "charts-in-workbook"
+        effect = "allow"
+        methods = ["create*Chart", "update*Chart", "delete*Chart"]
+        workbook_ids = ["wb-x"]
+    "#;
+
+    fn call<'a>(method: &'a str, category: &'a str, payload: &'a Value) -> Call<'a> {
+        Call {
+            method,
+            category,
+            experimental: false,
+            payload,
+        }
+    }
+
+    #[test]
+    fn first_matching_rule_decides() {
+        let policy = Policy::parse(CHARTS_IN_WORKBOOK).unwrap();
+
+        let payload = json!({"entry": {"workbookId": "wb-x", "name": "c"}});
+        let decision = policy.evaluate(&call("createWizardChart", "write", &payload));
+        assert_eq!(decision.effect, Effect::Allow);
+        assert_eq!(decision.describe(), "rule #3 (charts-in-workbook)");
+
+        let decision = policy.evaluate(&Call {
+            experimental: true,
+            ..call("createWizardChart", "write", &payload)
+        });
+        assert_eq!(decision.effect, Effect::Deny);
+        assert_eq!(decision.describe(), "rule #1 (no-experimental)");
+
+        let decision = policy.evaluate(&call("getDataset", "read", &json!({})));
+        assert_eq!(decision.effect, Effect::Allow);
+
+        let decision = policy.evaluate(&call("deleteDataset", "write", &json!({})));
+        assert_eq!(decision.effect, Effect::Deny);
+        assert_eq!(decision.describe(), "default effect");
+    }
+
+    #[test]
+    fn allow_rules_need_every_payload_id_listed() {
+        let policy = Policy::parse(CHARTS_IN_WORKBOOK).unwrap();
+
+        for payload in [
+            json!({"entry": {"workbookId": "wb-y"}}),
+            json!({"chartId": "c-1"}),
+            json!({"entry": {"workbookId": "wb-x"}, "copy": {"workbookIds": ["wb-y"]}}),
+        ] {
+            let decision = policy.evaluate(&call("updateWizardChart", "write", &payload));
+            assert_eq!(decision.effect, Effect::Deny, "{payload}");
+        }
+    }
+
+    #[test]
+    fn deny_rules_match_any_listed_id() {
+        let policy = Policy::parse(
+            r#"
+            [[rules]]
+            effect = "deny"
+            collection_ids = ["prod"]
+            "#,
+        )
+        .unwrap();
+
+        let payload = json!({"collectionIds": ["sandbox", "prod"]});
+        assert_eq!(
+            policy
+                .evaluate(&call("deleteCollections", "write", &payload))
+                .effect,
+            Effect::Deny
+        );
+        assert_eq!(
+            policy
+                .evaluate(&call(
+                    "getCollection",
+                    "read",
+                    &json!({"collectionId": "dev"})
+                ))
+                .effect,
+            Effect::Allow
+        );
+    }
+
+    #[test]
+    fn snake_case_workbook_ids_are_matched() {
+        let policy = Policy::parse(
+            r#"
+            [[rules]]
+            effect = "deny"
+            workbook_ids = ["wb-y"]
+            "#,
+        )
+        .unwrap();
+        let payload = json!({"workbook_id": "wb-y", "dataset": {}});
+        assert_eq!(
+            policy
+                .evaluate(&call("createDataset", "write", &payload))
+                .effect,
+            Effect::Deny
+        );
+
+        let policy = Policy::parse(
+            r#"
+            default = "deny"
+
+            [[rules]]
+            effect = "allow"
+            methods = ["createDataset", "createConnection"]
+            workbook_ids = ["wb-x"]
+            "#,
+        )
+        .unwrap();
+        for (workbook, effect) in [("wb-x", Effect::Allow), ("wb-y", Effect::Deny)] {
+            let payload = json!({"workbook_id": workbook, "name": "conn"});
+            assert_eq!(
+                policy
+                    .evaluate(&call("createConnection", "write", &payload))
+                    .effect,
+                effect,
+                "{payload}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_rejects_unknown_keys_and_empty_lists() {
+        assert!(Policy::parse("[[rules]]\neffect = \"deny\"\nmethod = [\"x\"]\n").is_err());
+        assert!(Policy::parse("[[rules]]\neffect = \"deny\"\nmethods = []\n").is_err());
+        assert!(Policy::parse("default = \"maybe\"\n").is_err());
+    }
+
+    #[test]
+    fn glob_matches_whole_name() {
+        assert!(glob_match("get*", "getDataset"));
+        assert!(glob_match("*Chart", "updateQLChart"));
+        assert!(glob_match("update*Chart", "updateWizardChart"));
+        assert!(glob_match("get?LChart", "getQLChart"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("get*", "listDirectory"));
+        assert!(!glob_match("*Chart", "getCharts"));
+        assert!(!glob_match("getDataset", "getDatasets"));
+    }
+}
@@ -0,0 +1,514 @@
+//! MCP prompts for common DataLens workflows, pre-filled with compact summaries of the
+//! objects they are about.
+
+use rmcp::{
+    ErrorData as McpError,
+    model::{
+        GetPromptResult, JsonObject, Prompt, PromptArgument, PromptMessage, PromptMessageRole,
+    },
+};
+use serde_json::{Map, Value, json};
+
+use crate::{
+    confirm::compact_entry,
+    responses::{self, ConnectionResponse, DashboardResponse, DatasetResponse, WorkbookResponse},
+};
+
+/// Items listed per section of a summary.
+const MAX_LISTED: usize = 50;
+
+const PROFILE_ARGUMENT: (&str, &str, bool) = (
+    "profile",
+    "Profile (organization) to read from; the default profile when omitted.",
+    false,
+);
+
+pub(crate) fn list() -> Vec<Prompt> {
+    let prompt = |name: &str, description: &str, arguments: &[(&str, &str, bool)]| {
+        let arguments = arguments
+            .iter()
+            .chain([&PROFILE_ARGUMENT])
+            .map(|(name, description, required)| PromptArgument {
+                name: (*name).to_owned(),
+                title: None,
+                description: Some((*description).to_owned()),
+                required: Some(*required),
+            })
+            .collect();
+        Prompt::new(name, Some(description), Some(arguments))
+    };
+
+    vec![
+        prompt(
+            "explain_dashboard",
+            "Explain what a dashboard shows: its tabs, charts, selectors and the data behind them.",
+            &[("dashboard_id", "Dashboard entry ID.", true)],
+        ),
+        prompt(
+            "audit_workbook_permissions",
+            "Review who can do what in a workbook and its entries, and flag risky access.",
+            &[("workbook_id", "Workbook ID.", true)],
+        ),
+        prompt(
+            "create_dataset_from_connection",
+            "Draft, create and then validate a dataset over one table of a connection.",
+            &[
+                ("connection_id", "Connection entry ID.", true),
+                (
+                    "table",
+                    "Table (or `schema.table`) to build the dataset on.",
+                    true,
+                ),
+            ],
+        ),
+        prompt(
+            "find_charts_using_dataset",
+            "List the charts built on a dataset and the dashboards that show them.",
+            &[("dataset_id", "Dataset entry ID.", true)],
+        ),
+    ]
+}
+
+/// A `prompts/get` request with its arguments checked.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct PromptRequest {
+    pub(crate) kind: PromptKind,
+    pub(crate) profile: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum PromptKind {
+    ExplainDashboard {
+        dashboard_id: String,
+    },
+    AuditWorkbookPermissions {
+        workbook_id: String,
+    },
+    CreateDatasetFromConnection {
+        connection_id: String,
+        table: String,
+    },
+    FindChartsUsingDataset {
+        dataset_id: String,
+    },
+}
+
+/// A read call a prompt needs, labelled with its section in the summary.
+pub(crate) type Fetch = (&'static str, &'static str, Value);
+
+impl PromptRequest {
+    pub(crate) fn parse(name: &str, arguments: Option<&JsonObject>) -> Result<Self, McpError> {
+        let argument = |key: &str| {
+            arguments
+                .and_then(|arguments| arguments.get(key))
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_owned)
+        };
+        let required = |key: &str| {
+            argument(key).ok_or_else(|| {
+                McpError::invalid_params(
+                    format!("prompt `{name}` requires the `{key}` argument"),
+                    Some(json!({ "prompt": name, "argument": key })),
+                )
+            })
+        };
+
+        let kind = match name {
+            "explain_dashboard" => PromptKind::ExplainDashboard {
+                dashboard_id: required("dashboard_id")?,
+            },
+            "audit_workbook_permissions" => PromptKind::AuditWorkbookPermissions {
+                workbook_id: required("workbook_id")?,
+            },
+            "create_dataset_from_connection" => PromptKind::CreateDatasetFromConnection {
+                connection_id: required("connection_id")?,
+                table: required("table")?,
+            },
+            "find_charts_using_dataset" => PromptKind::FindChartsUsingDataset {
+                dataset_id: required("dataset_id")?,
+            },
+            _ => {
+                return Err(McpError::invalid_params(
+                    format!("unknown prompt `{name}`"),
+                    Some(json!({
+                        "prompt": name,
+                        "available": list().into_iter().map(|prompt| prompt.name).collect::<Vec<_>>(),
+                    })),
+                ));
+            }
+        };
+        Ok(Self {
+            kind,
+            profile: argument("profile"),
+        })
+    }
+
+    pub(crate) fn fetches(&self) -> Vec<Fetch> {
+        match &self.kind {
+            PromptKind::ExplainDashboard { dashboard_id } => vec![
+                (
+                    "dashboard",
+                    "getDashboard",
+                    json!({ "dashboardId": dashboard_id }),
+                ),
+                (
+                    "relations",
+                    "getEntriesRelations",
+                    json!({ "entryIds": [dashboard_id] }),
+                ),
+            ],
+            PromptKind::AuditWorkbookPermissions { workbook_id } => vec![
+                (
+                    "workbook",
+                    "getWorkbook",
+                    json!({ "workbookId": workbook_id, "includePermissionsInfo": true }),
+                ),
+                (
+                    "entries",
+                    "getWorkbookEntries",
+                    json!({
+                        "workbookId": workbook_id,
+                        "includePermissionsInfo": true,
+                        "pageSize": MAX_LISTED,
+                    }),
+                ),
+            ],
+            PromptKind::CreateDatasetFromConnection { connection_id, .. } => vec![(
+                "connection",
+                "getConnection",
+                json!({ "connectionId": connection_id }),
+            )],
+            PromptKind::FindChartsUsingDataset { dataset_id } => vec![
+                ("dataset", "getDataset", json!({ "datasetId": dataset_id })),
+                (
+                    "charts",
+                    "getEntriesRelations",
+                    json!({ "entryIds": [dataset_id], "scope": "widget" }),
+                ),
+            ],
+        }
+    }
+
+    /// Builds the prompt from the results of [`Self::fetches`], in the same order. Failed
+    /// calls are reported in the summary instead of failing the prompt.
+    pub(crate) fn render(&self, results: Vec<(Fetch, Result<Value, String>)>) -> GetPromptResult {
+        let mut summary = Map::new();
+        let mut errors = Vec::new();
+        for ((section, method, _), result) in results {
+            match result {
+                Ok(response) => {
+                    summary.insert(section.to_owned(), summarize(section, response));
+                }
+                Err(message) => errors.push(json!({ "method": method, "message": message })),
+            }
+        }
+        if !errors.is_empty() {
+            summary.insert("errors".to_owned(), Value::Array(errors));
+        }
+
+        let profile = match &self.profile {
+            Some(profile) => format!(" Pass `profile: \"{profile}\"` to every tool call."),
+            None => String::new(),
+        };
+        let (description, task) = match &self.kind {
+            PromptKind::ExplainDashboard { dashboard_id } => (
+                format!("Explain dashboard {dashboard_id}"),
+                format!(
+                    "Explain the DataLens dashboard `{dashboard_id}` to a business user: what each tab is for, what its charts show and how the selectors filter them. Name the datasets behind the charts. The summary below lists tabs, widgets with their chart IDs, and related entries. For chart details call `datalens_get_wizard_chart`, `datalens_get_ql_chart` or `datalens_get_editor_chart`, and `datalens_get_dataset` for the fields a chart uses.{profile}"
+                ),
+            ),
+            PromptKind::AuditWorkbookPermissions { workbook_id } => (
+                format!("Audit permissions of workbook {workbook_id}"),
+                format!(
+                    "Audit access to the DataLens workbook `{workbook_id}`. From the permissions below, report what the current user may do with the workbook and each entry, point out entries that are locked or whose permissions differ from the workbook's, and flag anything that looks too broad (for example publish or embed rights). Use `datalens_get_entries_permissions` for entries the summary does not cover. Do not change access bindings; only recommend changes.{profile}"
+                ),
+            ),
+            PromptKind::CreateDatasetFromConnection {
+                connection_id,
+                table,
+            } => (
+                format!("Create a dataset from {table}"),
+                format!(
+                    "Create a DataLens dataset over table `{table}` of connection `{connection_id}`, summarized below. First read the request schema with `datalens_get_method_schema` for `createDataset`. Then draft the payload: one source for the table and a result_schema with a field per useful column, using measures for numeric facts and dimensions for the rest. Preview the request with `datalens_create_dataset` and `dry_run: true`; this only checks the payload against the local schema and does not contact DataLens. Show the payload to the user and create the dataset only after they approve. Then run `datalens_validate_dataset` on the new dataset ID, which is where DataLens reports field and formula errors, and fix them with `updateDataset` through `datalens_rpc`.{profile}"
+                ),
+            ),
+            PromptKind::FindChartsUsingDataset { dataset_id } => (
+                format!("Find charts using dataset {dataset_id}"),
+                format!(
+                    "List the charts built on the DataLens dataset `{dataset_id}`, summarized below with its fields. For each chart, give its title and type and, if it is on dashboards, which ones (`datalens_get_entries_relations` on the chart). Note which dataset fields each chart uses when that is needed to judge the impact of changing the dataset.{profile}"
+                ),
+            ),
+        };
+
+        let summary = serde_json::to_string_pretty(&Value::Object(summary))
+            .unwrap_or_else(|_| "{}".to_owned());
+        GetPromptResult {
+            description: Some(description),
+            messages: vec![PromptMessage::new_text(
+                PromptMessageRole::User,
+                format!("{task}\n\n```json\n{summary}\n```"),
+            )],
+        }
+    }
+}
+
+fn summarize(section: &str, response: Value) -> Value {
+    let Value::Object(response) = response else {
+        return response;
+    };
+    match section {
+        "dashboard" => summarize_dashboard(responses::from_object(response).unwrap_or_default()),
+        "workbook" => summarize_workbook(responses::from_object(response).unwrap_or_default()),
+        "connection" => summarize_connection(responses::from_object(response).unwrap_or_default()),
+        "dataset" => summarize_dataset(responses::from_object(response).unwrap_or_default()),
+        _ => summarize_entries(&response),
+    }
+}
+
+/// Compact entries of `entries` or `relations`, with the total when some were cut.
+fn summarize_entries(response: &Map<String, Value>) -> Value {
+    let items = ["entries", "relations"]
+        .iter()
+        .find_map(|key| response.get(*key).and_then(Value::as_array))
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let listed: Vec<Value> = items
+        .iter()
+        .take(MAX_LISTED)
+        .map(|item| {
+            let mut entry = compact_entry(item);
+            if let (Value::Object(entry), Some(permissions)) = (&mut entry, item.get("permissions"))
+            {
+                entry.insert("permissions".to_owned(), permissions.clone());
+            }
+            entry
+        })
+        .collect();
+    let mut summary = json!({ "items": listed, "total": items.len() });
+    if response
+        .get("nextPageToken")
+        .is_some_and(|token| !token.is_null())
+    {
+        summary["more"] = json!(true);
+    }
+    summary
+}
+
+fn summarize_dashboard(response: DashboardResponse) -> Value {
+    let Some(entry) = response.entry else {
+        return Value::Object(response.extra);
+    };
+    let tabs: Vec<Value> = entry
+        .data
+        .and_then(|data| data.tabs)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|tab| {
+            let items: Vec<Value> = tab
+                .items
+                .unwrap_or_default()
+                .into_iter()
+                .take(MAX_LISTED)
+                .map(|item| {
+                    let data = item.data.unwrap_or_default();
+                    let title = data
+                        .get("title")
+                        .or_else(|| data.get("text"))
+                        .and_then(Value::as_str);
+                    // Widgets hold one or more charts as their own tabs.
+                    let charts: Vec<Value> = data
+                        .get("tabs")
+                        .and_then(Value::as_array)
+                        .map(Vec::as_slice)
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|chart| json!({ "title": chart.get("title"), "chartId": chart.get("chartId") }))
+                        .collect();
+                    let dataset_id = data.pointer("/source/datasetId");
+                    let mut summary = json!({ "type": item.kind, "title": title });
+                    if !charts.is_empty() {
+                        summary["charts"] = Value::Array(charts);
+                    }
+                    if let Some(dataset_id) = dataset_id {
+                        summary["datasetId"] = dataset_id.clone();
+                    }
+                    summary
+                })
+                .collect();
+            json!({ "title": tab.title, "items": items })
+        })
+        .collect();
+    json!({
+        "entryId": entry.entry_id,
+        "key": entry.key,
+        "workbookId": entry.workbook_id,
+        "updatedAt": entry.updated_at,
+        "updatedBy": entry.updated_by,
+        "tabs": tabs,
+    })
+}
+
+fn summarize_workbook(response: WorkbookResponse) -> Value {
+    json!({
+        "workbookId": response.workbook_id,
+        "title": response.title,
+        "description": response.description,
+        "collectionId": response.collection_id,
+        "createdBy": response.created_by,
+        "permissions": response.permissions,
+    })
+}
+
+/// Only identifying fields and the names of connector settings, never their values.
+fn summarize_connection(response: ConnectionResponse) -> Value {
+    json!({
+        "id": response.id,
+        "key": response.key,
+        "name": response.name,
+        "dbType": response.db_type,
+        "workbookId": response.workbook_id,
+        "settings": response.extra.keys().collect::<Vec<_>>(),
+    })
+}
+
+fn summarize_dataset(response: DatasetResponse) -> Value {
+    let dataset = response.dataset.unwrap_or_default();
+    let fields = dataset.result_schema.unwrap_or_default();
+    let listed: Vec<Value> = fields
+        .iter()
+        .take(MAX_LISTED)
+        .map(|field| {
+            json!({
+                "guid": field.guid,
+                "title": field.title,
+                "type": field.kind,
+                "dataType": field.data_type,
+                "calcMode": field.calc_mode,
+            })
+        })
+        .collect();
+    json!({
+        "id": response.id,
+        "key": response.key,
+        "workbookId": response.workbook_id,
+        "sources": dataset.sources.map_or(0, |sources| sources.len()),
+        "fields": listed,
+        "fieldsTotal": fields.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rmcp::model::PromptMessageContent;
+
+    use super::*;
+
+    fn arguments(value: Value) -> JsonObject {
+        value.as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn parse_checks_required_arguments() {
+        let request = PromptRequest::parse(
+            "create_dataset_from_connection",
+            Some(&arguments(
+                json!({"connection_id": "conn-1", "table": "public.sales", "profile": " "}),
+            )),
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            PromptRequest {
+                kind: PromptKind::CreateDatasetFromConnection {
+                    connection_id: "conn-1".to_owned(),
+                    table: "public.sales".to_owned(),
+                },
+                profile: None,
+            }
+        );
+
+        let err = PromptRequest::parse(
+            "create_dataset_from_connection",
+            Some(&arguments(json!({"connection_id": "conn-1"}))),
+        )
+        .unwrap_err();
+        assert_eq!(err.data.unwrap()["argument"], json!("table"));
+        assert!(PromptRequest::parse("explain_everything", None).is_err());
+
+        for prompt in list() {
+            let required: Vec<(String, Value)> = prompt
+                .arguments
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|argument| argument.required == Some(true))
+                .map(|argument| (argument.name, json!("x")))
+                .collect();
+            let arguments: JsonObject = required.into_iter().collect();
+            assert!(
+                PromptRequest::parse(&prompt.name, Some(&arguments)).is_ok(),
+                "{}",
+                prompt.name
+            );
+        }
+    }
+
+    #[test]
+    fn render_embeds_compact_summaries_and_errors() {
+        let request = PromptRequest::parse(
+            "explain_dashboard",
+            Some(&arguments(
+                json!({"dashboard_id": "d-1", "profile": "prod"}),
+            )),
+        )
+        .unwrap();
+        let mut fetches = request.fetches().into_iter();
+        let dashboard = json!({"entry": {
+            "entryId": "d-1",
+            "key": "Sales overview",
+            "data": {"tabs": [{"title": "Main", "items": [
+                {"type": "widget", "data": {"tabs": [{"title": "Revenue", "chartId": "c-1", "params": {}}]}},
+                {"type": "control", "data": {"title": "Region", "source": {"datasetId": "ds-1"}}}
+            ]}]}
+        }});
+        let result = request.render(vec![
+            (fetches.next().unwrap(), Ok(dashboard)),
+            (fetches.next().unwrap(), Err("forbidden".to_owned())),
+        ]);
+
+        assert_eq!(result.description.as_deref(), Some("Explain dashboard d-1"));
+        let PromptMessageContent::Text { text } = &result.messages[0].content else {
+            panic!("expected a text message");
+        };
+        assert!(text.contains("`profile: \"prod\"`"));
+        let summary: Value = serde_json::from_str(
+            text.split("```json\n")
+                .nth(1)
+                .unwrap()
+                .trim_end_matches("\n```"),
+        )
+        .unwrap();
+        assert_eq!(
+            summary["dashboard"]["tabs"][0]["items"],
+            json!([
+                {"type": "widget", "title": null, "charts": [{"title": "Revenue", "chartId": "c-1"}]},
+                {"type": "control", "title": "Region", "datasetId": "ds-1"}
+            ])
+        );
+        assert_eq!(
+            summary["errors"],
+            json!([{"method": "getEntriesRelations", "message": "forbidden"}])
+        );
+    }
+
+    #[test]
+    fn connection_summary_omits_setting_values() {
+        let summary = summarize(
+            "connection",
+            json!({"id": "conn-1", "db_type": "postgres", "host": "db.internal", "password": "s3cret"}),
+        );
+        assert_eq!(summary["settings"], json!(["host", "password"]));
+        assert!(!summary.to_string().contains("s3cret"));
+    }
+}
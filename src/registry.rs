@@ -0,0 +1,704 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use rmcp::model::ToolAnnotations;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use tracing::{info, warn};
+
+use crate::{env_non_empty, schema};
+
+const EMBEDDED_REGISTRY: &str = include_str!("../openapi/datalens-rpc-methods.json");
+const RPC_PATH_PREFIX: &str = "/rpc/";
+const HTTP_METHODS: [&str; 5] = ["post", "get", "put", "patch", "delete"];
+/// Nesting depth at which synthesized examples stop descending into schemas.
+const MAX_EXAMPLE_DEPTH: usize = 12;
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MethodRegistry {
+    pub(crate) snapshot_date: String,
+    pub(crate) source_url: String,
+    pub(crate) openapi_version: Option<String>,
+    pub(crate) api_info: Value,
+    #[serde(default)]
+    pub(crate) components: RegistryComponents,
+    pub(crate) methods: Vec<MethodRegistryItem>,
+}
+
+/// Shared schemas that method request schemas reference as `#/components/schemas/<name>`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct RegistryComponents {
+    /// Where the schemas come from when it is not the document at `sourceUrl`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) source: Option<String>,
+    #[serde(default)]
+    pub(crate) schemas: Map<String, Value>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MethodRegistryItem {
+    pub(crate) method: String,
+    pub(crate) category: String,
+    pub(crate) experimental: bool,
+    pub(crate) typed_tool: Option<String>,
+    pub(crate) invoke_with: String,
+    pub(crate) summary: Option<String>,
+    pub(crate) description: Option<String>,
+    pub(crate) request_schema: Value,
+    #[serde(default)]
+    pub(crate) request_example: Option<Value>,
+    #[serde(default)]
+    pub(crate) response_example: Option<Value>,
+}
+
+impl MethodRegistry {
+    pub(crate) fn find(&self, method: &str) -> Option<&MethodRegistryItem> {
+        self.methods.iter().find(|item| item.method == method)
+    }
+}
+
+impl MethodRegistryItem {
+    /// MCP hints for the typed tool that calls this method. `delete*`, `update*` and `move*`
+    /// replace existing state, so repeating them changes nothing further; other writes add objects.
+    pub(crate) fn tool_annotations(&self) -> ToolAnnotations {
+        let annotations = ToolAnnotations::new().open_world(true);
+        if self.category == "read" {
+            return annotations
+                .read_only(true)
+                .destructive(false)
+                .idempotent(true);
+        }
+        let replaces = ["delete", "update", "move"]
+            .iter()
+            .any(|prefix| self.method.starts_with(prefix));
+        annotations
+            .read_only(false)
+            .destructive(replaces)
+            .idempotent(replaces)
+    }
+}
+
+static METHOD_REGISTRY: OnceLock<MethodRegistry> = OnceLock::new();
+static EMBEDDED: OnceLock<MethodRegistry> = OnceLock::new();
+
+/// The registry in use: `DATALENS_OPENAPI_PATH` when it is set and loads, else the embedded snapshot.
+pub(crate) fn method_registry() -> &'static MethodRegistry {
+    METHOD_REGISTRY.get_or_init(|| {
+        let Some(path) = env_non_empty("DATALENS_OPENAPI_PATH") else {
+            return embedded_registry_owned();
+        };
+        match load(Path::new(&path)) {
+            Ok(registry) => {
+                info!(
+                    path = %path,
+                    methods = registry.methods.len(),
+                    snapshot_date = %registry.snapshot_date,
+                    "loaded DataLens method registry"
+                );
+                registry
+            }
+            Err(error) => {
+                warn!(
+                    path = %path,
+                    "failed to load DATALENS_OPENAPI_PATH, using the embedded method registry: {error:#}"
+                );
+                embedded_registry_owned()
+            }
+        }
+    })
+}
+
+/// The snapshot compiled into the binary; also the source of typed tool names for converted documents.
+pub(crate) fn embedded_registry() -> &'static MethodRegistry {
+    EMBEDDED.get_or_init(embedded_registry_owned)
+}
+
+fn embedded_registry_owned() -> MethodRegistry {
+    serde_json::from_str(EMBEDDED_REGISTRY)
+        .expect("embedded DataLens method registry must be valid JSON")
+}
+
+/// Loads a registry JSON or converts a raw OpenAPI document, depending on what the file holds.
+pub(crate) fn load(path: &Path) -> Result<MethodRegistry> {
+    let raw =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let document: Value = serde_json::from_str(&raw)
+        .with_context(|| format!("{} is not valid JSON", path.display()))?;
+
+    if document.get("methods").is_some_and(Value::is_array) {
+        return serde_json::from_value(document)
+            .with_context(|| format!("{} is not a valid method registry", path.display()));
+    }
+    if document.get("openapi").is_some() && document.get("paths").is_some() {
+        let snapshot_date = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map(DateTime::<Utc>::from)
+            .unwrap_or_else(|_| Utc::now())
+            .format("%Y-%m-%d")
+            .to_string();
+        return from_openapi(
+            &document,
+            &source_url(path),
+            snapshot_date,
+            embedded_registry(),
+        );
+    }
+    bail!(
+        "{} is neither a method registry (with `methods`) nor an OpenAPI document (with `openapi` and `paths`)",
+        path.display()
+    )
+}
+
+/// Converts an OpenAPI 3.1 document whose operations live under `/rpc/<method>`.
+///
+/// Typed tool mappings and categories of methods already in `baseline` are kept, since they
+/// describe this server rather than the API; new methods get inferred values.
+pub(crate) fn from_openapi(
+    document: &Value,
+    source_url: &str,
+    snapshot_date: String,
+    baseline: &MethodRegistry,
+) -> Result<MethodRegistry> {
+    let paths = document
+        .get("paths")
+        .and_then(Value::as_object)
+        .context("OpenAPI document has no `paths` object")?;
+    let components = document
+        .pointer("/components/schemas")
+        .and_then(Value::as_object)
+        .cloned()
+        .unwrap_or_default();
+
+    let mut methods = Vec::new();
+    for (path, item) in paths {
+        let Some(name) = path.strip_prefix(RPC_PATH_PREFIX) else {
+            continue;
+        };
+        let Some(operation) = HTTP_METHODS
+            .iter()
+            .find_map(|http_method| item.get(*http_method))
+        else {
+            continue;
+        };
+        let name = name.trim_matches('/');
+        methods.push(convert_operation(
+            name,
+            operation,
+            baseline.find(name),
+            &components,
+        ));
+    }
+    if methods.is_empty() {
+        bail!("OpenAPI document has no `{RPC_PATH_PREFIX}<method>` operations");
+    }
+
+    Ok(MethodRegistry {
+        snapshot_date,
+        source_url: source_url.to_owned(),
+        openapi_version: document
+            .get("openapi")
+            .and_then(Value::as_str)
+            .map(str::to_owned),
+        api_info: document.get("info").cloned().unwrap_or_else(|| json!({})),
+        components: RegistryComponents {
+            source: None,
+            schemas: components,
+        },
+        methods,
+    })
+}
+
+fn convert_operation(
+    method: &str,
+    operation: &Value,
+    known: Option<&MethodRegistryItem>,
+    components: &Map<String, Value>,
+) -> MethodRegistryItem {
+    let text = |key: &str| {
+        operation
+            .get(key)
+            .and_then(Value::as_str)
+            .map(str::to_owned)
+    };
+    let request = operation.pointer("/requestBody/content/application~1json");
+    let response = ["200", "201", "default"].iter().find_map(|status| {
+        operation.pointer(&format!("/responses/{status}/content/application~1json"))
+    });
+
+    let request_schema = request
+        .and_then(|content| content.get("schema"))
+        .cloned()
+        .unwrap_or_else(|| json!({"type": "object"}));
+    let request_example = request
+        .and_then(media_example)
+        .unwrap_or_else(|| example_from_schema(&request_schema, components, true));
+    let response_example = response.and_then(|content| {
+        media_example(content).or_else(|| {
+            content
+                .get("schema")
+                .map(|schema| example_from_schema(schema, components, false))
+        })
+    });
+
+    let typed_tool = known.and_then(|item| item.typed_tool.clone());
+    MethodRegistryItem {
+        method: method.to_owned(),
+        category: known.map_or_else(
+            || infer_category(method).to_owned(),
+            |item| item.category.clone(),
+        ),
+        experimental: experimental_marker(operation)
+            .or(known.map(|item| item.experimental))
+            .unwrap_or(false),
+        invoke_with: typed_tool
+            .clone()
+            .unwrap_or_else(|| "datalens_rpc".to_owned()),
+        typed_tool,
+        summary: text("summary"),
+        description: text("description"),
+        request_schema,
+        request_example: Some(request_example),
+        response_example,
+    }
+}
+
+/// Placeholder payload shaped like `schema`: `"string"`, `1`, `true`, the first enum value.
+/// Request examples include required properties only, when the schema lists any.
+pub(crate) fn example_from_schema(
+    schema: &Value,
+    components: &Map<String, Value>,
+    required_only: bool,
+) -> Value {
+    example(&schema::resolve_refs(schema, components), required_only, 0)
+}
+
+fn example(schema: &Value, required_only: bool, depth: usize) -> Value {
+    let Some(schema) = schema.as_object() else {
+        return json!({});
+    };
+    if depth > MAX_EXAMPLE_DEPTH || schema.contains_key("$ref") {
+        return json!({});
+    }
+    if let Some(value) = schema.get("example").or_else(|| schema.get("const")) {
+        return value.clone();
+    }
+    if let Some(first) = schema
+        .get("enum")
+        .and_then(Value::as_array)
+        .and_then(|values| values.first())
+    {
+        return first.clone();
+    }
+    for keyword in ["oneOf", "anyOf"] {
+        if let Some(first) = schema
+            .get(keyword)
+            .and_then(Value::as_array)
+            .and_then(|branches| branches.first())
+        {
+            return example(first, required_only, depth + 1);
+        }
+    }
+    if let Some(branches) = schema.get("allOf").and_then(Value::as_array) {
+        let mut merged = Map::new();
+        for branch in branches {
+            match example(branch, required_only, depth + 1) {
+                Value::Object(object) => merged.extend(object),
+                other => return other,
+            }
+        }
+        return Value::Object(merged);
+    }
+
+    let schema_type = match schema.get("type") {
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .find(|name| *name != "null"),
+        Some(other) => other.as_str(),
+        None if schema.contains_key("properties") => Some("object"),
+        None => None,
+    };
+    match schema_type {
+        Some("string") => json!("string"),
+        Some("integer" | "number") => json!(1),
+        Some("boolean") => json!(true),
+        Some("null") => Value::Null,
+        Some("array") => match schema.get("items") {
+            Some(items) => json!([example(items, required_only, depth + 1)]),
+            None => json!([]),
+        },
+        _ => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            let required: Vec<&str> = schema
+                .get("required")
+                .and_then(Value::as_array)
+                .map(|names| names.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+            let mut object = Map::new();
+            for (name, property) in properties.into_iter().flatten() {
+                if !required_only || required.is_empty() || required.contains(&name.as_str()) {
+                    object.insert(name.clone(), example(property, required_only, depth + 1));
+                }
+            }
+            Value::Object(object)
+        }
+    }
+}
+
+/// `get*` and `list*` methods read; everything else is treated as a write.
+pub(crate) fn infer_category(method: &str) -> &'static str {
+    let starts_with_word = |prefix: &str| {
+        method
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_uppercase))
+    };
+    if starts_with_word("get") || starts_with_word("list") {
+        "read"
+    } else {
+        "write"
+    }
+}
+
+/// `x-experimental`, an `experimental` tag, or "experimental" in the summary; `None` when unmarked.
+fn experimental_marker(operation: &Value) -> Option<bool> {
+    if let Some(flag) = operation.get("x-experimental").and_then(Value::as_bool) {
+        return Some(flag);
+    }
+    let tagged = operation
+        .get("tags")
+        .and_then(Value::as_array)
+        .is_some_and(|tags| {
+            tags.iter()
+                .filter_map(Value::as_str)
+                .any(|tag| tag.eq_ignore_ascii_case("experimental"))
+        });
+    let in_summary = operation
+        .get("summary")
+        .and_then(Value::as_str)
+        .is_some_and(|summary| summary.to_ascii_lowercase().contains("experimental"));
+    (tagged || in_summary).then_some(true)
+}
+
+/// Writes the registry in the layout of the checked-in snapshot.
+pub(crate) fn write(registry: &MethodRegistry, path: &Path) -> Result<()> {
+    let mut json = serde_json::to_string_pretty(registry)?;
+    json.push('\n');
+    fs::write(path, json).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Methods added, removed or changed between two registries.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct RegistryChanges {
+    pub(crate) added: Vec<String>,
+    pub(crate) removed: Vec<String>,
+    /// Method name and the registry fields that differ.
+    pub(crate) changed: Vec<(String, Vec<&'static str>)>,
+}
+
+impl RegistryChanges {
+    pub(crate) fn between(old: &MethodRegistry, new: &MethodRegistry) -> Self {
+        let mut changes = Self::default();
+        for item in &new.methods {
+            let Some(previous) = old.find(&item.method) else {
+                changes.added.push(item.method.clone());
+                continue;
+            };
+            let old_schema =
+                schema::resolve_refs(&previous.request_schema, &old.components.schemas);
+            let new_schema = schema::resolve_refs(&item.request_schema, &new.components.schemas);
+            let fields: Vec<&'static str> = [
+                ("category", previous.category != item.category),
+                ("experimental", previous.experimental != item.experimental),
+                ("typedTool", previous.typed_tool != item.typed_tool),
+                ("summary", previous.summary != item.summary),
+                ("requestSchema", old_schema != new_schema),
+                (
+                    "responseExample",
+                    previous.response_example != item.response_example,
+                ),
+            ]
+            .into_iter()
+            .filter_map(|(field, differs)| differs.then_some(field))
+            .collect();
+            if !fields.is_empty() {
+                changes.changed.push((item.method.clone(), fields));
+            }
+        }
+        changes.removed = old
+            .methods
+            .iter()
+            .filter(|item| new.find(&item.method).is_none())
+            .map(|item| item.method.clone())
+            .collect();
+        changes
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    pub(crate) fn render(&self) -> String {
+        let mut out = String::new();
+        for method in &self.added {
+            let _ = writeln!(out, "+ {method}");
+        }
+        for method in &self.removed {
+            let _ = writeln!(out, "- {method}");
+        }
+        for (method, fields) in &self.changed {
+            let _ = writeln!(out, "~ {method} ({})", fields.join(", "));
+        }
+        out
+    }
+}
+
+fn media_example(content: &Value) -> Option<Value> {
+    content.get("example").cloned().or_else(|| {
+        content
+            .get("examples")
+            .and_then(Value::as_object)
+            .and_then(|examples| examples.values().next())
+            .and_then(|example| example.get("value"))
+            .cloned()
+    })
+}
+
+fn source_url(path: &Path) -> String {
+    let absolute = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    format!("file://{}", absolute.display())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn openapi_document() -> Value {
+        json!({
+            "openapi": "3.1.0",
+            "info": { "title": "DataLens API", "version": "2" },
+            "paths": {
+                "/rpc/getDataset": {
+                    "post": {
+                        "summary": "Get dataset",
+                        "requestBody": { "content": { "application/json": {
+                            "schema": { "$ref": "#/components/schemas/GetDatasetArgs" },
+                            "example": { "datasetId": "ds-1" }
+                        } } },
+                        "responses": { "200": { "content": { "application/json": {
+                            "examples": { "ok": { "value": { "id": "ds-1" } } }
+                        } } } }
+                    }
+                },
+                "/rpc/archiveWorkbook": {
+                    "post": { "summary": "Archive workbook", "tags": ["Experimental"] }
+                },
+                "/healthz": { "get": { "summary": "Health" } }
+            },
+            "components": { "schemas": {
+                "GetDatasetArgs": { "type": "object", "required": ["datasetId"] }
+            } }
+        })
+    }
+
+    #[test]
+    fn from_openapi_converts_rpc_operations() {
+        let registry = from_openapi(
+            &openapi_document(),
+            "file:///tmp/api.json",
+            "2026-10-01".to_owned(),
+            embedded_registry(),
+        )
+        .expect("document must convert");
+
+        assert_eq!(registry.openapi_version.as_deref(), Some("3.1.0"));
+        assert_eq!(registry.api_info["version"], json!("2"));
+        assert!(registry.components.schemas.contains_key("GetDatasetArgs"));
+        assert_eq!(registry.methods.len(), 2);
+
+        let get = registry.find("getDataset").unwrap();
+        assert_eq!(get.category, "read");
+        assert!(!get.experimental);
+        assert_eq!(get.typed_tool.as_deref(), Some("datalens_get_dataset"));
+        assert_eq!(get.invoke_with, "datalens_get_dataset");
+        assert_eq!(get.request_example, Some(json!({ "datasetId": "ds-1" })));
+        assert_eq!(get.response_example, Some(json!({ "id": "ds-1" })));
+
+        let archive = registry.find("archiveWorkbook").unwrap();
+        assert_eq!(archive.category, "write");
+        assert!(archive.experimental);
+        assert_eq!(archive.invoke_with, "datalens_rpc");
+        assert_eq!(archive.request_schema, json!({ "type": "object" }));
+        assert_eq!(archive.request_example, Some(json!({})));
+    }
+
+    #[test]
+    fn tool_annotations_follow_category_and_method_name() {
+        let registry = embedded_registry();
+        let hints = |method: &str| {
+            let annotations = registry.find(method).unwrap().tool_annotations();
+            (
+                annotations.read_only_hint,
+                annotations.destructive_hint,
+                annotations.idempotent_hint,
+                annotations.open_world_hint,
+            )
+        };
+
+        let read = (Some(true), Some(false), Some(true), Some(true));
+        assert_eq!(hints("getDataset"), read);
+        assert_eq!(hints("listDirectory"), read);
+        let additive = (Some(false), Some(false), Some(false), Some(true));
+        assert_eq!(hints("createDataset"), additive);
+        assert_eq!(hints("validateDataset"), additive);
+        let replacing = (Some(false), Some(true), Some(true), Some(true));
+        assert_eq!(hints("deleteWorkbook"), replacing);
+        assert_eq!(hints("updateDashboard"), replacing);
+        assert_eq!(hints("moveCollections"), replacing);
+    }
+
+    #[test]
+    fn example_from_schema_fills_placeholders() {
+        let components = json!({
+            "Field": { "type": "object", "properties": {
+                "calc_mode": { "type": "string", "enum": ["direct", "formula"] },
+                "hidden": { "type": "boolean" }
+            } }
+        });
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "datasetId": { "type": "string" },
+                "workbookId": { "type": ["null", "string"] },
+                "fields": { "type": "array", "items": { "$ref": "#/components/schemas/Field" } },
+                "page": { "type": "integer" }
+            },
+            "required": ["datasetId", "fields"]
+        });
+        let components = components.as_object().unwrap();
+
+        assert_eq!(
+            example_from_schema(&schema, components, true),
+            json!({ "datasetId": "string", "fields": [{ "calc_mode": "direct", "hidden": true }] })
+        );
+        assert_eq!(
+            example_from_schema(&schema, components, false)["page"],
+            json!(1)
+        );
+        assert_eq!(
+            example_from_schema(&schema, components, false)["workbookId"],
+            json!("string")
+        );
+    }
+
+    #[test]
+    fn registry_changes_list_added_removed_and_changed_methods() {
+        let old = from_openapi(
+            &openapi_document(),
+            "old",
+            "2026-01-01".to_owned(),
+            embedded_registry(),
+        )
+        .unwrap();
+        let mut document = openapi_document();
+        document["paths"]["/rpc/archiveWorkbook"]["post"]["tags"] = json!([]);
+        document["paths"]["/rpc/archiveWorkbook"]["post"]["x-experimental"] = json!(false);
+        document["paths"]
+            .as_object_mut()
+            .unwrap()
+            .remove("/rpc/getDataset");
+        document["paths"]["/rpc/listThings"] = json!({ "post": { "summary": "List things" } });
+        let new = from_openapi(&document, "new", "2026-02-01".to_owned(), &old).unwrap();
+
+        let changes = RegistryChanges::between(&old, &new);
+        assert_eq!(changes.added, ["listThings"]);
+        assert_eq!(changes.removed, ["getDataset"]);
+        assert_eq!(
+            changes.changed,
+            [("archiveWorkbook".to_owned(), vec!["experimental"])]
+        );
+        assert_eq!(
+            changes.render(),
+            "+ listThings\n- getDataset\n~ archiveWorkbook (experimental)\n"
+        );
+        assert!(RegistryChanges::between(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn embedded_components_are_marked_and_do_not_restrict_payloads() {
+        fn check(name: &str, node: &Value) {
+            match node {
+                Value::Object(object) => {
+                    assert!(!object.contains_key("enum"), "{name} has an enum");
+                    assert!(!object.contains_key("required"), "{name} requires fields");
+                    assert_ne!(
+                        object.get("additionalProperties"),
+                        Some(&Value::Bool(false)),
+                        "{name} forbids additional properties"
+                    );
+                    for (key, value) in object {
+                        // Property names are not keywords: `required` may be a field.
+                        match (key.as_str(), value) {
+                            ("properties", Value::Object(properties)) => {
+                                properties.values().for_each(|value| check(name, value));
+                            }
+                            _ => check(name, value),
+                        }
+                    }
+                }
+                Value::Array(items) => items.iter().for_each(|value| check(name, value)),
+                _ => {}
+            }
+        }
+
+        let components = &embedded_registry().components;
+        assert!(components.source.is_some());
+        for (name, schema) in &components.schemas {
+            assert_eq!(
+                schema["x-source"], "hand-written",
+                "{name} lacks its provenance"
+            );
+            check(name, schema);
+        }
+    }
+
+    #[test]
+    fn infer_category_reads_get_and_list_methods() {
+        assert_eq!(infer_category("getEntries"), "read");
+        assert_eq!(infer_category("listDirectory"), "read");
+        assert_eq!(infer_category("getaway"), "write");
+        assert_eq!(infer_category("validateDataset"), "write");
+        assert_eq!(infer_category("deleteWorkbook"), "write");
+    }
+
+    #[test]
+    fn load_accepts_registry_json_and_openapi_documents() {
+        let dir =
+            std::env::temp_dir().join(format!("datalens-mcp-registry-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let registry_file = dir.join("registry.json");
+        fs::write(&registry_file, EMBEDDED_REGISTRY).unwrap();
+        let registry = load(&registry_file).expect("registry JSON must load");
+        assert_eq!(registry.methods.len(), embedded_registry().methods.len());
+
+        let openapi_file = dir.join("openapi.json");
+        fs::write(&openapi_file, openapi_document().to_string()).unwrap();
+        let registry = load(&openapi_file).expect("OpenAPI document must load");
+        assert!(registry.source_url.starts_with("file://"));
+        assert!(registry.find("archiveWorkbook").is_some());
+
+        let other_file = dir.join("other.json");
+        fs::write(&other_file, r#"{"hello": "world"}"#).unwrap();
+        assert!(load(&other_file).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
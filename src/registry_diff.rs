@@ -0,0 +1,580 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs,
+    ops::Deref,
+    path::{Component, Path},
+};
+
+use anyhow::{Context, Result, bail, ensure};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    registry::{self, MethodRegistry, MethodRegistryItem},
+    schema,
+};
+
+/// Nesting depth at which schema walking stops; deeper fields are not compared.
+const MAX_SCHEMA_DEPTH: usize = 16;
+/// Largest registry file `datalens_registry_diff` reads from `DATALENS_REGISTRY_DIR`.
+pub(crate) const MAX_TOOL_REGISTRY_BYTES: u64 = 16 * 1024 * 1024;
+
+/// A registry named on the command line or in tool arguments.
+pub(crate) enum RegistrySource {
+    Static(&'static MethodRegistry),
+    Loaded(MethodRegistry),
+}
+
+impl Deref for RegistrySource {
+    type Target = MethodRegistry;
+
+    fn deref(&self) -> &MethodRegistry {
+        match self {
+            Self::Static(registry) => registry,
+            Self::Loaded(registry) => registry,
+        }
+    }
+}
+
+/// `embedded` (compiled-in snapshot), `active` (what the server uses, honoring
+/// `DATALENS_OPENAPI_PATH`), or a path to a registry JSON or OpenAPI document.
+pub(crate) fn open(spec: &str) -> Result<RegistrySource> {
+    Ok(match spec {
+        "embedded" => RegistrySource::Static(registry::embedded_registry()),
+        "active" => RegistrySource::Static(registry::method_registry()),
+        path => RegistrySource::Loaded(registry::load(Path::new(path))?),
+    })
+}
+
+/// [`open`] for MCP callers: `embedded`, `active`, or the name of a file directly in `dir`
+/// (`DATALENS_REGISTRY_DIR`) of at most [`MAX_TOOL_REGISTRY_BYTES`]. Arbitrary paths are
+/// only accepted from the command line. Reads block, so call this off the async runtime.
+pub(crate) fn open_for_tool(spec: &str, dir: Option<&Path>) -> Result<RegistrySource> {
+    if matches!(spec, "embedded" | "active") {
+        return open(spec);
+    }
+    let Some(dir) = dir else {
+        bail!(
+            "only `embedded` and `active` are available; set DATALENS_REGISTRY_DIR to compare registry files"
+        );
+    };
+    let mut components = Path::new(spec).components();
+    ensure!(
+        matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ),
+        "`{spec}` must be a file name in DATALENS_REGISTRY_DIR"
+    );
+
+    let dir = dir
+        .canonicalize()
+        .with_context(|| format!("DATALENS_REGISTRY_DIR {} is not readable", dir.display()))?;
+    let path = dir
+        .join(spec)
+        .canonicalize()
+        .with_context(|| format!("`{spec}` was not found in DATALENS_REGISTRY_DIR"))?;
+    ensure!(
+        path.starts_with(&dir),
+        "`{spec}` points outside DATALENS_REGISTRY_DIR"
+    );
+    let metadata = fs::metadata(&path)?;
+    ensure!(metadata.is_file(), "`{spec}` is not a regular file");
+    ensure!(
+        metadata.len() <= MAX_TOOL_REGISTRY_BYTES,
+        "`{spec}` is larger than {MAX_TOOL_REGISTRY_BYTES} bytes"
+    );
+    Ok(RegistrySource::Loaded(registry::load(&path)?))
+}
+
+/// API drift between two registries, focused on what breaks saved payloads and typed wrappers.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RegistryDiff {
+    pub(crate) base: RegistrySummary,
+    pub(crate) target: RegistrySummary,
+    pub(crate) added_methods: Vec<AddedMethod>,
+    pub(crate) removed_methods: Vec<String>,
+    pub(crate) changed_methods: Vec<MethodDiff>,
+    /// Removed methods plus changed methods with a breaking change.
+    pub(crate) breaking_changes: usize,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RegistrySummary {
+    pub(crate) source: String,
+    pub(crate) snapshot_date: String,
+    pub(crate) methods: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct AddedMethod {
+    pub(crate) method: String,
+    pub(crate) category: String,
+    pub(crate) experimental: bool,
+}
+
+/// Request schema fields are JSON pointers into the payload; `*` stands for any array item.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MethodDiff {
+    pub(crate) method: String,
+    /// A payload valid before may now be rejected.
+    pub(crate) breaking: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) newly_required: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) removed_properties: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) added_properties: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) enum_changes: Vec<EnumChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) experimental: Option<Flip<bool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) category: Option<Flip<String>>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct EnumChange {
+    pub(crate) path: String,
+    pub(crate) added: Vec<Value>,
+    pub(crate) removed: Vec<Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Flip<T> {
+    pub(crate) from: T,
+    pub(crate) to: T,
+}
+
+impl RegistryDiff {
+    pub(crate) fn between(
+        base_source: &str,
+        base: &MethodRegistry,
+        target_source: &str,
+        target: &MethodRegistry,
+    ) -> Self {
+        let summary = |source: &str, registry: &MethodRegistry| RegistrySummary {
+            source: source.to_owned(),
+            snapshot_date: registry.snapshot_date.clone(),
+            methods: registry.methods.len(),
+        };
+        let mut diff = Self {
+            base: summary(base_source, base),
+            target: summary(target_source, target),
+            ..Self::default()
+        };
+
+        for item in &target.methods {
+            match base.find(&item.method) {
+                Some(previous) => {
+                    let method_diff = diff_method(base, previous, target, item);
+                    if !method_diff.is_empty() {
+                        diff.changed_methods.push(method_diff);
+                    }
+                }
+                None => diff.added_methods.push(AddedMethod {
+                    method: item.method.clone(),
+                    category: item.category.clone(),
+                    experimental: item.experimental,
+                }),
+            }
+        }
+        diff.removed_methods = base
+            .methods
+            .iter()
+            .filter(|item| target.find(&item.method).is_none())
+            .map(|item| item.method.clone())
+            .collect();
+        diff.breaking_changes = diff.removed_methods.len()
+            + diff
+                .changed_methods
+                .iter()
+                .filter(|method| method.breaking)
+                .count();
+        diff
+    }
+
+    pub(crate) fn to_json(&self) -> serde_json::Map<String, Value> {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(object)) => object,
+            _ => serde_json::Map::new(),
+        }
+    }
+
+    pub(crate) fn render(&self) -> String {
+        let mut out = format!(
+            "{} ({}, {} methods) -> {} ({}, {} methods)\n",
+            self.base.source,
+            self.base.snapshot_date,
+            self.base.methods,
+            self.target.source,
+            self.target.snapshot_date,
+            self.target.methods,
+        );
+        if self.added_methods.is_empty()
+            && self.removed_methods.is_empty()
+            && self.changed_methods.is_empty()
+        {
+            out.push_str("No differences.\n");
+            return out;
+        }
+
+        for added in &self.added_methods {
+            let experimental = if added.experimental {
+                ", experimental"
+            } else {
+                ""
+            };
+            let _ = writeln!(out, "+ {} ({}{experimental})", added.method, added.category);
+        }
+        for method in &self.removed_methods {
+            let _ = writeln!(out, "- {method} (breaking)");
+        }
+        for method in &self.changed_methods {
+            let breaking = if method.breaking { " (breaking)" } else { "" };
+            let _ = writeln!(out, "~ {}{breaking}", method.method);
+            for path in &method.newly_required {
+                let _ = writeln!(out, "    newly required: {path}");
+            }
+            for path in &method.removed_properties {
+                let _ = writeln!(out, "    removed property: {path}");
+            }
+            for path in &method.added_properties {
+                let _ = writeln!(out, "    added property: {path}");
+            }
+            for change in &method.enum_changes {
+                let values = |sign: char, values: &[Value]| {
+                    values
+                        .iter()
+                        .map(|value| format!("{sign}{value}"))
+                        .collect::<Vec<_>>()
+                };
+                let mut changes = values('+', &change.added);
+                changes.extend(values('-', &change.removed));
+                let _ = writeln!(out, "    enum {}: {}", change.path, changes.join(" "));
+            }
+            if let Some(flip) = &method.experimental {
+                let _ = writeln!(out, "    experimental: {} -> {}", flip.from, flip.to);
+            }
+            if let Some(flip) = &method.category {
+                let _ = writeln!(out, "    category: {} -> {}", flip.from, flip.to);
+            }
+        }
+        let _ = writeln!(out, "{} breaking change(s)", self.breaking_changes);
+        out
+    }
+}
+
+impl MethodDiff {
+    fn is_empty(&self) -> bool {
+        self.newly_required.is_empty()
+            && self.removed_properties.is_empty()
+            && self.added_properties.is_empty()
+            && self.enum_changes.is_empty()
+            && self.experimental.is_none()
+            && self.category.is_none()
+    }
+}
+
+fn diff_method(
+    base: &MethodRegistry,
+    previous: &MethodRegistryItem,
+    target: &MethodRegistry,
+    item: &MethodRegistryItem,
+) -> MethodDiff {
+    let old = fields(&schema::resolve_refs(
+        &previous.request_schema,
+        &base.components.schemas,
+    ));
+    let new = fields(&schema::resolve_refs(
+        &item.request_schema,
+        &target.components.schemas,
+    ));
+
+    let mut diff = MethodDiff {
+        method: item.method.clone(),
+        ..MethodDiff::default()
+    };
+    for (path, field) in &new {
+        match old.get(path) {
+            None => {
+                if field.required && !is_nested_in(path, &new, &old) {
+                    diff.newly_required.push(path.clone());
+                } else if !is_nested_in(path, &new, &old) {
+                    diff.added_properties.push(path.clone());
+                }
+            }
+            Some(previous_field) => {
+                if field.required && !previous_field.required {
+                    diff.newly_required.push(path.clone());
+                }
+                if let (Some(before), Some(after)) =
+                    (&previous_field.enum_values, &field.enum_values)
+                {
+                    let added: Vec<Value> = after
+                        .iter()
+                        .filter(|value| !before.contains(value))
+                        .cloned()
+                        .collect();
+                    let removed: Vec<Value> = before
+                        .iter()
+                        .filter(|value| !after.contains(value))
+                        .cloned()
+                        .collect();
+                    if !added.is_empty() || !removed.is_empty() {
+                        diff.enum_changes.push(EnumChange {
+                            path: path.clone(),
+                            added,
+                            removed,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    diff.removed_properties = old
+        .keys()
+        .filter(|path| !new.contains_key(*path) && !is_nested_in(path, &old, &new))
+        .cloned()
+        .collect();
+
+    if previous.experimental != item.experimental {
+        diff.experimental = Some(Flip {
+            from: previous.experimental,
+            to: item.experimental,
+        });
+    }
+    if previous.category != item.category {
+        diff.category = Some(Flip {
+            from: previous.category.clone(),
+            to: item.category.clone(),
+        });
+    }
+    diff.breaking = !diff.newly_required.is_empty()
+        || !diff.removed_properties.is_empty()
+        || diff
+            .enum_changes
+            .iter()
+            .any(|change| !change.removed.is_empty());
+    diff
+}
+
+/// Whether `path` sits under a parent that exists in `here` but not in `there`, so only the
+/// parent is reported.
+fn is_nested_in(
+    path: &str,
+    here: &BTreeMap<String, Field>,
+    there: &BTreeMap<String, Field>,
+) -> bool {
+    let mut parent = path;
+    while let Some((prefix, _)) = parent.rsplit_once('/') {
+        if prefix.is_empty() {
+            return false;
+        }
+        if here.contains_key(prefix) && !there.contains_key(prefix) {
+            return true;
+        }
+        parent = prefix;
+    }
+    false
+}
+
+#[derive(Debug, Default)]
+struct Field {
+    required: bool,
+    enum_values: Option<Vec<Value>>,
+}
+
+/// Flattens a dereferenced schema into payload fields keyed by JSON pointer.
+fn fields(schema: &Value) -> BTreeMap<String, Field> {
+    let mut fields = BTreeMap::new();
+    collect(schema, "", true, 0, &mut fields);
+    fields
+}
+
+fn collect(
+    schema: &Value,
+    path: &str,
+    count_required: bool,
+    depth: usize,
+    fields: &mut BTreeMap<String, Field>,
+) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+    if depth > MAX_SCHEMA_DEPTH {
+        return;
+    }
+
+    if let Some(values) = schema.get("enum").and_then(Value::as_array)
+        && let Some(field) = fields.get_mut(path)
+    {
+        field.enum_values = Some(values.clone());
+    }
+
+    if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+        let required: Vec<&str> = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|names| names.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        for (name, property) in properties {
+            let child = format!("{path}/{}", name.replace('~', "~0").replace('/', "~1"));
+            let field = fields.entry(child.clone()).or_default();
+            field.required |= count_required && required.contains(&name.as_str());
+            collect(property, &child, count_required, depth + 1, fields);
+        }
+    }
+    if let Some(items) = schema.get("items") {
+        let child = format!("{path}/*");
+        fields.entry(child.clone()).or_default();
+        collect(items, &child, count_required, depth + 1, fields);
+    }
+
+    if let Some(branches) = schema.get("allOf").and_then(Value::as_array) {
+        for branch in branches {
+            collect(branch, path, count_required, depth + 1, fields);
+        }
+    }
+    // Only one variant applies to a payload, so variant-level `required` is not tracked.
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(branches) = schema.get(keyword).and_then(Value::as_array) {
+            for branch in branches {
+                collect(branch, path, false, depth + 1, fields);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn registry(methods: Value) -> MethodRegistry {
+        serde_json::from_value(json!({
+            "snapshotDate": "2026-02-18",
+            "sourceUrl": "test",
+            "openapiVersion": "3.1.0",
+            "apiInfo": {},
+            "components": { "schemas": {
+                "Mode": { "type": "string", "enum": ["save", "publish"] }
+            } },
+            "methods": methods
+        }))
+        .unwrap()
+    }
+
+    fn method(name: &str, experimental: bool, schema: Value) -> Value {
+        json!({
+            "method": name,
+            "category": "write",
+            "experimental": experimental,
+            "typedTool": null,
+            "invokeWith": "datalens_rpc",
+            "summary": null,
+            "description": null,
+            "requestSchema": schema
+        })
+    }
+
+    #[test]
+    fn diff_reports_breaking_schema_changes_and_flips() {
+        let base = registry(json!([
+            method(
+                "updateChart",
+                false,
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "mode": { "$ref": "#/components/schemas/Mode" },
+                        "entry": { "type": "object", "properties": {
+                            "entryId": { "type": "string" },
+                            "legacy": { "type": "object", "properties": { "x": { "type": "string" } } }
+                        } }
+                    },
+                    "required": ["mode"]
+                })
+            ),
+            method("deleteChart", false, json!({ "type": "object" })),
+        ]));
+        let target = registry(json!([
+            method(
+                "updateChart",
+                true,
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "mode": { "type": "string", "enum": ["save", "draft"] },
+                        "entry": { "type": "object", "properties": {
+                            "entryId": { "type": "string" },
+                            "revId": { "type": "string" }
+                        }, "required": ["entryId", "revId"] }
+                    },
+                    "required": ["mode"]
+                })
+            ),
+            method("archiveChart", false, json!({ "type": "object" })),
+        ]));
+
+        let diff = RegistryDiff::between("base", &base, "target", &target);
+        assert_eq!(diff.added_methods.len(), 1);
+        assert_eq!(diff.added_methods[0].method, "archiveChart");
+        assert_eq!(diff.removed_methods, ["deleteChart"]);
+        assert_eq!(diff.breaking_changes, 2);
+
+        let changed = &diff.changed_methods[0];
+        assert!(changed.breaking);
+        assert_eq!(changed.newly_required, ["/entry/entryId", "/entry/revId"]);
+        assert_eq!(changed.removed_properties, ["/entry/legacy"]);
+        assert_eq!(changed.enum_changes[0].path, "/mode");
+        assert_eq!(changed.enum_changes[0].added, [json!("draft")]);
+        assert_eq!(changed.enum_changes[0].removed, [json!("publish")]);
+        assert!(changed.experimental.as_ref().is_some_and(|flip| flip.to));
+
+        let text = diff.render();
+        assert!(text.contains("- deleteChart (breaking)"));
+        assert!(text.contains("    enum /mode: +\"draft\" -\"publish\""));
+        assert!(text.contains("    experimental: false -> true"));
+    }
+
+    #[test]
+    fn diff_of_identical_registries_is_empty() {
+        let embedded = registry::embedded_registry();
+        let diff = RegistryDiff::between("embedded", embedded, "embedded", embedded);
+        assert!(diff.changed_methods.is_empty());
+        assert_eq!(diff.breaking_changes, 0);
+        assert!(diff.render().ends_with("No differences.\n"));
+    }
+
+    #[test]
+    fn tool_reads_only_small_files_in_the_registry_dir() {
+        let dir =
+            std::env::temp_dir().join(format!("datalens-mcp-registry-dir-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::File::create(dir.join("huge.json"))
+            .unwrap()
+            .set_len(MAX_TOOL_REGISTRY_BYTES + 1)
+            .unwrap();
+
+        let error = |spec: &str| match open_for_tool(spec, Some(&dir)) {
+            Ok(_) => panic!("{spec} must be rejected"),
+            Err(err) => err.to_string(),
+        };
+        assert!(error("huge.json").contains("larger than"));
+        assert!(error("nested").contains("not a regular file"));
+        assert!(error("nested/huge.json").contains("file name"));
+        assert!(open_for_tool("embedded", None).is_ok());
+        assert!(
+            open_for_tool("huge.json", None)
+                .is_err_and(|err| err.to_string().contains("DATALENS_REGISTRY_DIR"))
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,487 @@
+//! DataLens objects as MCP resources with `datalens://` URIs.
+
+use std::collections::VecDeque;
+
+use rmcp::model::{AnnotateAble, RawResource, RawResourceTemplate, Resource, ResourceTemplate};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::responses::{self, Entry, GetEntriesResponse, ListDirectoryResponse};
+
+const SCHEME: &str = "datalens://";
+pub(crate) const MIME_TYPE: &str = "application/json";
+/// Items requested per `listDirectory`, `getCollectionContent` or `getWorkbookEntries` page.
+const LIST_PAGE_SIZE: u64 = 100;
+
+/// How a chart was built; each kind has its own get method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ChartKind {
+    Wizard,
+    Ql,
+    Editor,
+}
+
+impl ChartKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Wizard => "wizard",
+            Self::Ql => "ql",
+            Self::Editor => "editor",
+        }
+    }
+
+    fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "wizard" => Some(Self::Wizard),
+            "ql" => Some(Self::Ql),
+            "editor" => Some(Self::Editor),
+            _ => None,
+        }
+    }
+
+    /// Chart entry types look like `graph_wizard_node` or `table_ql_node`; other widget
+    /// types (`graph_node`, `markdown_node`, ...) are Editor charts.
+    fn from_entry_type(kind: &str) -> Self {
+        if kind.contains("wizard") {
+            Self::Wizard
+        } else if kind.contains("ql") {
+            Self::Ql
+        } else {
+            Self::Editor
+        }
+    }
+
+    fn method(self) -> &'static str {
+        match self {
+            Self::Wizard => "getWizardChart",
+            Self::Ql => "getQLChart",
+            Self::Editor => "getEditorChart",
+        }
+    }
+}
+
+/// A DataLens object addressed by a `datalens://` URI.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ResourceRef {
+    Dataset(String),
+    Dashboard(String),
+    Chart(ChartKind, String),
+    Workbook(String),
+    Connection(String),
+}
+
+impl ResourceRef {
+    pub(crate) fn parse(uri: &str) -> Result<Self, String> {
+        let path = uri
+            .strip_prefix(SCHEME)
+            .ok_or_else(|| format!("resource URIs start with `{SCHEME}`"))?;
+        let parts: Vec<&str> = path.split('/').collect();
+        if parts.iter().any(|part| part.is_empty()) {
+            return Err(format!("malformed resource URI `{uri}`"));
+        }
+        match parts.as_slice() {
+            ["dataset", id] => Ok(Self::Dataset((*id).to_owned())),
+            ["dashboard", id] => Ok(Self::Dashboard((*id).to_owned())),
+            ["workbook", id] => Ok(Self::Workbook((*id).to_owned())),
+            ["connection", id] => Ok(Self::Connection((*id).to_owned())),
+            ["chart", kind, id] => ChartKind::parse(kind)
+                .map(|kind| Self::Chart(kind, (*id).to_owned()))
+                .ok_or_else(|| {
+                    format!("unknown chart type `{kind}`; expected wizard, ql or editor")
+                }),
+            _ => Err(format!("unknown resource URI `{uri}`")),
+        }
+    }
+
+    pub(crate) fn uri(&self) -> String {
+        match self {
+            Self::Dataset(id) => format!("{SCHEME}dataset/{id}"),
+            Self::Dashboard(id) => format!("{SCHEME}dashboard/{id}"),
+            Self::Chart(kind, id) => format!("{SCHEME}chart/{}/{id}", kind.as_str()),
+            Self::Workbook(id) => format!("{SCHEME}workbook/{id}"),
+            Self::Connection(id) => format!("{SCHEME}connection/{id}"),
+        }
+    }
+
+    /// The get call that reads the object.
+    pub(crate) fn request(&self) -> (&'static str, Value) {
+        match self {
+            Self::Dataset(id) => ("getDataset", json!({ "datasetId": id })),
+            Self::Dashboard(id) => ("getDashboard", json!({ "dashboardId": id })),
+            Self::Chart(kind, id) => (kind.method(), json!({ "chartId": id })),
+            Self::Workbook(id) => ("getWorkbook", json!({ "workbookId": id })),
+            Self::Connection(id) => ("getConnection", json!({ "connectionId": id })),
+        }
+    }
+
+    /// Folders and other scopes are not resources.
+    pub(crate) fn from_entry(entry: &Entry) -> Option<Self> {
+        let id = entry.entry_id.clone()?;
+        match entry.scope.as_deref()? {
+            "dataset" => Some(Self::Dataset(id)),
+            "dash" => Some(Self::Dashboard(id)),
+            "connection" => Some(Self::Connection(id)),
+            "widget" => Some(Self::Chart(
+                ChartKind::from_entry_type(entry.kind.as_deref().unwrap_or_default()),
+                id,
+            )),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn templates() -> Vec<ResourceTemplate> {
+    [
+        (
+            "datalens://dataset/{id}",
+            "dataset",
+            "DataLens dataset (getDataset): sources and result_schema fields.",
+        ),
+        (
+            "datalens://dashboard/{id}",
+            "dashboard",
+            "DataLens dashboard (getDashboard): tabs, widgets and selectors.",
+        ),
+        (
+            "datalens://chart/{type}/{id}",
+            "chart",
+            "DataLens chart; type is wizard (getWizardChart), ql (getQLChart) or editor (getEditorChart).",
+        ),
+        (
+            "datalens://workbook/{id}",
+            "workbook",
+            "DataLens workbook (getWorkbook).",
+        ),
+        (
+            "datalens://connection/{id}",
+            "connection",
+            "DataLens connection (getConnection).",
+        ),
+    ]
+    .into_iter()
+    .map(|(uri_template, name, description)| {
+        RawResourceTemplate {
+            uri_template: uri_template.to_owned(),
+            name: name.to_owned(),
+            title: None,
+            description: Some(description.to_owned()),
+            mime_type: Some(MIME_TYPE.to_owned()),
+            icons: None,
+        }
+        .no_annotation()
+    })
+    .collect()
+}
+
+fn resource(target: &ResourceRef, name: String, description: Option<String>) -> Resource {
+    RawResource {
+        description,
+        mime_type: Some(MIME_TYPE.to_owned()),
+        ..RawResource::new(target.uri(), name)
+    }
+    .no_annotation()
+}
+
+/// One page of a listing source.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "source", rename_all = "camelCase")]
+pub(crate) enum Source {
+    Directory {
+        path: String,
+        page: u64,
+    },
+    Collection {
+        id: Option<String>,
+        page: Option<String>,
+    },
+    Workbook {
+        id: String,
+        page: u64,
+    },
+}
+
+impl Source {
+    pub(crate) fn request(&self) -> (&'static str, Value) {
+        match self {
+            Self::Directory { path, page } => (
+                "listDirectory",
+                json!({ "path": path, "page": page, "pageSize": LIST_PAGE_SIZE }),
+            ),
+            Self::Collection { id, page } => (
+                "getCollectionContent",
+                json!({ "collectionId": id, "page": page, "pageSize": LIST_PAGE_SIZE }),
+            ),
+            Self::Workbook { id, page } => (
+                "getWorkbookEntries",
+                json!({ "workbookId": id, "page": page, "pageSize": LIST_PAGE_SIZE }),
+            ),
+        }
+    }
+}
+
+/// A breadth-first walk over folders (`listDirectory`) and collections and their workbooks
+/// (`getCollectionContent`, `getWorkbookEntries`), one page per `resources/list` call. The
+/// pages still to fetch travel in the cursor.
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Crawl {
+    queue: VecDeque<Source>,
+}
+
+impl Crawl {
+    pub(crate) fn start() -> Self {
+        Self {
+            queue: VecDeque::from([
+                Source::Directory {
+                    path: "/".to_owned(),
+                    page: 0,
+                },
+                Source::Collection {
+                    id: None,
+                    page: None,
+                },
+            ]),
+        }
+    }
+
+    pub(crate) fn from_cursor(cursor: &str) -> Result<Self, String> {
+        serde_json::from_str(cursor).map_err(|error| format!("invalid resources cursor: {error}"))
+    }
+
+    /// `None` once every page has been listed.
+    pub(crate) fn cursor(&self) -> Option<String> {
+        (!self.queue.is_empty()).then(|| serde_json::to_string(self).unwrap_or_default())
+    }
+
+    pub(crate) fn next_source(&mut self) -> Option<Source> {
+        self.queue.pop_front()
+    }
+
+    /// Turns a fetched page into resources and queues its next page and child sources.
+    pub(crate) fn absorb(&mut self, source: Source, response: Value) -> Vec<Resource> {
+        let Value::Object(response) = response else {
+            return Vec::new();
+        };
+        match source {
+            Source::Directory { path, page } => {
+                let listing: ListDirectoryResponse =
+                    responses::from_object(response).unwrap_or_default();
+                if has_next_page(listing.next_page_token.as_ref()) {
+                    self.queue.push_front(Source::Directory {
+                        path,
+                        page: page + 1,
+                    });
+                }
+                let entries = listing.entries.unwrap_or_default();
+                for entry in &entries {
+                    if entry.scope.as_deref() == Some("folder")
+                        && let Some(key) = &entry.key
+                    {
+                        self.queue.push_back(Source::Directory {
+                            path: key.clone(),
+                            page: 0,
+                        });
+                    }
+                }
+                entry_resources(&entries)
+            }
+            Source::Workbook { id, page } => {
+                let listing: GetEntriesResponse =
+                    responses::from_object(response).unwrap_or_default();
+                if has_next_page(listing.next_page_token.as_ref()) {
+                    self.queue
+                        .push_front(Source::Workbook { id, page: page + 1 });
+                }
+                entry_resources(&listing.entries.unwrap_or_default())
+            }
+            Source::Collection { id, .. } => {
+                if let Some(Value::String(token)) = response.get("nextPageToken")
+                    && !token.is_empty()
+                {
+                    self.queue.push_front(Source::Collection {
+                        id,
+                        page: Some(token.clone()),
+                    });
+                }
+                let items = response
+                    .get("items")
+                    .and_then(Value::as_array)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                let mut resources = Vec::new();
+                for item in items {
+                    let text = |key: &str| item.get(key).and_then(Value::as_str);
+                    match (text("entity"), text("workbookId"), text("collectionId")) {
+                        (Some("workbook"), Some(workbook_id), _) => {
+                            self.queue.push_back(Source::Workbook {
+                                id: workbook_id.to_owned(),
+                                page: 0,
+                            });
+                            resources.push(resource(
+                                &ResourceRef::Workbook(workbook_id.to_owned()),
+                                text("title").unwrap_or(workbook_id).to_owned(),
+                                text("description")
+                                    .filter(|description| !description.is_empty())
+                                    .map(str::to_owned),
+                            ));
+                        }
+                        (Some("collection"), _, Some(collection_id)) => {
+                            self.queue.push_back(Source::Collection {
+                                id: Some(collection_id.to_owned()),
+                                page: None,
+                            });
+                        }
+                        _ => {}
+                    }
+                }
+                resources
+            }
+        }
+    }
+}
+
+/// `listDirectory` reports more pages with `nextPageToken: true`, other methods with a token.
+fn has_next_page(token: Option<&Value>) -> bool {
+    match token {
+        Some(Value::Bool(more)) => *more,
+        Some(Value::String(token)) => !token.is_empty(),
+        Some(Value::Number(_)) => true,
+        _ => false,
+    }
+}
+
+fn entry_resources(entries: &[Entry]) -> Vec<Resource> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let target = ResourceRef::from_entry(entry)?;
+            let key = entry.key.as_deref().unwrap_or_default();
+            let name = entry
+                .name
+                .clone()
+                .or_else(|| {
+                    key.rsplit('/')
+                        .find(|part| !part.is_empty())
+                        .map(str::to_owned)
+                })
+                .unwrap_or_else(|| target.uri());
+            let description = Some(key)
+                .filter(|key| key.contains('/'))
+                .map(|key| format!("/{}", key.trim_start_matches('/')));
+            Some(resource(&target, name, description))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uris_round_trip_and_map_to_get_calls() {
+        for uri in [
+            "datalens://dataset/ds-1",
+            "datalens://dashboard/d-1",
+            "datalens://chart/wizard/c-1",
+            "datalens://chart/ql/c-2",
+            "datalens://chart/editor/c-3",
+            "datalens://workbook/wb-1",
+            "datalens://connection/conn-1",
+        ] {
+            assert_eq!(ResourceRef::parse(uri).unwrap().uri(), uri);
+        }
+
+        let chart = ResourceRef::parse("datalens://chart/ql/c-2").unwrap();
+        assert_eq!(chart.request(), ("getQLChart", json!({"chartId": "c-2"})));
+
+        for uri in [
+            "https://dataset/ds-1",
+            "datalens://dataset/",
+            "datalens://dataset/ds-1/extra",
+            "datalens://chart/d3/c-1",
+            "datalens://folder/f-1",
+        ] {
+            assert!(ResourceRef::parse(uri).is_err(), "{uri}");
+        }
+    }
+
+    #[test]
+    fn crawl_pages_through_folders_collections_and_workbooks() {
+        let mut crawl = Crawl::start();
+
+        let source = crawl.next_source().unwrap();
+        assert_eq!(source.request().0, "listDirectory");
+        let resources = crawl.absorb(
+            source,
+            json!({
+                "nextPageToken": true,
+                "entries": [
+                    {"entryId": "f-1", "scope": "folder", "key": "reports/"},
+                    {"entryId": "ds-1", "scope": "dataset", "key": "sales"},
+                    {"entryId": "c-1", "scope": "widget", "type": "graph_wizard_node", "key": "reports/revenue"}
+                ]
+            }),
+        );
+        let uris: Vec<&str> = resources.iter().map(|r| r.uri.as_str()).collect();
+        assert_eq!(
+            uris,
+            ["datalens://dataset/ds-1", "datalens://chart/wizard/c-1"]
+        );
+        assert_eq!(resources[1].name, "revenue");
+
+        // The cursor survives a round trip and keeps the next page first.
+        let mut crawl = Crawl::from_cursor(&crawl.cursor().unwrap()).unwrap();
+        assert_eq!(
+            crawl.next_source(),
+            Some(Source::Directory {
+                path: "/".to_owned(),
+                page: 1
+            })
+        );
+        crawl.absorb(
+            Source::Directory {
+                path: "/".to_owned(),
+                page: 1,
+            },
+            json!({"entries": []}),
+        );
+
+        let collection = crawl.next_source().unwrap();
+        assert_eq!(
+            collection.request(),
+            (
+                "getCollectionContent",
+                json!({"collectionId": null, "page": null, "pageSize": 100})
+            )
+        );
+        let resources = crawl.absorb(
+            collection,
+            json!({"items": [
+                {"entity": "workbook", "workbookId": "wb-1", "title": "Sales"},
+                {"entity": "collection", "collectionId": "col-1", "title": "Team"}
+            ]}),
+        );
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0].uri, "datalens://workbook/wb-1");
+        assert_eq!(resources[0].name, "Sales");
+
+        let rest: Vec<Source> = std::iter::from_fn(|| crawl.next_source()).collect();
+        assert_eq!(
+            rest,
+            [
+                Source::Directory {
+                    path: "reports/".to_owned(),
+                    page: 0
+                },
+                Source::Workbook {
+                    id: "wb-1".to_owned(),
+                    page: 0
+                },
+                Source::Collection {
+                    id: Some("col-1".to_owned()),
+                    page: None
+                },
+            ]
+        );
+        assert_eq!(crawl.cursor(), None);
+    }
+}
@@ -0,0 +1,398 @@
+//! Response types of the typed tools for the most used read methods.
+//!
+//! Every field is optional and a value of an unexpected type is kept in `extra` under its own
+//! key rather than failing the call, so the types survive DataLens API changes. Fields not
+//! listed here are kept in `extra` too, and the client gets back exactly what DataLens sent.
+
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Deserializer, Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value, json};
+
+/// Values that can be read from a response field without failing on a type mismatch.
+pub(crate) trait Lenient: Sized {
+    fn from_value(value: Value) -> Option<Self>;
+}
+
+impl Lenient for String {
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl Lenient for bool {
+    fn from_value(value: Value) -> Option<Self> {
+        value.as_bool()
+    }
+}
+
+impl Lenient for Value {
+    fn from_value(value: Value) -> Option<Self> {
+        (!value.is_null()).then_some(value)
+    }
+}
+
+impl Lenient for Map<String, Value> {
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Object(object) => Some(object),
+            _ => None,
+        }
+    }
+}
+
+/// A list with an item that does not fit is not read at all, so it is kept whole.
+impl<T: Lenient> Lenient for Vec<T> {
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Array(items) => items.into_iter().map(T::from_value).collect(),
+            _ => None,
+        }
+    }
+}
+
+/// A response type; see [`from_object`].
+pub(crate) trait ResponseType: Serialize + DeserializeOwned {
+    fn extra_mut(&mut self) -> &mut Map<String, Value>;
+}
+
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Lenient,
+{
+    Value::deserialize(deserializer).map(T::from_value)
+}
+
+/// Declares a response type: each field becomes an `Option` read with [`Lenient`] and left
+/// out of the output when unset, and unknown fields are collected in `extra`. The output
+/// schema accepts any value for a field, since a mistyped one is returned as it came.
+macro_rules! response_type {
+    (
+        $(#[$meta:meta])*
+        struct $name:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $field:ident: $ty:ty,
+            )*
+        }
+    ) => {
+        #[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+        #[schemars(transform = accept_any_value)]
+        $(#[$meta])*
+        pub(crate) struct $name {
+            $(
+                $(#[$field_meta])*
+                #[serde(default, deserialize_with = "lenient")]
+                #[serde(skip_serializing_if = "Option::is_none")]
+                pub(crate) $field: Option<$ty>,
+            )*
+            #[serde(flatten)]
+            pub(crate) extra: Map<String, Value>,
+        }
+
+        impl ResponseType for $name {
+            fn extra_mut(&mut self) -> &mut Map<String, Value> {
+                &mut self.extra
+            }
+        }
+
+        impl Lenient for $name {
+            fn from_value(value: Value) -> Option<Self> {
+                match value {
+                    Value::Object(object) => from_object(object).ok(),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+response_type! {
+    /// `getDataset`.
+    struct DatasetResponse {
+        id: String,
+        key: String,
+        workbook_id: String,
+        dataset: Dataset,
+        options: Value,
+    }
+}
+
+response_type! {
+    struct Dataset {
+        /// Dataset fields: dimensions and measures.
+        result_schema: Vec<DatasetField>,
+        sources: Vec<Value>,
+        source_avatars: Vec<Value>,
+        avatar_relations: Vec<Value>,
+        revision_id: String,
+        description: String,
+    }
+}
+
+response_type! {
+    struct DatasetField {
+        guid: String,
+        title: String,
+        /// `DIMENSION` or `MEASURE`.
+        #[serde(rename = "type")]
+        kind: String,
+        data_type: String,
+        cast: String,
+        aggregation: String,
+        /// `direct`, `formula` or `parameter`.
+        calc_mode: String,
+        /// Source column of a `direct` field.
+        source: String,
+        formula: String,
+        hidden: bool,
+        description: String,
+    }
+}
+
+response_type! {
+    /// `getConnection`. Connector-specific settings are in `extra`.
+    struct ConnectionResponse {
+        id: String,
+        key: String,
+        name: String,
+        /// Connector type, e.g. `clickhouse` or `postgres`.
+        db_type: String,
+        workbook_id: String,
+        created_at: String,
+        updated_at: String,
+    }
+}
+
+response_type! {
+    /// `getDashboard`.
+    struct DashboardResponse {
+        entry: DashboardEntry,
+    }
+}
+
+response_type! {
+    #[serde(rename_all = "camelCase")]
+    struct DashboardEntry {
+        entry_id: String,
+        key: String,
+        scope: String,
+        workbook_id: String,
+        rev_id: String,
+        saved_id: String,
+        published_id: String,
+        created_by: String,
+        created_at: String,
+        updated_by: String,
+        updated_at: String,
+        meta: Value,
+        data: DashboardData,
+    }
+}
+
+response_type! {
+    struct DashboardData {
+        tabs: Vec<DashboardTab>,
+        settings: Value,
+    }
+}
+
+response_type! {
+    struct DashboardTab {
+        id: String,
+        title: String,
+        items: Vec<DashboardItem>,
+    }
+}
+
+response_type! {
+    /// A widget, selector, title or text block on a dashboard tab.
+    struct DashboardItem {
+        id: String,
+        /// `widget`, `control`, `group_control`, `title` or `text`.
+        #[serde(rename = "type")]
+        kind: String,
+        data: Value,
+    }
+}
+
+response_type! {
+    /// `getWorkbook`.
+    #[serde(rename_all = "camelCase")]
+    struct WorkbookResponse {
+        workbook_id: String,
+        collection_id: String,
+        title: String,
+        description: String,
+        created_by: String,
+        created_at: String,
+        updated_by: String,
+        updated_at: String,
+        /// Present with `include_permissions_info`.
+        permissions: Map<String, Value>,
+    }
+}
+
+response_type! {
+    /// `listDirectory`.
+    #[serde(rename_all = "camelCase")]
+    struct ListDirectoryResponse {
+        entries: Vec<Entry>,
+        #[serde(rename = "breadCrumbs")]
+        bread_crumbs: Vec<Value>,
+        next_page_token: Value,
+    }
+}
+
+response_type! {
+    /// `getEntries`.
+    #[serde(rename_all = "camelCase")]
+    struct GetEntriesResponse {
+        entries: Vec<Entry>,
+        next_page_token: Value,
+    }
+}
+
+response_type! {
+    /// A navigation entry: a folder, connection, dataset, chart or dashboard.
+    #[serde(rename_all = "camelCase")]
+    struct Entry {
+        entry_id: String,
+        /// Path of the entry; for workbook entries, its name.
+        key: String,
+        /// `folder`, `connection`, `dataset`, `widget` (charts) or `dash`.
+        scope: String,
+        /// Kind within the scope, e.g. `graph_wizard_node` or `ql_chart`.
+        #[serde(rename = "type")]
+        kind: String,
+        name: String,
+        workbook_id: String,
+        collection_id: String,
+        saved_id: String,
+        published_id: String,
+        created_by: String,
+        created_at: String,
+        updated_by: String,
+        updated_at: String,
+        hidden: bool,
+        is_locked: bool,
+        is_favorite: bool,
+        meta: Value,
+    }
+}
+
+/// Reads a raw response object as `T`. Values that a typed field could not take are put in
+/// `extra` under their own key, so serializing `T` gives back the same object.
+pub(crate) fn from_object<T: ResponseType>(response: Map<String, Value>) -> serde_json::Result<T> {
+    let mut parsed: T = serde_json::from_value(Value::Object(response.clone()))?;
+    let Value::Object(kept) = serde_json::to_value(&parsed)? else {
+        return Ok(parsed);
+    };
+    for (key, value) in response {
+        if !kept.contains_key(&key) {
+            parsed.extra_mut().insert(key, value);
+        }
+    }
+    Ok(parsed)
+}
+
+/// Lets each property of a response schema also take a value of any other type.
+fn accept_any_value(schema: &mut Schema) {
+    let Some(Value::Object(properties)) = schema.get_mut("properties") else {
+        return;
+    };
+    for property in properties.values_mut() {
+        let typed = property.take();
+        *property = json!({ "anyOf": [typed, {}] });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn parse<T: ResponseType>(raw: &Value) -> T {
+        from_object(raw.as_object().unwrap().clone()).unwrap()
+    }
+
+    #[test]
+    fn dataset_response_keeps_unknown_and_mistyped_fields() {
+        let raw = json!({
+            "id": "ds-1",
+            "workbook_id": 7,
+            "is_favorite": false,
+            "options": null,
+            "dataset": {
+                "result_schema": [
+                    {"guid": "f-1", "title": "Revenue", "type": "MEASURE", "calc_mode": "formula", "formula": "SUM([amount])", "strict": true, "hidden": "no"}
+                ],
+                "rls": {}
+            }
+        });
+        let response: DatasetResponse = parse(&raw);
+
+        assert_eq!(response.id.as_deref(), Some("ds-1"));
+        assert_eq!(response.workbook_id, None);
+        assert_eq!(response.extra["workbook_id"], json!(7));
+        assert_eq!(response.extra["is_favorite"], json!(false));
+        assert_eq!(response.extra["options"], Value::Null);
+        let dataset = response.dataset.as_ref().unwrap();
+        let fields = dataset.result_schema.as_ref().unwrap();
+        assert_eq!(fields[0].kind.as_deref(), Some("MEASURE"));
+        assert_eq!(fields[0].hidden, None);
+        assert_eq!(fields[0].extra["hidden"], json!("no"));
+        assert_eq!(fields[0].extra["strict"], json!(true));
+        assert_eq!(dataset.extra["rls"], json!({}));
+
+        assert_eq!(serde_json::to_value(&response).unwrap(), raw);
+    }
+
+    #[test]
+    fn entry_responses_round_trip_camel_case_fields() {
+        let raw = json!({
+            "entries": [{"entryId": "e-1", "scope": "dash", "type": "", "workbookId": "wb-1", "isLocked": false, "permissions": {"read": true}}],
+            "breadCrumbs": [{"title": "root"}],
+            "nextPageToken": "2"
+        });
+        let response: ListDirectoryResponse = parse(&raw);
+        let entry = &response.entries.as_ref().unwrap()[0];
+        assert_eq!(entry.entry_id.as_deref(), Some("e-1"));
+        assert_eq!(entry.workbook_id.as_deref(), Some("wb-1"));
+        assert_eq!(entry.extra["permissions"], json!({"read": true}));
+        assert_eq!(serde_json::to_value(&response).unwrap(), raw);
+
+        let raw = json!({"entries": [{"entryId": "e-1"}, "not an entry"], "nextPageToken": null});
+        let response: GetEntriesResponse = parse(&raw);
+        assert!(response.entries.is_none());
+        assert_eq!(response.extra["entries"], raw["entries"]);
+        assert_eq!(serde_json::to_value(&response).unwrap(), raw);
+
+        let raw = json!({"entry": {
+            "entryId": "d-1",
+            "revId": "r-2",
+            "data": {"tabs": [{"id": "t-1", "items": [{"id": "i-1", "type": "widget", "data": {"tabs": []}}]}]}
+        }});
+        let response: DashboardResponse = parse(&raw);
+        let entry = response.entry.as_ref().unwrap();
+        assert_eq!(entry.rev_id.as_deref(), Some("r-2"));
+        let tabs = entry.data.as_ref().unwrap().tabs.as_ref().unwrap();
+        let items = tabs[0].items.as_ref().unwrap();
+        assert_eq!(items[0].kind.as_deref(), Some("widget"));
+        assert_eq!(serde_json::to_value(&response).unwrap(), raw);
+    }
+
+    #[test]
+    fn output_schemas_are_objects_that_allow_unknown_fields() {
+        let schema = serde_json::to_value(schemars::schema_for!(WorkbookResponse)).unwrap();
+        assert_eq!(schema["type"], json!("object"));
+        let workbook_id = &schema["properties"]["workbookId"]["anyOf"];
+        assert_eq!(workbook_id[0]["type"], json!(["string", "null"]));
+        assert_eq!(workbook_id[1], json!({}));
+        assert_ne!(schema["additionalProperties"], json!(false));
+    }
+}
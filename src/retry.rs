@@ -0,0 +1,210 @@
+use std::{
+    collections::hash_map::RandomState,
+    error::Error as _,
+    hash::{BuildHasher, Hasher},
+    io,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use reqwest::{
+    StatusCode,
+    header::{HeaderMap, RETRY_AFTER},
+};
+
+use crate::{env_flag, parse_non_negative};
+
+const DEFAULT_MAX_RETRIES: u64 = 3;
+const DEFAULT_BASE_DELAY_MS: u64 = 200;
+const DEFAULT_MAX_DELAY_MS: u64 = 5_000;
+
+/// When and how `call_rpc` retries transient DataLens failures.
+///
+/// Only methods whose registry category is `read` are retried unless `retry_writes` is set;
+/// the whole sequence of attempts still fits in the profile timeout.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct RetryPolicy {
+    pub(crate) max_retries: u32,
+    pub(crate) base_delay: Duration,
+    pub(crate) max_delay: Duration,
+    pub(crate) retry_writes: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES as u32,
+            base_delay: Duration::from_millis(DEFAULT_BASE_DELAY_MS),
+            max_delay: Duration::from_millis(DEFAULT_MAX_DELAY_MS),
+            retry_writes: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub(crate) fn from_env() -> Self {
+        Self {
+            max_retries: parse_non_negative("DATALENS_MAX_RETRIES", DEFAULT_MAX_RETRIES)
+                .min(u64::from(u32::MAX)) as u32,
+            base_delay: Duration::from_millis(parse_non_negative(
+                "DATALENS_RETRY_BASE_DELAY_MS",
+                DEFAULT_BASE_DELAY_MS,
+            )),
+            max_delay: Duration::from_millis(parse_non_negative(
+                "DATALENS_RETRY_MAX_DELAY_MS",
+                DEFAULT_MAX_DELAY_MS,
+            )),
+            retry_writes: env_flag("DATALENS_RETRY_WRITES"),
+        }
+    }
+
+    /// Whether a method with this registry category may be sent more than once.
+    /// Methods missing from the registry are treated as writes.
+    pub(crate) fn allows(&self, category: Option<&str>) -> bool {
+        self.max_retries > 0 && (category == Some("read") || self.retry_writes)
+    }
+
+    /// Exponential backoff with equal jitter for the given retry (1-based).
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(16);
+        let ceiling = self
+            .base_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+        let half = ceiling / 2;
+        half + jitter(ceiling - half)
+    }
+}
+
+pub(crate) fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Connection failures and resets, where the request most likely never reached DataLens
+/// or the response was lost on the way back.
+pub(crate) fn is_transient_error(error: &reqwest::Error) -> bool {
+    if error.is_connect() {
+        return true;
+    }
+
+    let mut source = error.source();
+    while let Some(err) = source {
+        if let Some(io_error) = err.downcast_ref::<io::Error>()
+            && matches!(
+                io_error.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::UnexpectedEof
+            )
+        {
+            return true;
+        }
+        if err
+            .to_string()
+            .contains("connection closed before message completed")
+        {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Parses `Retry-After` as delay seconds or an HTTP date.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let at = DateTime::parse_from_rfc2822(value)
+        .ok()?
+        .with_timezone(&Utc);
+    Some((at - Utc::now()).to_std().unwrap_or(Duration::ZERO))
+}
+
+fn jitter(max: Duration) -> Duration {
+    let nanos = max.as_nanos().min(u128::from(u64::MAX)) as u64;
+    if nanos == 0 {
+        return Duration::ZERO;
+    }
+    let random = RandomState::new().build_hasher().finish();
+    Duration::from_nanos(random % (nanos + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn allows_reads_and_only_opted_in_writes() {
+        let policy = RetryPolicy::default();
+        assert!(policy.allows(Some("read")));
+        assert!(!policy.allows(Some("write")));
+        assert!(!policy.allows(None));
+
+        let policy = RetryPolicy {
+            retry_writes: true,
+            ..RetryPolicy::default()
+        };
+        assert!(policy.allows(Some("write")));
+
+        let policy = RetryPolicy {
+            max_retries: 0,
+            ..RetryPolicy::default()
+        };
+        assert!(!policy.allows(Some("read")));
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_within_jitter_bounds() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1_000),
+            ..RetryPolicy::default()
+        };
+        for _ in 0..20 {
+            let first = policy.backoff(1);
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+            let third = policy.backoff(3);
+            assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(400));
+            let capped = policy.backoff(10);
+            assert!(capped >= Duration::from_millis(500) && capped <= Duration::from_millis(1_000));
+        }
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_http_dates() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("3"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(3)));
+
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn retryable_statuses_are_throttling_and_gateway_errors() {
+        for status in [429, 502, 503, 504] {
+            assert!(is_retryable_status(StatusCode::from_u16(status).unwrap()));
+        }
+        for status in [400, 401, 404, 500] {
+            assert!(!is_retryable_status(StatusCode::from_u16(status).unwrap()));
+        }
+    }
+}
@@ -0,0 +1,521 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::warn;
+
+use crate::env_non_empty;
+
+const COMPONENT_REF_PREFIX: &str = "#/components/schemas/";
+const DEFINITION_REF_PREFIX: &str = "#/definitions/";
+/// Errors reported per payload; the rest are counted but not listed.
+pub(crate) const MAX_REPORTED_ERRORS: usize = 20;
+
+/// What `call_rpc` does when a payload does not match the method's request schema.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ValidationMode {
+    /// Reject the call with `invalid_params` before anything is sent.
+    Strict,
+    /// Log the mismatches and send the payload anyway. The default, since the registry
+    /// schemas may be stricter than what DataLens accepts.
+    #[default]
+    Warn,
+    /// Skip validation.
+    Off,
+}
+
+impl ValidationMode {
+    pub(crate) fn from_env() -> Self {
+        match env_non_empty("DATALENS_VALIDATION") {
+            Some(raw) => Self::parse(&raw).unwrap_or_else(|| {
+                warn!("DATALENS_VALIDATION must be strict, warn or off, got '{raw}'; using warn");
+                Self::Warn
+            }),
+            None => Self::Warn,
+        }
+    }
+
+    fn parse(raw: &str) -> Option<Self> {
+        match raw.to_ascii_lowercase().as_str() {
+            "strict" => Some(Self::Strict),
+            "warn" => Some(Self::Warn),
+            "off" | "0" | "false" => Some(Self::Off),
+            _ => None,
+        }
+    }
+}
+
+/// One mismatch between a payload and a schema, located by a JSON pointer into the payload.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct SchemaError {
+    pub(crate) path: String,
+    pub(crate) keyword: &'static str,
+    pub(crate) message: String,
+}
+
+/// Inlines `#/components/schemas/*` references so a method schema is usable on its own.
+///
+/// Recursive components cannot be inlined; they are kept as `#/definitions/<name>` refs and
+/// the referenced schemas are attached to the root under `definitions`. References to
+/// unknown components are left untouched.
+pub(crate) fn resolve_refs(schema: &Value, components: &Map<String, Value>) -> Value {
+    let mut resolver = Resolver {
+        components,
+        stack: Vec::new(),
+        recursive: BTreeSet::new(),
+    };
+    let mut resolved = resolver.resolve(schema);
+
+    let mut definitions = Map::new();
+    while let Some(name) = resolver
+        .recursive
+        .iter()
+        .find(|name| !definitions.contains_key(*name))
+        .cloned()
+    {
+        resolver.stack.push(name.clone());
+        let definition = resolver.resolve(&components[&name]);
+        resolver.stack.pop();
+        definitions.insert(name, definition);
+    }
+
+    if !definitions.is_empty()
+        && let Some(root) = resolved.as_object_mut()
+    {
+        root.insert("definitions".to_owned(), Value::Object(definitions));
+    }
+    resolved
+}
+
+struct Resolver<'a> {
+    components: &'a Map<String, Value>,
+    stack: Vec<String>,
+    recursive: BTreeSet<String>,
+}
+
+impl Resolver<'_> {
+    fn resolve(&mut self, schema: &Value) -> Value {
+        match schema {
+            Value::Object(object) => {
+                if let Some(name) = component_ref(object)
+                    && let Some(target) = self.components.get(name)
+                {
+                    return self.resolve_component(name, target, object);
+                }
+                Value::Object(
+                    object
+                        .iter()
+                        .map(|(key, value)| (key.clone(), self.resolve(value)))
+                        .collect(),
+                )
+            }
+            Value::Array(items) => {
+                Value::Array(items.iter().map(|item| self.resolve(item)).collect())
+            }
+            other => other.clone(),
+        }
+    }
+
+    fn resolve_component(
+        &mut self,
+        name: &str,
+        target: &Value,
+        object: &Map<String, Value>,
+    ) -> Value {
+        let mut resolved = if self.stack.iter().any(|open| open == name) {
+            self.recursive.insert(name.to_owned());
+            serde_json::json!({ "$ref": format!("{DEFINITION_REF_PREFIX}{name}") })
+        } else {
+            self.stack.push(name.to_owned());
+            let resolved = self.resolve(target);
+            self.stack.pop();
+            resolved
+        };
+
+        // OpenAPI 3.1 allows keywords such as `description` next to `$ref`; keep them.
+        if let Some(resolved) = resolved.as_object_mut() {
+            for (key, value) in object.iter().filter(|(key, _)| key.as_str() != "$ref") {
+                resolved.insert(key.clone(), self.resolve(value));
+            }
+        }
+        resolved
+    }
+}
+
+/// Checks `instance` against a schema produced by [`resolve_refs`].
+///
+/// Covers `type`, `enum`, `const`, `required`, `properties`, `additionalProperties`, `items`
+/// and the `allOf` / `anyOf` / `oneOf` combinators; format and range keywords are left to
+/// DataLens. A payload matching several `oneOf` variants is accepted.
+pub(crate) fn validate(schema: &Value, instance: &Value) -> Vec<SchemaError> {
+    let validator = Validator {
+        definitions: schema.get("definitions").and_then(Value::as_object),
+    };
+    let mut errors = Vec::new();
+    validator.check(schema, instance, "", &mut errors);
+    errors
+}
+
+struct Validator<'a> {
+    definitions: Option<&'a Map<String, Value>>,
+}
+
+impl Validator<'_> {
+    fn check(&self, schema: &Value, instance: &Value, path: &str, errors: &mut Vec<SchemaError>) {
+        let Some(schema) = schema.as_object() else {
+            if schema == &Value::Bool(false) {
+                errors.push(error(path, "false", "no value is allowed here".to_owned()));
+            }
+            return;
+        };
+
+        if let Some(name) = schema
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|reference| reference.strip_prefix(DEFINITION_REF_PREFIX))
+            && let Some(target) = self
+                .definitions
+                .and_then(|definitions| definitions.get(name))
+        {
+            self.check(target, instance, path, errors);
+        }
+
+        if let Some(expected) = schema.get("type")
+            && !type_matches(expected, instance)
+        {
+            errors.push(error(
+                path,
+                "type",
+                format!(
+                    "expected {}, got {}",
+                    describe_type(expected),
+                    type_name(instance)
+                ),
+            ));
+            return;
+        }
+
+        if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+            && !allowed.contains(instance)
+        {
+            errors.push(error(
+                path,
+                "enum",
+                format!("must be one of {}", Value::Array(allowed.clone())),
+            ));
+        }
+        if let Some(expected) = schema.get("const")
+            && expected != instance
+        {
+            errors.push(error(path, "const", format!("must be {expected}")));
+        }
+
+        if let Some(object) = instance.as_object() {
+            self.check_object(schema, object, path, errors);
+        }
+        if let (Some(items), Some(array)) = (schema.get("items"), instance.as_array()) {
+            for (index, item) in array.iter().enumerate() {
+                self.check(items, item, &format!("{path}/{index}"), errors);
+            }
+        }
+
+        if let Some(all_of) = schema.get("allOf").and_then(Value::as_array) {
+            for branch in all_of {
+                self.check(branch, instance, path, errors);
+            }
+        }
+        for keyword in ["anyOf", "oneOf"] {
+            if let Some(branches) = schema.get(keyword).and_then(Value::as_array) {
+                self.check_variants(branches, instance, path, errors);
+            }
+        }
+    }
+
+    fn check_object(
+        &self,
+        schema: &Map<String, Value>,
+        object: &Map<String, Value>,
+        path: &str,
+        errors: &mut Vec<SchemaError>,
+    ) {
+        let properties = schema.get("properties").and_then(Value::as_object);
+
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    errors.push(error(
+                        &child_path(path, name),
+                        "required",
+                        format!("missing required property '{name}'"),
+                    ));
+                }
+            }
+        }
+
+        for (name, value) in object {
+            let child = child_path(path, name);
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(property) => self.check(property, value, &child, errors),
+                // Without a regex engine, names matched by `patternProperties` can't be told apart.
+                None if schema.contains_key("patternProperties") => {}
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => errors.push(error(
+                        &child,
+                        "additionalProperties",
+                        format!("property '{name}' is not allowed"),
+                    )),
+                    Some(additional) => self.check(additional, value, &child, errors),
+                    None => {}
+                },
+            }
+        }
+    }
+
+    /// Accepts the instance if any variant matches; otherwise reports the closest variant,
+    /// preferring variants whose discriminating `type`/`enum`/`const` values match.
+    fn check_variants(
+        &self,
+        branches: &[Value],
+        instance: &Value,
+        path: &str,
+        errors: &mut Vec<SchemaError>,
+    ) {
+        let mut closest: Option<((bool, usize), Vec<SchemaError>)> = None;
+        for branch in branches {
+            let mut branch_errors = Vec::new();
+            self.check(branch, instance, path, &mut branch_errors);
+            if branch_errors.is_empty() {
+                return;
+            }
+            let rank = (
+                branch_errors
+                    .iter()
+                    .any(|error| matches!(error.keyword, "type" | "enum" | "const")),
+                branch_errors.len(),
+            );
+            if closest.as_ref().is_none_or(|(best, _)| rank < *best) {
+                closest = Some((rank, branch_errors));
+            }
+        }
+        errors.extend(closest.map(|(_, errors)| errors).unwrap_or_default());
+    }
+}
+
+fn type_matches(expected: &Value, instance: &Value) -> bool {
+    match expected {
+        Value::String(name) => type_is(name, instance),
+        Value::Array(names) => names
+            .iter()
+            .filter_map(Value::as_str)
+            .any(|name| type_is(name, instance)),
+        _ => true,
+    }
+}
+
+fn type_is(name: &str, instance: &Value) -> bool {
+    match name {
+        "object" => instance.is_object(),
+        "array" => instance.is_array(),
+        "string" => instance.is_string(),
+        "boolean" => instance.is_boolean(),
+        "null" => instance.is_null(),
+        "number" => instance.is_number(),
+        "integer" => {
+            instance.is_i64()
+                || instance.is_u64()
+                || instance.as_f64().is_some_and(|value| value.fract() == 0.0)
+        }
+        _ => true,
+    }
+}
+
+fn describe_type(expected: &Value) -> String {
+    match expected {
+        Value::Array(names) => names
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" or "),
+        other => other.as_str().unwrap_or("any").to_owned(),
+    }
+}
+
+fn type_name(instance: &Value) -> &'static str {
+    match instance {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn child_path(path: &str, name: &str) -> String {
+    format!("{path}/{}", name.replace('~', "~0").replace('/', "~1"))
+}
+
+fn error(path: &str, keyword: &'static str, message: String) -> SchemaError {
+    SchemaError {
+        path: path.to_owned(),
+        keyword,
+        message,
+    }
+}
+
+fn component_ref(object: &Map<String, Value>) -> Option<&str> {
+    object
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|reference| reference.strip_prefix(COMPONENT_REF_PREFIX))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn components(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn resolve_refs_inlines_nested_components() {
+        let components = components(json!({
+            "Args": {
+                "type": "object",
+                "properties": { "dataset": { "$ref": "#/components/schemas/Dataset" } }
+            },
+            "Dataset": { "type": "object", "properties": { "title": { "type": "string" } } }
+        }));
+
+        let resolved = resolve_refs(
+            &json!({ "$ref": "#/components/schemas/Args", "description": "Create" }),
+            &components,
+        );
+        assert_eq!(
+            resolved,
+            json!({
+                "type": "object",
+                "description": "Create",
+                "properties": {
+                    "dataset": { "type": "object", "properties": { "title": { "type": "string" } } }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn resolve_refs_moves_recursive_components_to_definitions() {
+        let components = components(json!({
+            "Node": {
+                "type": "object",
+                "properties": {
+                    "children": { "type": "array", "items": { "$ref": "#/components/schemas/Node" } }
+                }
+            }
+        }));
+
+        let resolved = resolve_refs(&json!({ "$ref": "#/components/schemas/Node" }), &components);
+        assert_eq!(
+            resolved["properties"]["children"]["items"],
+            json!({ "$ref": "#/definitions/Node" })
+        );
+        assert_eq!(
+            resolved["definitions"]["Node"]["properties"]["children"]["items"],
+            json!({ "$ref": "#/definitions/Node" })
+        );
+    }
+
+    #[test]
+    fn validate_reports_json_pointer_paths() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "datasetId": { "type": "string" },
+                "data": {
+                    "type": "object",
+                    "properties": {
+                        "fields": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "calc_mode": { "type": "string", "enum": ["direct", "formula"] },
+                                    "a/b": { "type": "integer" }
+                                },
+                                "required": ["guid"]
+                            }
+                        }
+                    }
+                }
+            },
+            "required": ["datasetId"],
+            "additionalProperties": false
+        });
+        let payload = json!({
+            "data": { "fields": [{ "guid": "g", "calc_mode": "sql", "a/b": 1.5 }, {}] },
+            "dataset_id": "ds-1"
+        });
+
+        let errors = validate(&schema, &payload);
+        let found: Vec<_> = errors
+            .iter()
+            .map(|error| (error.path.as_str(), error.keyword))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("/datasetId", "required"),
+                ("/data/fields/0/a~1b", "type"),
+                ("/data/fields/0/calc_mode", "enum"),
+                ("/data/fields/1/guid", "required"),
+                ("/dataset_id", "additionalProperties"),
+            ]
+        );
+        assert_eq!(errors[1].message, "expected integer, got number");
+    }
+
+    #[test]
+    fn validate_accepts_any_matching_variant_and_nullable_types() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "workbookId": { "type": ["string", "null"] },
+                "item": {
+                    "oneOf": [
+                        { "type": "object", "properties": { "kind": { "enum": ["text"] } }, "required": ["kind", "text"] },
+                        { "type": "object", "properties": { "kind": { "enum": ["image"] } }, "required": ["kind", "src", "alt"] }
+                    ]
+                }
+            }
+        });
+
+        let valid =
+            json!({ "workbookId": null, "item": { "kind": "image", "src": "a.png", "alt": "" } });
+        assert!(validate(&schema, &valid).is_empty());
+
+        // The variant whose `kind` matches is reported even though it has more errors.
+        let invalid = json!({ "workbookId": 7, "item": { "kind": "image", "text": "" } });
+        let errors = validate(&schema, &invalid);
+        let paths: Vec<_> = errors.iter().map(|error| error.path.as_str()).collect();
+        assert_eq!(paths, ["/item/src", "/item/alt", "/workbookId"]);
+        assert_eq!(errors[2].message, "expected string or null, got integer");
+    }
+
+    #[test]
+    fn validation_mode_parses_names() {
+        assert_eq!(ValidationMode::parse("WARN"), Some(ValidationMode::Warn));
+        assert_eq!(ValidationMode::parse("off"), Some(ValidationMode::Off));
+        assert_eq!(ValidationMode::parse("lenient"), None);
+    }
+
+    #[test]
+    fn resolve_refs_keeps_unknown_references() {
+        let schema = json!({ "properties": { "x": { "$ref": "#/components/schemas/Missing" } } });
+        assert_eq!(resolve_refs(&schema, &Map::new()), schema);
+    }
+}
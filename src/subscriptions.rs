@@ -0,0 +1,380 @@
+//! `resources/subscribe`: background polling for changes to watched DataLens objects.
+//!
+//! Each MCP session keeps its own watched URIs, the peer to notify and, over HTTP, the
+//! caller's credentials. A single task polls every session on a fixed interval, first with
+//! `getAuditEntriesUpdates` and, if that method is unavailable to the session's token, by
+//! comparing revision IDs returned by the resources' own get calls.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{
+        Arc, Mutex, Weak,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use ring::digest;
+use rmcp::{ErrorData as McpError, Peer, RoleServer};
+use serde_json::Value;
+
+use crate::{RequestScope, parse_non_negative, resources::ResourceRef};
+
+/// Default interval between two polls of watched resources.
+pub(crate) const DEFAULT_POLL_SECONDS: u64 = 60;
+/// `getAuditEntriesUpdates` pages read per poll; the next poll resumes at the following page.
+pub(crate) const MAX_UPDATE_PAGES: usize = 10;
+pub(crate) const UPDATES_PAGE_LIMIT: u64 = 200;
+
+/// `DATALENS_SUBSCRIPTION_POLL_SECONDS`; `0` turns subscriptions off.
+pub(crate) fn poll_interval_from_env() -> Option<Duration> {
+    let seconds = parse_non_negative("DATALENS_SUBSCRIPTION_POLL_SECONDS", DEFAULT_POLL_SECONDS);
+    (seconds > 0).then(|| Duration::from_secs(seconds))
+}
+
+/// Sessions with subscriptions, shared by all sessions of the server.
+pub(crate) struct Subscriptions {
+    interval: Duration,
+    sessions: Mutex<Vec<Weak<Session>>>,
+    polling: AtomicBool,
+}
+
+impl Subscriptions {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            sessions: Mutex::new(Vec::new()),
+            polling: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Adds `session` to the polled sessions; returns `true` only for the first call, which
+    /// should start the polling task.
+    pub(crate) fn register(&self, session: &Arc<Session>) -> bool {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|err| err.into_inner());
+        if !sessions
+            .iter()
+            .any(|known| std::ptr::eq(known.as_ptr(), Arc::as_ptr(session)))
+        {
+            sessions.push(Arc::downgrade(session));
+        }
+        !self.polling.swap(true, Ordering::Relaxed)
+    }
+
+    /// Sessions that are still connected, dropping the rest.
+    pub(crate) fn live_sessions(&self) -> Vec<Arc<Session>> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|err| err.into_inner());
+        sessions.retain(|session| {
+            session
+                .upgrade()
+                .is_some_and(|session| !session.is_closed())
+        });
+        sessions.iter().filter_map(Weak::upgrade).collect()
+    }
+}
+
+/// What one MCP session watches.
+#[derive(Default)]
+pub(crate) struct Session {
+    state: Mutex<SessionState>,
+}
+
+#[derive(Default)]
+struct SessionState {
+    peer: Option<Peer<RoleServer>>,
+    scope: Option<RequestScope>,
+    watched: BTreeMap<String, Watched>,
+    /// Start of the next `getAuditEntriesUpdates` window.
+    since: Option<DateTime<Utc>>,
+    /// Page of the current window to resume at, when a poll stopped at `MAX_UPDATE_PAGES`.
+    page_token: Option<String>,
+    /// When the first poll of a window that spans several polls started.
+    window_started: Option<DateTime<Utc>>,
+    /// Set once `getAuditEntriesUpdates` turned out to be unavailable; revision IDs are
+    /// compared from then on.
+    revisions: bool,
+}
+
+struct Watched {
+    target: ResourceRef,
+    fingerprint: Option<String>,
+}
+
+/// The parts of a session one poll needs, copied out so no lock is held across calls.
+pub(crate) struct PollPlan {
+    pub(crate) peer: Peer<RoleServer>,
+    pub(crate) scope: RequestScope,
+    pub(crate) targets: Vec<(String, ResourceRef)>,
+    pub(crate) since: DateTime<Utc>,
+    pub(crate) page_token: Option<String>,
+    pub(crate) revisions: bool,
+}
+
+impl Session {
+    fn state(&self) -> std::sync::MutexGuard<'_, SessionState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state()
+            .peer
+            .as_ref()
+            .is_some_and(Peer::is_transport_closed)
+    }
+
+    pub(crate) fn subscribe(
+        &self,
+        uri: String,
+        target: ResourceRef,
+        peer: Peer<RoleServer>,
+        scope: RequestScope,
+        now: DateTime<Utc>,
+    ) {
+        let mut state = self.state();
+        state.peer = Some(peer);
+        state.scope = Some(scope);
+        if state.watched.is_empty() {
+            state.since = Some(now);
+            state.page_token = None;
+            state.window_started = None;
+        }
+        state.watched.entry(uri).or_insert(Watched {
+            target,
+            fingerprint: None,
+        });
+    }
+
+    pub(crate) fn unsubscribe(&self, uri: &str) {
+        self.state().watched.remove(uri);
+    }
+
+    pub(crate) fn plan(&self) -> Option<PollPlan> {
+        let state = self.state();
+        if state.watched.is_empty() {
+            return None;
+        }
+        Some(PollPlan {
+            peer: state.peer.clone()?,
+            scope: state.scope.clone()?,
+            targets: state
+                .watched
+                .iter()
+                .map(|(uri, watched)| (uri.clone(), watched.target.clone()))
+                .collect(),
+            since: state.since?,
+            page_token: state.page_token.clone(),
+            revisions: state.revisions,
+        })
+    }
+
+    /// Records a `getAuditEntriesUpdates` poll that started at `started`. While pages are
+    /// left, the window stays open and the next poll resumes at `next_page_token`. Once it is
+    /// read to the end, the next window starts when the window's first poll started.
+    pub(crate) fn record_updates(&self, started: DateTime<Utc>, next_page_token: Option<String>) {
+        let mut state = self.state();
+        let window_started = *state.window_started.get_or_insert(started);
+        state.page_token = next_page_token;
+        if state.page_token.is_none() {
+            state.since = Some(window_started);
+            state.window_started = None;
+        }
+    }
+
+    pub(crate) fn use_revisions(&self) {
+        self.state().revisions = true;
+    }
+
+    /// Records the latest fingerprint of `uri`; `true` when it differs from a known earlier one.
+    pub(crate) fn record_fingerprint(&self, uri: &str, fingerprint: String) -> bool {
+        let mut state = self.state();
+        let Some(watched) = state.watched.get_mut(uri) else {
+            return false;
+        };
+        let previous = watched.fingerprint.replace(fingerprint);
+        previous.is_some_and(|previous| Some(&previous) != watched.fingerprint.as_ref())
+    }
+}
+
+/// Whether a `getAuditEntriesUpdates` failure means the method is unavailable to the
+/// session: DataLens answered 403 or 404, or a method policy denied the call. Other failures
+/// are retried on the next poll.
+pub(crate) fn updates_unavailable(error: &McpError) -> bool {
+    let Some(data) = &error.data else {
+        return false;
+    };
+    matches!(data.get("status").and_then(Value::as_u64), Some(403 | 404))
+        || data.get("policy").is_some()
+}
+
+/// Watched URIs touched by `getAuditEntriesUpdates` entries: entries match by `entryId`,
+/// workbooks by the `workbookId` of any changed entry in them.
+pub(crate) fn updated_uris(targets: &[(String, ResourceRef)], entries: &[Value]) -> Vec<String> {
+    let mut entry_ids = BTreeSet::new();
+    let mut workbook_ids = BTreeSet::new();
+    for entry in entries {
+        if let Some(id) = entry.get("entryId").and_then(Value::as_str) {
+            entry_ids.insert(id);
+        }
+        if let Some(id) = entry.get("workbookId").and_then(Value::as_str) {
+            workbook_ids.insert(id);
+        }
+    }
+
+    targets
+        .iter()
+        .filter(|(_, target)| match target {
+            ResourceRef::Workbook(id) => workbook_ids.contains(id.as_str()),
+            ResourceRef::Dataset(id)
+            | ResourceRef::Dashboard(id)
+            | ResourceRef::Chart(_, id)
+            | ResourceRef::Connection(id) => entry_ids.contains(id.as_str()),
+        })
+        .map(|(uri, _)| uri.clone())
+        .collect()
+}
+
+/// A value that changes whenever the object does: its revision ID when the response has
+/// one, otherwise a hash of the whole response.
+pub(crate) fn fingerprint(response: &Value) -> String {
+    let revision = [
+        "/entry/revId",
+        "/entry/savedId",
+        "/dataset/revision_id",
+        "/revId",
+        "/savedId",
+        "/updatedAt",
+    ]
+    .iter()
+    .find_map(|pointer| response.pointer(pointer).and_then(Value::as_str));
+    if let Some(revision) = revision {
+        return revision.to_owned();
+    }
+    let digest = digest::digest(&digest::SHA256, response.to_string().as_bytes());
+    digest
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::resources::ChartKind;
+
+    #[test]
+    fn audit_updates_match_entries_and_workbooks() {
+        let targets = vec![
+            (
+                "datalens://dashboard/d-1".to_owned(),
+                ResourceRef::Dashboard("d-1".to_owned()),
+            ),
+            (
+                "datalens://chart/wizard/c-1".to_owned(),
+                ResourceRef::Chart(ChartKind::Wizard, "c-1".to_owned()),
+            ),
+            (
+                "datalens://workbook/wb-1".to_owned(),
+                ResourceRef::Workbook("wb-1".to_owned()),
+            ),
+        ];
+        let entries = [
+            json!({"entryId": "c-1", "workbookId": "wb-2", "scope": "widget"}),
+            json!({"entryId": "ds-9", "workbookId": "wb-1", "scope": "dataset", "isDeleted": true}),
+        ];
+
+        assert_eq!(
+            updated_uris(&targets, &entries),
+            ["datalens://chart/wizard/c-1", "datalens://workbook/wb-1"]
+        );
+    }
+
+    #[test]
+    fn first_fingerprint_is_a_baseline() {
+        let session = Session::default();
+        session.state().watched.insert(
+            "datalens://dataset/ds-1".to_owned(),
+            Watched {
+                target: ResourceRef::Dataset("ds-1".to_owned()),
+                fingerprint: None,
+            },
+        );
+
+        assert!(!session.record_fingerprint("datalens://dataset/ds-1", "r-1".to_owned()));
+        assert!(!session.record_fingerprint("datalens://dataset/ds-1", "r-1".to_owned()));
+        assert!(session.record_fingerprint("datalens://dataset/ds-1", "r-2".to_owned()));
+        assert!(!session.record_fingerprint("datalens://dataset/ds-2", "r-1".to_owned()));
+
+        session.unsubscribe("datalens://dataset/ds-1");
+        assert!(session.plan().is_none());
+    }
+
+    #[test]
+    fn only_forbidden_missing_or_denied_updates_are_unavailable() {
+        let api_error = |status: u16| {
+            McpError::internal_error(
+                "DataLens API returned an error",
+                Some(json!({"method": "getAuditEntriesUpdates", "status": status})),
+            )
+        };
+        assert!(updates_unavailable(&api_error(403)));
+        assert!(updates_unavailable(&api_error(404)));
+        assert!(!updates_unavailable(&api_error(500)));
+        assert!(!updates_unavailable(&api_error(429)));
+        assert!(!updates_unavailable(&McpError::internal_error(
+            "failed to reach DataLens API: timed out",
+            Some(json!({"method": "getAuditEntriesUpdates", "attempts": 4})),
+        )));
+        assert!(updates_unavailable(&McpError::invalid_request(
+            "getAuditEntriesUpdates is denied",
+            Some(json!({"method": "getAuditEntriesUpdates", "policy": "/etc/policy.toml"})),
+        )));
+    }
+
+    #[test]
+    fn update_window_stays_open_until_every_page_is_read() {
+        let at = |time: &str| {
+            DateTime::parse_from_rfc3339(time)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let session = Session::default();
+        session.state().since = Some(at("2026-10-16T09:00:00Z"));
+
+        session.record_updates(at("2026-10-16T09:01:00Z"), Some("p-11".to_owned()));
+        session.record_updates(at("2026-10-16T09:02:00Z"), Some("p-21".to_owned()));
+        {
+            let state = session.state();
+            assert_eq!(state.since, Some(at("2026-10-16T09:00:00Z")));
+            assert_eq!(state.page_token.as_deref(), Some("p-21"));
+        }
+
+        session.record_updates(at("2026-10-16T09:03:00Z"), None);
+        let state = session.state();
+        assert_eq!(state.since, Some(at("2026-10-16T09:01:00Z")));
+        assert!(state.page_token.is_none());
+        assert!(state.window_started.is_none());
+    }
+
+    #[test]
+    fn fingerprints_prefer_revision_ids() {
+        assert_eq!(
+            fingerprint(&json!({"entry": {"revId": "r-2", "savedId": "s-1"}})),
+            "r-2"
+        );
+        assert_eq!(
+            fingerprint(&json!({"id": "ds-1", "dataset": {"revision_id": "r-7"}})),
+            "r-7"
+        );
+        let hashed = fingerprint(&json!({"id": "conn-1", "host": "a"}));
+        assert_eq!(hashed.len(), 64);
+        assert_ne!(hashed, fingerprint(&json!({"id": "conn-1", "host": "b"})));
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::debug;
+
+use crate::{env_non_empty, parse_non_negative};
+
+const DEFAULT_MAX_IN_FLIGHT: u64 = 8;
+
+/// Client-side limits on requests sent to DataLens for one profile.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ThrottleConfig {
+    /// Sustained request rate; `0` disables the rate limiter.
+    pub(crate) requests_per_second: f64,
+    /// Requests allowed back to back before the rate applies.
+    pub(crate) burst: u32,
+    /// Concurrent requests; `0` means unlimited.
+    pub(crate) max_in_flight: usize,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 0.0,
+            burst: 1,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT as usize,
+        }
+    }
+}
+
+impl ThrottleConfig {
+    pub(crate) fn from_env() -> Self {
+        let requests_per_second = match env_non_empty("DATALENS_RATE_LIMIT_RPS") {
+            Some(raw) => match raw.parse::<f64>() {
+                Ok(value) if value.is_finite() && value >= 0.0 => value,
+                _ => {
+                    tracing::warn!(
+                        "DATALENS_RATE_LIMIT_RPS must be a non-negative number, got '{raw}'; rate limiting is disabled"
+                    );
+                    0.0
+                }
+            },
+            None => 0.0,
+        };
+
+        Self {
+            requests_per_second,
+            burst: parse_non_negative(
+                "DATALENS_RATE_LIMIT_BURST",
+                default_burst(requests_per_second),
+            )
+            .clamp(1, u64::from(u32::MAX)) as u32,
+            max_in_flight: parse_non_negative("DATALENS_MAX_IN_FLIGHT", DEFAULT_MAX_IN_FLIGHT)
+                as usize,
+        }
+    }
+}
+
+pub(crate) fn default_burst(requests_per_second: f64) -> u64 {
+    requests_per_second.ceil().max(1.0) as u64
+}
+
+/// Token bucket plus in-flight cap. Callers wait in line instead of being rejected.
+pub(crate) struct Throttle {
+    in_flight: Option<Arc<Semaphore>>,
+    bucket: Option<Mutex<TokenBucket>>,
+}
+
+/// Keeps an in-flight slot taken until dropped.
+pub(crate) struct ThrottlePermit {
+    _in_flight: Option<OwnedSemaphorePermit>,
+}
+
+impl Throttle {
+    pub(crate) fn new(cfg: &ThrottleConfig) -> Self {
+        Self {
+            in_flight: (cfg.max_in_flight > 0).then(|| Arc::new(Semaphore::new(cfg.max_in_flight))),
+            bucket: (cfg.requests_per_second > 0.0).then(|| {
+                Mutex::new(TokenBucket::new(
+                    cfg.requests_per_second,
+                    cfg.burst,
+                    Instant::now(),
+                ))
+            }),
+        }
+    }
+
+    /// Waits for an in-flight slot and a rate-limit token, logging the time spent queued.
+    pub(crate) async fn acquire(&self, profile: &str, method: &str) -> ThrottlePermit {
+        let queued_at = Instant::now();
+
+        let in_flight = match &self.in_flight {
+            Some(semaphore) => Arc::clone(semaphore).acquire_owned().await.ok(),
+            None => None,
+        };
+        if let Some(bucket) = &self.bucket {
+            let wait = bucket
+                .lock()
+                .map(|mut bucket| bucket.reserve(Instant::now()))
+                .unwrap_or_default();
+            if !wait.is_zero() {
+                tokio::time::sleep(wait).await;
+            }
+        }
+
+        let waited = queued_at.elapsed();
+        if waited >= Duration::from_millis(1) {
+            debug!(
+                profile = %profile,
+                method = %method,
+                queue_wait_ms = waited.as_millis() as u64,
+                "DataLens request waited in the client-side queue"
+            );
+        }
+
+        ThrottlePermit {
+            _in_flight: in_flight,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, burst: u32, now: Instant) -> Self {
+        Self {
+            rate,
+            capacity: f64::from(burst),
+            tokens: f64::from(burst),
+            updated: now,
+        }
+    }
+
+    /// Takes one token, possibly on credit, and returns how long to wait until it is valid.
+    /// Borrowing ahead keeps concurrent waiters in arrival order.
+    fn reserve(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+        self.tokens -= 1.0;
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_bucket_allows_burst_then_spaces_requests() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2.0, 2, start);
+
+        assert_eq!(bucket.reserve(start), Duration::ZERO);
+        assert_eq!(bucket.reserve(start), Duration::ZERO);
+        assert_eq!(bucket.reserve(start), Duration::from_millis(500));
+        assert_eq!(bucket.reserve(start), Duration::from_millis(1_000));
+
+        // After two idle seconds the debt is paid back and the burst is available again.
+        let later = start + Duration::from_secs(2);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+        assert_eq!(bucket.reserve(later), Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn throttle_caps_requests_in_flight() {
+        let throttle = Throttle::new(&ThrottleConfig {
+            max_in_flight: 1,
+            ..ThrottleConfig::default()
+        });
+
+        let first = throttle.acquire("default", "getEntries").await;
+        let second = tokio::time::timeout(
+            Duration::from_millis(50),
+            throttle.acquire("default", "getEntries"),
+        )
+        .await;
+        assert!(
+            second.is_err(),
+            "second request must queue behind the first"
+        );
+
+        drop(first);
+        tokio::time::timeout(
+            Duration::from_millis(50),
+            throttle.acquire("default", "getEntries"),
+        )
+        .await
+        .expect("slot must be released when the permit is dropped");
+    }
+
+    #[test]
+    fn default_burst_rounds_rate_up() {
+        assert_eq!(default_burst(0.0), 1);
+        assert_eq!(default_burst(0.5), 1);
+        assert_eq!(default_burst(4.2), 5);
+    }
+}
@@ -114,6 +114,7 @@ fn kill_child(child: &mut Child) {
 }
 
 #[test]
+#[allow(clippy::explicit_counter_loop)]
 fn datalens_get_method_schema_covers_every_listed_method() -> Result<()> {
     let mut child = Command::new(env!("CARGO_BIN_EXE_datalens-mcp"))
         .stdin(Stdio::piped())
@@ -180,6 +181,12 @@ fn datalens_get_method_schema_covers_every_listed_method() -> Result<()> {
                 schema.get("requestSchema").is_some_and(Value::is_object),
                 "method {method_name} must return object requestSchema"
             );
+            assert!(
+                !schema["requestSchema"]
+                    .to_string()
+                    .contains("#/components/schemas/"),
+                "method {method_name} must return a dereferenced requestSchema"
+            );
             assert!(
                 schema.get("requestExample").is_some(),
                 "method {method_name} must return requestExample from embedded snapshot"